@@ -0,0 +1,254 @@
+use crate::ast::*;
+
+// Semantics of u8 arithmetic, shared by every pass that evaluates operators.
+
+// How plain binary operators (`+`, `-`, `*`, `/`) behave when their result does
+// not fit in a u8. Shifts overflow when shifting by 8 or more bits, and have no saturating form.
+// Bitwise operators (`&`, `|`, `^`) never overflow.
+//  - Error: constant evaluation that overflows aborts compilation (the default)
+//  - Panic: debug build semantics, the operation panics at runtime
+//  - Wrap: release build semantics, the result wraps around
+//  - Saturate: the result is clamped to the bounds of u8 (shifts panic like in debug builds)
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum OverflowMode {
+    Error,
+    Panic,
+    Wrap,
+    Saturate,
+}
+
+impl OverflowMode {
+    pub const NAMES: [&'static str; 4] = ["error", "panic", "wrap", "saturate"];
+
+    pub fn from_name(name: &str) -> Option<OverflowMode> {
+        match name {
+            "error" => Some(OverflowMode::Error),
+            "panic" => Some(OverflowMode::Panic),
+            "wrap" => Some(OverflowMode::Wrap),
+            "saturate" => Some(OverflowMode::Saturate),
+            _ => None,
+        }
+    }
+}
+
+// How constant expressions are evaluated
+//  - Exact: every operation has to fit in u8, like rustc's arithmetic_overflow lint (the default)
+//  - Mathematical: a constant expression is evaluated over the integers and only its result has to fit in u8
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum EvaluationMode {
+    Exact,
+    Mathematical,
+}
+
+impl EvaluationMode {
+    pub const NAMES: [&'static str; 2] = ["exact", "mathematical"];
+
+    pub fn from_name(name: &str) -> Option<EvaluationMode> {
+        match name {
+            "exact" => Some(EvaluationMode::Exact),
+            "mathematical" => Some(EvaluationMode::Mathematical),
+            _ => None,
+        }
+    }
+}
+
+// The reasons a u8 operation can panic at runtime
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Panic {
+    // 250u8 + 10u8
+    Overflow(Operator),
+    // 1u8 / 0u8
+    DivisionByZero,
+    // 250u8.checked_add(10u8).unwrap()
+    Unwrap,
+}
+
+impl Panic {
+    // The message Rust prints when the panic happens
+    pub fn message(&self) -> &'static str {
+        match self {
+            Panic::Overflow(Operator::Add) => "attempt to add with overflow",
+            Panic::Overflow(Operator::Subtract) => "attempt to subtract with overflow",
+            Panic::Overflow(Operator::Multiply) => "attempt to multiply with overflow",
+            Panic::Overflow(Operator::Divide) => "attempt to divide with overflow",
+            Panic::Overflow(Operator::ShiftLeft) => "attempt to shift left with overflow",
+            Panic::Overflow(Operator::ShiftRight) => "attempt to shift right with overflow",
+            Panic::Overflow(Operator::BitAnd | Operator::BitOr | Operator::BitXor) => unreachable!("bitwise operations cannot overflow"),
+            Panic::DivisionByZero => "attempt to divide by zero",
+            Panic::Unwrap => "called `Option::unwrap()` on a `None` value",
+        }
+    }
+}
+
+// Evaluates a plain binary operator under the given overflow mode
+pub fn evaluate_operator(mode: OverflowMode, operator: &Operator, left: u8, right: u8) -> Result<u8, Panic> {
+    if *operator == Operator::Divide && right == 0 {
+        return Err(Panic::DivisionByZero);
+    }
+
+    match mode {
+        OverflowMode::Error | OverflowMode::Panic => {
+            checked(operator, left, right).ok_or(Panic::Overflow(operator.clone()))
+        }
+        OverflowMode::Wrap => Ok(overflowing(operator, left, right).0),
+        OverflowMode::Saturate if matches!(operator, Operator::ShiftLeft | Operator::ShiftRight) => {
+            checked(operator, left, right).ok_or(Panic::Overflow(operator.clone()))
+        }
+        OverflowMode::Saturate => Ok(saturating(operator, left, right)),
+    }
+}
+
+// Evaluates an integer method call, e.g. `left.wrapping_add(right)`
+pub fn evaluate_method(method: &Method, left: u8, right: u8) -> Result<u8, Panic> {
+    let operator = &method.operator;
+
+    if *operator == Operator::Divide && right == 0 {
+        // checked_div returns None instead of panicking, the unwrap panics instead
+        return match method.kind {
+            MethodKind::Checked => Err(Panic::Unwrap),
+            _ => Err(Panic::DivisionByZero),
+        };
+    }
+
+    match method.kind {
+        MethodKind::Wrapping | MethodKind::Overflowing => Ok(overflowing(operator, left, right).0),
+        MethodKind::Saturating => Ok(saturating(operator, left, right)),
+        MethodKind::Checked => checked(operator, left, right).ok_or(Panic::Unwrap),
+        MethodKind::OverflowFlag => Ok(overflowing(operator, left, right).1 as u8),
+    }
+}
+
+// Evaluates a plain binary operator over the integers, using i128 as the wide domain.
+// Division truncates towards zero and shifts are multiplications or divisions by powers of two.
+// Returns None on division by zero, on shifts by 8 bits or more, on bitwise operations on values
+// that do not fit in u8, or if the result leaves the domain.
+pub fn evaluate_wide(operator: &Operator, left: i128, right: i128) -> Option<i128> {
+    match operator {
+        Operator::Add => left.checked_add(right),
+        Operator::Subtract => left.checked_sub(right),
+        Operator::Multiply => left.checked_mul(right),
+        Operator::Divide => left.checked_div(right),
+        Operator::ShiftLeft | Operator::ShiftRight if !(0..8).contains(&right) => None,
+        Operator::ShiftLeft => left.checked_mul(1 << right),
+        Operator::ShiftRight => left.checked_div(1 << right),
+        // Bitwise operations are only defined on u8 values
+        Operator::BitAnd | Operator::BitOr | Operator::BitXor => {
+            let left = u8::try_from(left).ok()?;
+            let right = u8::try_from(right).ok()?;
+            Some(bitwise(operator, left, right) as i128)
+        }
+    }
+}
+
+// Whether evaluating an expression may panic at runtime under the given overflow mode.
+// This is conservative: only operations that provably cannot panic are considered safe.
+pub fn may_panic(mode: OverflowMode, expression: &Expression) -> bool {
+    match expression {
+        Expression::Binary { left, operator, right } => {
+            value_may_panic(mode, left)
+                || may_panic(mode, right)
+                || operation_may_panic(mode, None, operator, left.as_integer(), right.as_integer())
+        }
+        Expression::Value(value) => value_may_panic(mode, value),
+    }
+}
+
+pub fn value_may_panic(mode: OverflowMode, value: &Value) -> bool {
+    match value {
+        Value::Integer(_) | Value::Identifier(_) => false,
+        Value::Expression(expression) => may_panic(mode, expression),
+        Value::MethodCall { receiver, method, argument } => {
+            value_may_panic(mode, receiver)
+                || may_panic(mode, argument)
+                || operation_may_panic(mode, Some(method), &method.operator, receiver.as_integer(), argument.as_integer())
+        }
+    }
+}
+
+// Whether a single operation may panic, given the operands that are known constants
+fn operation_may_panic(mode: OverflowMode, method: Option<&Method>, operator: &Operator, left: Option<u8>, right: Option<u8>) -> bool {
+    if let (Some(left), Some(right)) = (left, right) {
+        return match method {
+            Some(method) => evaluate_method(method, left, right).is_err(),
+            None => evaluate_operator(mode, operator, left, right).is_err(),
+        };
+    }
+
+    // Division panics on a zero divisor whatever the overflow semantics
+    if *operator == Operator::Divide {
+        return !matches!(right, Some(divisor) if divisor != 0);
+    }
+
+    // Bitwise operations never panic
+    if matches!(operator, Operator::BitAnd | Operator::BitOr | Operator::BitXor) {
+        return false;
+    }
+
+    // Shifts only panic on the amount they shift by, unless they wrap
+    if matches!(operator, Operator::ShiftLeft | Operator::ShiftRight) {
+        return mode != OverflowMode::Wrap && !matches!(right, Some(bits) if bits < 8);
+    }
+
+    let checked = match method {
+        Some(method) => method.kind == MethodKind::Checked,
+        None => mode == OverflowMode::Error || mode == OverflowMode::Panic,
+    };
+    if !checked {
+        return false;
+    }
+
+    // Operations with a neutral or absorbing constant cannot overflow
+    match operator {
+        Operator::Add => left != Some(0) && right != Some(0),
+        Operator::Subtract => right != Some(0),
+        Operator::Multiply => !matches!(left, Some(0) | Some(1)) && !matches!(right, Some(0) | Some(1)),
+        _ => unreachable!("handled above"),
+    }
+}
+
+// The helpers below assume that division by zero has already been ruled out
+
+fn checked(operator: &Operator, left: u8, right: u8) -> Option<u8> {
+    match operator {
+        Operator::Add => left.checked_add(right),
+        Operator::Subtract => left.checked_sub(right),
+        Operator::Multiply => left.checked_mul(right),
+        Operator::Divide => left.checked_div(right),
+        Operator::ShiftLeft => left.checked_shl(right as u32),
+        Operator::ShiftRight => left.checked_shr(right as u32),
+        Operator::BitAnd | Operator::BitOr | Operator::BitXor => Some(bitwise(operator, left, right)),
+    }
+}
+
+fn overflowing(operator: &Operator, left: u8, right: u8) -> (u8, bool) {
+    match operator {
+        Operator::Add => left.overflowing_add(right),
+        Operator::Subtract => left.overflowing_sub(right),
+        Operator::Multiply => left.overflowing_mul(right),
+        Operator::Divide => left.overflowing_div(right),
+        Operator::ShiftLeft => left.overflowing_shl(right as u32),
+        Operator::ShiftRight => left.overflowing_shr(right as u32),
+        Operator::BitAnd | Operator::BitOr | Operator::BitXor => (bitwise(operator, left, right), false),
+    }
+}
+
+fn saturating(operator: &Operator, left: u8, right: u8) -> u8 {
+    match operator {
+        Operator::Add => left.saturating_add(right),
+        Operator::Subtract => left.saturating_sub(right),
+        Operator::Multiply => left.saturating_mul(right),
+        Operator::Divide => left.saturating_div(right),
+        // There are no saturating shifts, they are evaluated like checked ones
+        Operator::ShiftLeft | Operator::ShiftRight => checked(operator, left, right).unwrap_or(0),
+        Operator::BitAnd | Operator::BitOr | Operator::BitXor => bitwise(operator, left, right),
+    }
+}
+
+fn bitwise(operator: &Operator, left: u8, right: u8) -> u8 {
+    match operator {
+        Operator::BitAnd => left & right,
+        Operator::BitOr => left | right,
+        Operator::BitXor => left ^ right,
+        _ => unreachable!("not a bitwise operator"),
+    }
+}
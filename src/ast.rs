@@ -1,5 +1,7 @@
 // The Abstract Syntax Tree (AST) for Rust
 
+use std::collections::HashSet;
+
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Program {
     // fn main(a: u8) {
@@ -50,14 +52,127 @@ pub enum Value {
     Identifier(String),
     // (1u8 + a)
     Expression(Box<Expression>),
+    // a.wrapping_add(1u8)
+    MethodCall {
+        receiver: Box<Value>,
+        method: Method,
+        argument: Box<Expression>,
+    },
 }
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+impl Expression {
+    pub fn integer(val: u8) -> Self {
+        Expression::Value(Box::new(Value::Integer(val)))
+    }
+
+    pub fn binary(left: Value, operator: Operator, right: Expression) -> Self {
+        Expression::Binary {
+            left,
+            operator,
+            right: Box::new(right),
+        }
+    }
+
+    // Removes the outer parenthesis, e.g ((a + b)) becomes a + b
+    pub fn unwrap_parenthesis(self) -> Self {
+        match self {
+            Expression::Value(value) => match *value {
+                Value::Expression(inner) => inner.unwrap_parenthesis(),
+                value => Expression::Value(Box::new(value)),
+            },
+            binary => binary,
+        }
+    }
+
+    // The expression as the left operand of a binary operation, parenthesized if it is one itself
+    pub fn parenthesize(self) -> Value {
+        match self.unwrap_parenthesis() {
+            Expression::Value(value) => *value,
+            binary => Value::Expression(Box::new(binary)),
+        }
+    }
+
+    // Returns the integer an expression consists of, looking through parenthesis
+    pub fn as_integer(&self) -> Option<u8> {
+        match self {
+            Expression::Value(value) => value.as_integer(),
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    // Returns the integer a value consists of, looking through parenthesis
+    pub fn as_integer(&self) -> Option<u8> {
+        match self {
+            Value::Integer(val) => Some(*val),
+            Value::Expression(expression) => expression.as_integer(),
+            _ => None,
+        }
+    }
+}
+
+// A temporary variable name that is not in `names` yet, e.g tmp0, which is then added to them
+pub fn fresh_name(names: &mut HashSet<String>) -> String {
+    let name = (0..)
+        .map(|index| format!("tmp{}", index))
+        .find(|name| !names.contains(name))
+        .unwrap();
+    names.insert(name.clone());
+    name
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
 pub enum Operator {
     Add,
     Subtract,
     Multiply,
     Divide,
+    ShiftLeft,
+    ShiftRight,
+    BitAnd,
+    BitOr,
+    BitXor,
+}
+
+impl Operator {
+    // Operators with a higher precedence bind tighter, e.g * binds tighter than +
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Operator::BitOr => 1,
+            Operator::BitXor => 2,
+            Operator::BitAnd => 3,
+            Operator::ShiftLeft | Operator::ShiftRight => 4,
+            Operator::Add | Operator::Subtract => 5,
+            Operator::Multiply | Operator::Divide => 6,
+        }
+    }
+
+    // Whether swapping the operands gives the same result, e.g a + b and b + a
+    pub fn is_commutative(&self) -> bool {
+        matches!(self, Operator::Add | Operator::Multiply | Operator::BitAnd | Operator::BitOr | Operator::BitXor)
+    }
+}
+
+// An integer method with explicit overflow semantics, e.g. `wrapping_add`
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Method {
+    pub kind: MethodKind,
+    pub operator: Operator,
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
+pub enum MethodKind {
+    // a.wrapping_add(b)
+    Wrapping,
+    // a.saturating_add(b)
+    Saturating,
+    // a.checked_add(b).unwrap()
+    Checked,
+    // a.overflowing_add(b).0
+    Overflowing,
+    // a.overflowing_add(b).1 as u8
+    OverflowFlag,
 }
 
 impl std::fmt::Display for Program {
@@ -72,13 +187,13 @@ impl std::fmt::Display for Program {
         let statements = self
             .statements
             .iter()
-            .map(|statement| format!("    {}", statement))
+            .map(|statement| format!("    {}\n", statement))
             .collect::<Vec<String>>()
-            .join("\n");
+            .join("");
 
         write!(
             f,
-            "fn {}({}) {{\n{}\n}}",
+            "fn {}({}) {{\n{}}}",
             self.name, inputs, statements
         )
     }
@@ -105,7 +220,22 @@ impl std::fmt::Display for Expression {
                 operator,
                 right,
             } => {
-                write!(f, "{} {} {}", left, operator, right)
+                // A cast followed by < or << reads as the start of generic arguments, e.g u8<...>,
+                // so casts are parenthesized as operands
+                if is_cast(left) {
+                    write!(f, "({}) {} ", left, operator)?;
+                } else {
+                    write!(f, "{} {} ", left, operator)?;
+                }
+
+                // Operations on the right bind tighter unless they are parenthesized
+                match &**right {
+                    Expression::Binary { operator: right_operator, .. } if right_operator.precedence() <= operator.precedence() => {
+                        write!(f, "({})", right)
+                    }
+                    Expression::Value(value) if is_cast(value) => write!(f, "({})", right),
+                    _ => write!(f, "{}", right),
+                }
             }
             Expression::Value(value) => {
                 write!(f, "{}", value)
@@ -129,6 +259,21 @@ impl std::fmt::Display for Operator {
             Operator::Divide => {
                 write!(f, "/")
             }
+            Operator::ShiftLeft => {
+                write!(f, "<<")
+            }
+            Operator::ShiftRight => {
+                write!(f, ">>")
+            }
+            Operator::BitAnd => {
+                write!(f, "&")
+            }
+            Operator::BitOr => {
+                write!(f, "|")
+            }
+            Operator::BitXor => {
+                write!(f, "^")
+            }
         }
     }
 }
@@ -145,10 +290,45 @@ impl std::fmt::Display for Value {
             Value::Expression(expression) => {
                 write!(f, "({})", expression)
             }
+            Value::MethodCall {
+                receiver,
+                method,
+                argument,
+            } => {
+                // A cast binds looser than a method call, so it needs parenthesis as a receiver
+                if is_cast(receiver) {
+                    write!(f, "({})", receiver)?;
+                } else {
+                    write!(f, "{}", receiver)?;
+                }
+
+                let name = match method.operator {
+                    Operator::Add => "add",
+                    Operator::Subtract => "sub",
+                    Operator::Multiply => "mul",
+                    Operator::Divide => "div",
+                    Operator::ShiftLeft => "shl",
+                    Operator::ShiftRight => "shr",
+                    Operator::BitAnd | Operator::BitOr | Operator::BitXor => unreachable!("bitwise operators have no methods"),
+                };
+
+                match method.kind {
+                    MethodKind::Wrapping => write!(f, ".wrapping_{}({})", name, argument),
+                    MethodKind::Saturating => write!(f, ".saturating_{}({})", name, argument),
+                    MethodKind::Checked => write!(f, ".checked_{}({}).unwrap()", name, argument),
+                    MethodKind::Overflowing => write!(f, ".overflowing_{}({}).0", name, argument),
+                    MethodKind::OverflowFlag => write!(f, ".overflowing_{}({}).1 as u8", name, argument),
+                }
+            }
         }
     }
 }
 
+// Whether a value prints as a cast, i.e a.overflowing_add(b).1 as u8
+fn is_cast(value: &Value) -> bool {
+    matches!(value, Value::MethodCall { method: Method { kind: MethodKind::OverflowFlag, .. }, .. })
+}
+
 impl std::fmt::Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use crate::ast::*;
+use crate::bytecode::instruction::{Bytecode, Instruction};
+use crate::ir::instruction::Arithmetic;
+
+// Compiles a program to bytecode. Every input and let binding gets its own slot.
+pub fn compile(program: &Program) -> Bytecode {
+    let mut compiler = Compiler {
+        slots: HashMap::new(),
+        code: Vec::new(),
+    };
+    let mut names = Vec::new();
+
+    for input in &program.inputs {
+        compiler.slots.insert(input.name.clone(), slot(names.len()));
+        names.push(input.name.clone());
+    }
+
+    for statement in &program.statements {
+        match statement {
+            Statement::Assign { variable, expression } => {
+                compiler.expression(expression);
+                let destination = slot(names.len());
+                compiler.slots.insert(variable.clone(), destination);
+                names.push(variable.clone());
+                compiler.code.push(Instruction::Store(destination));
+            }
+        }
+    }
+
+    Bytecode {
+        name: program.name.clone(),
+        inputs: program.inputs.len(),
+        slots: names,
+        code: compiler.code,
+    }
+}
+
+fn slot(index: usize) -> u16 {
+    u16::try_from(index).expect("at most 65536 variables")
+}
+
+struct Compiler {
+    slots: HashMap<String, u16>,
+    code: Vec<Instruction>,
+}
+
+impl Compiler {
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Binary { left, operator, right } => {
+                self.value(left);
+                self.expression(right);
+                self.code.push(Instruction::Binary {
+                    operator: operator.clone(),
+                    arithmetic: Arithmetic::Panicking,
+                });
+            }
+            Expression::Value(value) => self.value(value),
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Integer(val) => self.code.push(Instruction::Push(*val)),
+            Value::Identifier(name) => self.code.push(Instruction::Load(self.slots[name])),
+            Value::Expression(expression) => self.expression(expression),
+            Value::MethodCall { receiver, method, argument } => {
+                self.value(receiver);
+                self.expression(argument);
+                self.code.push(Instruction::Binary {
+                    operator: method.operator.clone(),
+                    arithmetic: Arithmetic::Method(method.kind.clone()),
+                });
+            }
+        }
+    }
+}
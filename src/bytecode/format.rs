@@ -0,0 +1,189 @@
+use crate::ast::{MethodKind, Operator};
+use crate::bytecode::instruction::{Bytecode, Instruction};
+use crate::ir::instruction::Arithmetic;
+
+// The on-disk format of bytecode. Integers are little endian.
+// 	- header: the magic bytes "RBC\0", then the version as a u16
+// 	- the name of the function, the number of inputs (u16), the number of slots (u16)
+// 	  and the name of every slot. Names are a u16 length followed by UTF-8 bytes.
+// 	- the length of the code in bytes (u32), then the code:
+// 		- 0x01 push, followed by the constant
+// 		- 0x02 load and 0x03 store, followed by the slot as a u16
+// 		- 0x10 to 0x18 an operation (add, sub, mul, div, shl, shr, and, or, xor), followed by its
+// 		  arithmetic: 0 panicking, 1 wrapping, 2 saturating, 3 checked, 4 overflowing, 5 overflow flag
+// 	- Decoding checks that every slot exists and that the stack never underflows,
+// 	  so the virtual machine can run decoded bytecode without checks
+pub const MAGIC: [u8; 4] = *b"RBC\0";
+pub const VERSION: u16 = 1;
+
+const PUSH: u8 = 0x01;
+const LOAD: u8 = 0x02;
+const STORE: u8 = 0x03;
+const BINARY: u8 = 0x10;
+
+const OPERATORS: [Operator; 9] = [
+    Operator::Add,
+    Operator::Subtract,
+    Operator::Multiply,
+    Operator::Divide,
+    Operator::ShiftLeft,
+    Operator::ShiftRight,
+    Operator::BitAnd,
+    Operator::BitOr,
+    Operator::BitXor,
+];
+
+const ARITHMETICS: [Arithmetic; 6] = [
+    Arithmetic::Panicking,
+    Arithmetic::Method(MethodKind::Wrapping),
+    Arithmetic::Method(MethodKind::Saturating),
+    Arithmetic::Method(MethodKind::Checked),
+    Arithmetic::Method(MethodKind::Overflowing),
+    Arithmetic::Method(MethodKind::OverflowFlag),
+];
+
+// Whether a file starts like serialized bytecode
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+pub fn encode(bytecode: &Bytecode) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+
+    write_name(&mut bytes, &bytecode.name);
+    bytes.extend_from_slice(&count(bytecode.inputs).to_le_bytes());
+    bytes.extend_from_slice(&count(bytecode.slots.len()).to_le_bytes());
+    for slot in &bytecode.slots {
+        write_name(&mut bytes, slot);
+    }
+
+    let mut code = Vec::new();
+    for instruction in &bytecode.code {
+        match instruction {
+            Instruction::Push(val) => code.extend_from_slice(&[PUSH, *val]),
+            Instruction::Load(slot) => {
+                code.push(LOAD);
+                code.extend_from_slice(&slot.to_le_bytes());
+            }
+            Instruction::Store(slot) => {
+                code.push(STORE);
+                code.extend_from_slice(&slot.to_le_bytes());
+            }
+            Instruction::Binary { operator, arithmetic } => {
+                let operator = OPERATORS.iter().position(|known| known == operator).expect("every operator has an opcode");
+                let arithmetic = ARITHMETICS.iter().position(|known| known == arithmetic).expect("every arithmetic has a code");
+                code.extend_from_slice(&[BINARY + operator as u8, arithmetic as u8]);
+            }
+        }
+    }
+    bytes.extend_from_slice(&(code.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&code);
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Bytecode, String> {
+    if !is_bytecode(bytes) {
+        return Err("Not a bytecode file.".to_string());
+    }
+    let mut reader = Reader { bytes, offset: MAGIC.len() };
+
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(format!("Unsupported bytecode version {}, expected {}.", version, VERSION));
+    }
+
+    let name = reader.name()?;
+    let inputs = reader.u16()? as usize;
+    let slot_count = reader.u16()? as usize;
+    let slots = (0..slot_count).map(|_| reader.name()).collect::<Result<Vec<String>, String>>()?;
+    if inputs > slot_count {
+        return Err("Invalid bytecode: more inputs than slots.".to_string());
+    }
+
+    let length = reader.u32()? as usize;
+    let end = reader.offset.checked_add(length).filter(|end| *end <= bytes.len()).ok_or_else(truncated)?;
+    let mut code = Vec::new();
+    let mut depth: usize = 0;
+    while reader.offset < end {
+        let offset = reader.offset - (end - length);
+        let instruction = match reader.u8()? {
+            PUSH => Instruction::Push(reader.u8()?),
+            LOAD => Instruction::Load(reader.u16()?),
+            STORE => Instruction::Store(reader.u16()?),
+            opcode if (BINARY..BINARY + OPERATORS.len() as u8).contains(&opcode) => {
+                let arithmetic = reader.u8()?;
+                let arithmetic = ARITHMETICS
+                    .get(arithmetic as usize)
+                    .ok_or_else(|| format!("Invalid bytecode: unknown arithmetic {} at offset {}.", arithmetic, offset))?;
+                Instruction::Binary {
+                    operator: OPERATORS[(opcode - BINARY) as usize].clone(),
+                    arithmetic: arithmetic.clone(),
+                }
+            }
+            opcode => return Err(format!("Invalid bytecode: unknown opcode 0x{:02x} at offset {}.", opcode, offset)),
+        };
+
+        // The depth of the stack after the instruction
+        depth = match &instruction {
+            Instruction::Push(_) | Instruction::Load(_) => Some(depth + 1),
+            Instruction::Store(_) => depth.checked_sub(1),
+            Instruction::Binary { .. } => depth.checked_sub(1).filter(|depth| *depth > 0),
+        }
+        .ok_or_else(|| format!("Invalid bytecode: the stack underflows at offset {}.", offset))?;
+        if let Instruction::Load(slot) | Instruction::Store(slot) = instruction {
+            if slot as usize >= slot_count {
+                return Err(format!("Invalid bytecode: slot {} does not exist at offset {}.", slot, offset));
+            }
+        }
+        code.push(instruction);
+    }
+    if reader.offset != end {
+        return Err(truncated());
+    }
+
+    Ok(Bytecode { name, inputs, slots, code })
+}
+
+fn truncated() -> String {
+    "Invalid bytecode: the file is truncated.".to_string()
+}
+
+fn count(count: usize) -> u16 {
+    u16::try_from(count).expect("at most 65536 variables")
+}
+
+fn write_name(bytes: &mut Vec<u8>, name: &str) {
+    bytes.extend_from_slice(&count(name.len()).to_le_bytes());
+    bytes.extend_from_slice(name.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, length: usize) -> Result<&[u8], String> {
+        let bytes = self.bytes.get(self.offset..self.offset + length).ok_or_else(truncated)?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let length = self.u16()? as usize;
+        String::from_utf8(self.take(length)?.to_vec()).map_err(|_| "Invalid bytecode: a name is not UTF-8.".to_string())
+    }
+}
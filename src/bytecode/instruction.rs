@@ -0,0 +1,88 @@
+// A compact bytecode for a stack machine
+// 	- Every variable lives in a numbered slot: the inputs first, then the let bindings
+// 	- Instructions push constants and slots on an operand stack. Operations pop their two
+// 	  operands, the right one on top, and push their result. Stores pop into a slot.
+// 	- Operands are pushed in evaluation order, so operations panic in the same order as
+// 	  the original expressions, e.g
+//
+// 	  let a = (x + 1u8) * y;
+//
+// 	  with x and y in slots 0 and 1 becomes
+//
+// 	  load 0, push 1, add, load 1, mul, store 2
+use crate::ast::Operator;
+use crate::ir::instruction::{mnemonic, Arithmetic};
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Bytecode {
+    pub name: String,
+    // The number of inputs, which take the first slots
+    pub inputs: usize,
+    // The name of the variable of every slot
+    pub slots: Vec<String>,
+    pub code: Vec<Instruction>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Instruction {
+    Push(u8),
+    Load(u16),
+    Store(u16),
+    Binary {
+        operator: Operator,
+        arithmetic: Arithmetic,
+    },
+}
+
+impl Instruction {
+    // The size of the instruction in the serialized format, in bytes
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::Push(_) | Instruction::Binary { .. } => 2,
+            Instruction::Load(_) | Instruction::Store(_) => 3,
+        }
+    }
+}
+
+impl Bytecode {
+    // The let bindings, in program order
+    pub fn bindings(&self) -> &[String] {
+        &self.slots[self.inputs..]
+    }
+}
+
+impl std::fmt::Display for Bytecode {
+    // A disassembly, with the offset of every instruction in the serialized code
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let inputs = self.slots[..self.inputs]
+            .iter()
+            .map(|input| format!("{}: u8", input))
+            .collect::<Vec<String>>()
+            .join(", ");
+        writeln!(f, "fn {}({}) {{", self.name, inputs)?;
+
+        let mut offset = 0;
+        for instruction in &self.code {
+            let text = instruction.to_string();
+            match instruction {
+                Instruction::Load(slot) | Instruction::Store(slot) => {
+                    writeln!(f, "    {:04}  {:<16}// {}", offset, text, self.slots[*slot as usize])?;
+                }
+                _ => writeln!(f, "    {:04}  {}", offset, text)?,
+            }
+            offset += instruction.size();
+        }
+        write!(f, "}}")
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Instruction::Push(val) => write!(f, "push {}", val),
+            Instruction::Load(slot) => write!(f, "load {}", slot),
+            Instruction::Store(slot) => write!(f, "store {}", slot),
+            Instruction::Binary { operator, arithmetic } => write!(f, "{}{}", mnemonic(operator), arithmetic),
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod instruction;
+pub mod compile;
+pub mod vm;
+pub mod format;
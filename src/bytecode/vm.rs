@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+use crate::ast::Method;
+use crate::arithmetic::*;
+use crate::bytecode::instruction::{Bytecode, Instruction};
+use crate::interpreter::RuntimeError;
+use crate::ir::instruction::Arithmetic;
+
+// A virtual machine for the bytecode
+// 	- Operators follow the overflow mode at runtime, like the interpreter
+// 	- The stack and the slots are kept between runs, so running the same bytecode
+// 	  on many inputs does not allocate
+// 	- The bytecode is trusted: its slots and stack depth are checked when it is decoded
+pub struct Vm {
+    overflow: OverflowMode,
+    stack: Vec<u8>,
+    slots: Vec<u8>,
+}
+
+impl Vm {
+    pub fn new(overflow: OverflowMode) -> Self {
+        Vm {
+            overflow,
+            stack: Vec::new(),
+            slots: Vec::new(),
+        }
+    }
+
+    // Runs bytecode on the values of its inputs, in order. Returns the value of every slot.
+    pub fn run(&mut self, bytecode: &Bytecode, inputs: &[u8]) -> Result<&[u8], RuntimeError> {
+        assert_eq!(inputs.len(), bytecode.inputs, "one value per input");
+
+        self.stack.clear();
+        self.slots.clear();
+        self.slots.extend_from_slice(inputs);
+        self.slots.resize(bytecode.slots.len(), 0);
+
+        for (pc, instruction) in bytecode.code.iter().enumerate() {
+            match instruction {
+                Instruction::Push(val) => self.stack.push(*val),
+                Instruction::Load(slot) => self.stack.push(self.slots[*slot as usize]),
+                Instruction::Store(slot) => self.slots[*slot as usize] = self.stack.pop().expect("verified stack depth"),
+                Instruction::Binary { operator, arithmetic } => {
+                    let right = self.stack.pop().expect("verified stack depth");
+                    let left = self.stack.pop().expect("verified stack depth");
+                    let result = match arithmetic {
+                        Arithmetic::Panicking => evaluate_operator(self.overflow, operator, left, right),
+                        Arithmetic::Method(kind) => {
+                            let method = Method {
+                                kind: kind.clone(),
+                                operator: operator.clone(),
+                            };
+                            evaluate_method(&method, left, right)
+                        }
+                    };
+                    match result {
+                        Ok(val) => self.stack.push(val),
+                        Err(panic) => return Err(panic_in(bytecode, pc, panic)),
+                    }
+                }
+            }
+        }
+        Ok(&self.slots)
+    }
+
+    // Runs bytecode on named arguments, like Interpreter::run. Returns the value of every binding, in program order.
+    pub fn run_named(&mut self, bytecode: &Bytecode, arguments: &BTreeMap<String, u8>) -> Result<Vec<(String, u8)>, RuntimeError> {
+        let inputs = &bytecode.slots[..bytecode.inputs];
+        if let Some(name) = arguments.keys().find(|name| !inputs.contains(name)) {
+            return Err(RuntimeError::UnknownInput(name.clone()));
+        }
+        let values = inputs
+            .iter()
+            .map(|input| arguments.get(input).copied().ok_or_else(|| RuntimeError::MissingInput(input.clone())))
+            .collect::<Result<Vec<u8>, RuntimeError>>()?;
+
+        let slots = self.run(bytecode, &values)?;
+        Ok(bytecode
+            .bindings()
+            .iter()
+            .cloned()
+            .zip(slots[bytecode.inputs..].iter().copied())
+            .collect())
+    }
+}
+
+// A panic of the operation at `pc`, in the initializer of the binding that the next store writes
+fn panic_in(bytecode: &Bytecode, pc: usize, panic: Panic) -> RuntimeError {
+    let variable = bytecode.code[pc..]
+        .iter()
+        .find_map(|instruction| match instruction {
+            Instruction::Store(slot) => Some(bytecode.slots[*slot as usize].clone()),
+            _ => None,
+        })
+        .unwrap_or_default();
+    RuntimeError::Panic { panic, variable }
+}
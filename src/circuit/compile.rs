@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use crate::ast::{Operator, Program};
+use crate::arithmetic::{OverflowMode, Panic};
+use crate::circuit::field::Field;
+use crate::circuit::r1cs::*;
+use crate::codegen::abi::{behaviour, division_by_zero, initialized_bindings, Behaviour};
+use crate::ir::instruction::{Instruction, Operand, Variable};
+use crate::ir::lower::lower;
+
+// Compiles a program to an arithmetic circuit over a prime field, as a rank-1 constraint system
+// 	- Values are field elements that the constraints keep in 0..=255. The inputs are decomposed
+// 	  into bits, which range checks them.
+// 	- An operation computes its exact result, which is below 2^16, and decomposes it into bits.
+// 	  The low byte is the wrapped result, and the high bits tell if it overflowed.
+// 	- A division has the quotient and the remainder as hints, constrained by
+// 	  dividend = quotient * divisor + remainder with remainder < divisor
+// 	- Bitwise operations decompose their operands into bits and combine them bit by bit
+// 	- A panic is a constraint that no witness satisfies when it happens, e.g the overflow
+// 	  of a checked addition is constrained to 0
+// 	- Operations on constants are folded as the circuit is built, so they add no wires
+pub fn compile(program: &Program, overflow: OverflowMode) -> ConstraintSystem {
+    let function = lower(program);
+    let mut builder = Builder {
+        overflow,
+        system: ConstraintSystem {
+            name: function.name.clone(),
+            wires: vec!["one".to_string()],
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            hints: Vec::new(),
+            constraints: Vec::new(),
+        },
+        values: HashMap::new(),
+        bits: HashMap::new(),
+        prefix: String::new(),
+        binding: 0,
+    };
+
+    for input in &function.inputs {
+        let wire = builder.wire(input.clone());
+        builder.system.inputs.push(wire);
+        builder.values.insert(Variable::Named(input.clone()), Combination::wire(wire));
+    }
+    // Range checks the inputs
+    for input in &function.inputs {
+        builder.operand_bits(&Operand::Variable(Variable::Named(input.clone())));
+    }
+
+    for (instruction, binding) in function.instructions.iter().zip(initialized_bindings(&function)) {
+        builder.binding = binding;
+        builder.instruction(instruction);
+    }
+    builder.system
+}
+
+struct Builder {
+    overflow: OverflowMode,
+    system: ConstraintSystem,
+    // The value of every variable
+    values: HashMap<Variable, Combination>,
+    // The bits of the variables that were decomposed
+    bits: HashMap<Variable, Vec<Combination>>,
+    // The name of the variable the current instruction defines, which its wires are named after
+    prefix: String,
+    // The index of the binding the current instruction initializes
+    binding: usize,
+}
+
+impl Builder {
+    fn wire(&mut self, name: String) -> usize {
+        self.system.wires.push(name);
+        self.system.wires.len() - 1
+    }
+
+    // The name of a wire of the current instruction, e.g sum.inverse
+    fn name(&self, role: &str) -> String {
+        format!("{}.{}", self.prefix, role)
+    }
+
+    // Adds the constraint a * b = c, unless it holds whatever the witness
+    fn constrain(&mut self, a: Combination, b: Combination, c: Combination, panic: Option<Panic>) {
+        if let (Some(a), Some(b), Some(c)) = (a.as_constant(), b.as_constant(), c.as_constant()) {
+            if a * b == c {
+                return;
+            }
+        }
+        let panic = panic.map(|panic| (panic, self.binding));
+        self.system.constraints.push(Constraint { a, b, c, panic });
+    }
+
+    fn operand(&self, operand: &Operand) -> Combination {
+        match operand {
+            Operand::Constant(val) => constant(*val as u64),
+            Operand::Variable(variable) => self.values[variable].clone(),
+        }
+    }
+
+    fn product(&mut self, left: Combination, right: Combination, role: &str) -> Combination {
+        match (left.as_constant(), right.as_constant()) {
+            (Some(factor), _) => right.scale(factor),
+            (_, Some(factor)) => left.scale(factor),
+            _ => {
+                let output = self.wire(self.name(role));
+                self.system.hints.push(Hint::Product { output, left: left.clone(), right: right.clone() });
+                self.constrain(left, right, Combination::wire(output), None);
+                Combination::wire(output)
+            }
+        }
+    }
+
+    // Decomposes a value into its lowest bits, which constrains it to be below 2^width.
+    // The wires are named like {name}.bit0.
+    fn bits(&mut self, value: &Combination, width: usize, name: &str) -> Vec<Combination> {
+        if let Some(val) = value.as_constant() {
+            return (0..width).map(|bit| constant(val.value() >> bit & 1)).collect();
+        }
+
+        let bits: Vec<usize> = (0..width).map(|bit| self.wire(format!("{}.bit{}", name, bit))).collect();
+        self.system.hints.push(Hint::Bits { value: value.clone(), bits: bits.clone() });
+        let bits: Vec<Combination> = bits.into_iter().map(Combination::wire).collect();
+        for bit in &bits {
+            self.constrain(bit.clone(), bit.clone(), bit.clone(), None);
+        }
+        self.constrain(recompose(&bits), constant(1), value.clone(), None);
+        bits
+    }
+
+    // The bits of an operand. A variable is decomposed once.
+    fn operand_bits(&mut self, operand: &Operand) -> Vec<Combination> {
+        match operand {
+            Operand::Variable(variable) if self.bits.contains_key(variable) => self.bits[variable].clone(),
+            Operand::Variable(variable) => {
+                let bits = self.bits(&self.values[variable].clone(), 8, &variable.to_string());
+                self.bits.insert(variable.clone(), bits.clone());
+                bits
+            }
+            Operand::Constant(_) => self.bits(&self.operand(operand), 8, ""),
+        }
+    }
+
+    // The inverse of a value, and 0 for 0
+    fn inverse(&mut self, value: &Combination) -> Combination {
+        match value.as_constant() {
+            Some(val) => Combination::constant(val.inverse().unwrap_or(Field::ZERO)),
+            None => {
+                let output = self.wire(self.name("inverse"));
+                self.system.hints.push(Hint::Inverse { output, value: value.clone() });
+                Combination::wire(output)
+            }
+        }
+    }
+
+    // A bit that is 1 if the value is not zero
+    fn is_nonzero(&mut self, value: &Combination) -> Combination {
+        let inverse = self.inverse(value);
+        let nonzero = self.product(value.clone(), inverse, "nonzero");
+        // The product is 1 if the value has an inverse, and 0 for 0 whatever the inverse
+        self.constrain(value.clone(), constant(1) - nonzero.clone(), constant(0), None);
+        nonzero
+    }
+
+    // The quotient of an integer division by a divisor that is not zero
+    fn divide(&mut self, dividend: Combination, divisor: Combination) -> Combination {
+        if let (Some(dividend), Some(divisor)) = (dividend.as_constant(), divisor.as_constant()) {
+            // A division by zero panics before
+            return constant(dividend.value().checked_div(divisor.value()).unwrap_or(0));
+        }
+
+        let quotient = self.wire(self.name("quotient"));
+        let remainder = self.wire(self.name("remainder"));
+        self.system.hints.push(Hint::Division { dividend: dividend.clone(), divisor: divisor.clone(), quotient, remainder });
+        let (quotient, remainder) = (Combination::wire(quotient), Combination::wire(remainder));
+        self.constrain(quotient.clone(), divisor.clone(), dividend - remainder.clone(), None);
+        // The quotient is a u8, and 0 <= remainder < divisor
+        self.bits(&quotient, 8, &self.name("quotient"));
+        self.bits(&remainder, 8, &self.name("remainder"));
+        self.bits(&(divisor - remainder - constant(1)), 8, &self.name("gap"));
+        quotient
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        let destination = instruction.destination();
+        self.prefix = destination.to_string();
+        let value = match instruction {
+            Instruction::Copy { source, .. } => self.operand(source),
+            Instruction::Binary { operator, arithmetic, left, right, .. } => {
+                let behaviour = behaviour(self.overflow, operator, arithmetic);
+                if *operator == Operator::Divide {
+                    // The divisor has an inverse, which comes before the other constraints of the division
+                    // so that a witness fails this one first
+                    let divisor = self.operand(right);
+                    let inverse = self.inverse(&divisor);
+                    self.constrain(divisor, inverse, constant(1), Some(division_by_zero(arithmetic)));
+                }
+                self.operation(operator, behaviour, left, right)
+            }
+        };
+
+        let value = match destination {
+            Variable::Named(binding) => {
+                let output = self.wire(binding.clone());
+                self.system.hints.push(Hint::Value { output, value: value.clone() });
+                self.constrain(value, constant(1), Combination::wire(output), None);
+                self.system.outputs.push((binding.clone(), output));
+                Combination::wire(output)
+            }
+            _ => value,
+        };
+        self.values.insert(destination.clone(), value);
+    }
+
+    fn operation(&mut self, operator: &Operator, behaviour: Behaviour, left: &Operand, right: &Operand) -> Combination {
+        let (x, y) = (self.operand(left), self.operand(right));
+        match operator {
+            Operator::Add | Operator::Subtract | Operator::Multiply => {
+                // The exact result, offset by 256 for a subtraction so that it is not negative
+                let (exact, width) = match operator {
+                    Operator::Add => (x + y, 9),
+                    Operator::Subtract => (x - y + constant(256), 9),
+                    _ => (self.product(x, y, "product"), 16),
+                };
+                let bits = self.bits(&exact, width, &self.name("exact"));
+                // Zero if and only if the operation does not overflow
+                let (overflow, saturated) = match operator {
+                    Operator::Add => (bits[8].clone(), 255),
+                    Operator::Subtract => (constant(1) - bits[8].clone(), 0),
+                    _ => (recompose(&bits[8..]), 255),
+                };
+                let is_bit = *operator != Operator::Multiply;
+                self.overflowing(behaviour, recompose(&bits[..8]), overflow, is_bit, saturated)
+            }
+            Operator::ShiftLeft | Operator::ShiftRight => {
+                let amount = self.operand_bits(right);
+                // 2 to the power of the amount modulo 8, from its three lowest bits
+                let factor = self.product(
+                    constant(1) + amount[0].clone(),
+                    constant(1) + amount[1].scale(Field::new(3)),
+                    "partial_factor",
+                );
+                let factor = self.product(factor, constant(1) + amount[2].scale(Field::new(15)), "factor");
+                let wrapped = match operator {
+                    Operator::ShiftLeft => {
+                        let exact = self.product(x, factor, "product");
+                        let bits = self.bits(&exact, 15, &self.name("exact"));
+                        recompose(&bits[..8])
+                    }
+                    _ => self.divide(x, factor),
+                };
+                // The amount divided by 8
+                let overflow = recompose(&amount[3..]);
+                self.overflowing(behaviour, wrapped, overflow, false, 0)
+            }
+            // Divisions and bitwise operations cannot overflow
+            _ if behaviour == Behaviour::Flag => constant(0),
+            Operator::Divide => self.divide(x, y),
+            Operator::BitAnd | Operator::BitOr | Operator::BitXor => {
+                let (x, y) = (self.operand_bits(left), self.operand_bits(right));
+                let bits: Vec<Combination> = x
+                    .into_iter()
+                    .zip(y)
+                    .enumerate()
+                    .map(|(index, (x, y))| {
+                        let both = self.product(x.clone(), y.clone(), &format!("and{}", index));
+                        match operator {
+                            Operator::BitAnd => both,
+                            Operator::BitOr => x + y - both,
+                            _ => x + y - both.scale(Field::new(2)),
+                        }
+                    })
+                    .collect();
+                recompose(&bits)
+            }
+        }
+    }
+
+    // The result of an operation that may overflow, from its wrapped result and a value that is
+    // not zero when it overflows. The value is a bit for additions and subtractions.
+    fn overflowing(&mut self, behaviour: Behaviour, wrapped: Combination, overflow: Combination, is_bit: bool, saturated: u64) -> Combination {
+        match behaviour {
+            Behaviour::Checked(panic) => {
+                self.constrain(overflow, constant(1), constant(0), Some(panic));
+                wrapped
+            }
+            Behaviour::Wrapping => wrapped,
+            Behaviour::Flag | Behaviour::Saturating => {
+                let flag = if is_bit { overflow } else { self.is_nonzero(&overflow) };
+                match behaviour {
+                    Behaviour::Flag => flag,
+                    // wrapped + flag * (saturated - wrapped)
+                    _ => wrapped.clone() + self.product(flag, constant(saturated) - wrapped, "saturated"),
+                }
+            }
+        }
+    }
+}
+
+fn constant(val: u64) -> Combination {
+    Combination::constant(Field::new(val))
+}
+
+// The value of bits, lowest first
+fn recompose(bits: &[Combination]) -> Combination {
+    bits.iter()
+        .enumerate()
+        .fold(Combination::default(), |sum, (index, bit)| sum + bit.scale(Field::new(1 << index)))
+}
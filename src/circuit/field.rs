@@ -0,0 +1,90 @@
+// The prime field the constraints are over, of order 2^64 - 2^32 + 1
+// 	- The order is larger than every value the circuits compute, which are below 2^16, so
+// 	  u8 arithmetic never wraps around the modulus
+// 	- Elements are kept reduced, so two elements are equal when their representations are
+pub const MODULUS: u64 = 0xffff_ffff_0000_0001;
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, PartialOrd, Ord)]
+pub struct Field(u64);
+
+impl Field {
+    pub const ZERO: Field = Field(0);
+    pub const ONE: Field = Field(1);
+
+    pub fn new(val: u64) -> Self {
+        Field(val % MODULUS)
+    }
+
+    // The representative of the element, in 0..MODULUS
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    pub fn pow(&self, mut exponent: u64) -> Field {
+        let mut base = *self;
+        let mut result = Field::ONE;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            exponent >>= 1;
+        }
+        result
+    }
+
+    // The multiplicative inverse, by Fermat's little theorem. None for zero.
+    pub fn inverse(&self) -> Option<Field> {
+        match self.0 {
+            0 => None,
+            _ => Some(self.pow(MODULUS - 2)),
+        }
+    }
+}
+
+impl From<u8> for Field {
+    fn from(val: u8) -> Self {
+        Field(val as u64)
+    }
+}
+
+impl std::ops::Add for Field {
+    type Output = Field;
+
+    fn add(self, other: Field) -> Field {
+        Field(((self.0 as u128 + other.0 as u128) % MODULUS as u128) as u64)
+    }
+}
+
+impl std::ops::Neg for Field {
+    type Output = Field;
+
+    fn neg(self) -> Field {
+        match self.0 {
+            0 => self,
+            val => Field(MODULUS - val),
+        }
+    }
+}
+
+impl std::ops::Sub for Field {
+    type Output = Field;
+
+    fn sub(self, other: Field) -> Field {
+        self + -other
+    }
+}
+
+impl std::ops::Mul for Field {
+    type Output = Field;
+
+    fn mul(self, other: Field) -> Field {
+        Field((self.0 as u128 * other.0 as u128 % MODULUS as u128) as u64)
+    }
+}
+
+impl std::fmt::Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,5 @@
+pub mod field;
+pub mod r1cs;
+pub mod compile;
+pub mod witness;
+pub mod netlist;
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use crate::ast::{Operator, Program};
+use crate::arithmetic::OverflowMode;
+use crate::codegen::abi::{behaviour, Behaviour};
+use crate::ir::instruction::{Instruction, Operand, Variable};
+use crate::ir::lower::lower;
+
+// A combinational circuit of logic gates, which computes the bindings of a program from its inputs
+// 	- A u8 is a byte of eight signals, lowest bit first
+// 	- Additions and subtractions are ripple-carry adders, multiplications add shifted partial
+// 	  products, divisions are restoring dividers and shifts are barrel shifters. The carries
+// 	  and the bits they shift out give the overflows.
+// 	- A circuit cannot stop, so it computes the wrapped result of an operation that panics, and
+// 	  raises the overflow flag. The bindings are only meaningful when the flag is low.
+// 	- Gates only read the inputs and the gates before them. Gates on constants are folded
+// 	  as the netlist is built.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Netlist {
+    pub name: String,
+    pub inputs: Vec<String>,
+    pub gates: Vec<Gate>,
+    // The byte of every binding, in program order
+    pub outputs: Vec<(String, Byte)>,
+    // Whether the program panics
+    pub overflow: Signal,
+    // The instruction that every gate from an index on computes
+    pub comments: Vec<(usize, String)>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Signal {
+    Constant(bool),
+    // A bit of an input
+    Input(usize, usize),
+    // The output of a gate
+    Gate(usize),
+}
+
+pub type Byte = [Signal; 8];
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Gate {
+    And(Signal, Signal),
+    Or(Signal, Signal),
+    Xor(Signal, Signal),
+    Not(Signal),
+}
+
+const ZERO: Signal = Signal::Constant(false);
+const ONE: Signal = Signal::Constant(true);
+
+pub fn synthesize(program: &Program, overflow: OverflowMode) -> Netlist {
+    let function = lower(program);
+    let mut builder = Builder {
+        gates: Vec::new(),
+        values: HashMap::new(),
+        panics: ZERO,
+    };
+    for (index, input) in function.inputs.iter().enumerate() {
+        let byte = std::array::from_fn(|bit| Signal::Input(index, bit));
+        builder.values.insert(Variable::Named(input.clone()), byte);
+    }
+
+    let mut outputs = Vec::new();
+    let mut comments = Vec::new();
+    for instruction in &function.instructions {
+        comments.push((builder.gates.len(), instruction.to_string()));
+        let value = match instruction {
+            Instruction::Copy { source, .. } => builder.operand(source),
+            Instruction::Binary { operator, arithmetic, left, right, .. } => {
+                let (x, y) = (builder.operand(left), builder.operand(right));
+                builder.operation(operator, behaviour(overflow, operator, arithmetic), x, y)
+            }
+        };
+        let destination = instruction.destination();
+        if let Variable::Named(binding) = destination {
+            outputs.push((binding.clone(), value));
+        }
+        builder.values.insert(destination.clone(), value);
+    }
+
+    Netlist {
+        name: function.name,
+        inputs: function.inputs,
+        gates: builder.gates,
+        outputs,
+        overflow: builder.panics,
+        comments,
+    }
+}
+
+impl Netlist {
+    // Evaluates the gates in order on the values of the inputs. Returns the value of every output
+    // and the overflow flag.
+    pub fn evaluate(&self, inputs: &[u8]) -> (Vec<u8>, bool) {
+        assert_eq!(inputs.len(), self.inputs.len(), "one value per input");
+        let mut values: Vec<bool> = Vec::with_capacity(self.gates.len());
+        let read = |values: &[bool], signal: &Signal| match signal {
+            Signal::Constant(val) => *val,
+            Signal::Input(input, bit) => inputs[*input] >> bit & 1 == 1,
+            Signal::Gate(gate) => values[*gate],
+        };
+        for gate in &self.gates {
+            let val = match gate {
+                Gate::And(x, y) => read(&values, x) && read(&values, y),
+                Gate::Or(x, y) => read(&values, x) || read(&values, y),
+                Gate::Xor(x, y) => read(&values, x) ^ read(&values, y),
+                Gate::Not(x) => !read(&values, x),
+            };
+            values.push(val);
+        }
+
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|(_, byte)| (0..8).fold(0, |val, bit| val | (read(&values, &byte[bit]) as u8) << bit))
+            .collect();
+        (outputs, read(&values, &self.overflow))
+    }
+
+    // Keeps the outputs of the given bindings, and the gates that they and the overflow flag read
+    pub fn select(&mut self, bindings: &[String]) {
+        self.outputs.retain(|(binding, _)| bindings.contains(binding));
+
+        let mut live = vec![false; self.gates.len()];
+        let mark = |signal: &Signal, live: &mut Vec<bool>| {
+            if let Signal::Gate(gate) = signal {
+                live[*gate] = true;
+            }
+        };
+        for signal in self.outputs.iter().flat_map(|(_, byte)| byte).chain([&self.overflow]) {
+            mark(signal, &mut live);
+        }
+        // Gates only read the gates before them
+        for index in (0..self.gates.len()).rev() {
+            if live[index] {
+                match self.gates[index] {
+                    Gate::And(x, y) | Gate::Or(x, y) | Gate::Xor(x, y) => {
+                        mark(&x, &mut live);
+                        mark(&y, &mut live);
+                    }
+                    Gate::Not(x) => mark(&x, &mut live),
+                }
+            }
+        }
+
+        // The new index of every gate that is kept
+        let mut indices = vec![0; self.gates.len()];
+        let mut kept = 0;
+        for (index, is_live) in live.iter().enumerate() {
+            indices[index] = kept;
+            kept += *is_live as usize;
+        }
+        let rename = |signal: Signal| match signal {
+            Signal::Gate(gate) => Signal::Gate(indices[gate]),
+            signal => signal,
+        };
+
+        let gates = std::mem::take(&mut self.gates);
+        self.gates = gates
+            .into_iter()
+            .zip(&live)
+            .filter(|(_, is_live)| **is_live)
+            .map(|(gate, _)| match gate {
+                Gate::And(x, y) => Gate::And(rename(x), rename(y)),
+                Gate::Or(x, y) => Gate::Or(rename(x), rename(y)),
+                Gate::Xor(x, y) => Gate::Xor(rename(x), rename(y)),
+                Gate::Not(x) => Gate::Not(rename(x)),
+            })
+            .collect();
+        for (_, byte) in self.outputs.iter_mut() {
+            *byte = byte.map(rename);
+        }
+        self.overflow = rename(self.overflow);
+
+        // The instructions that no gate is kept for lose their comment
+        let ends: Vec<usize> = self.comments.iter().skip(1).map(|(start, _)| *start).chain([live.len()]).collect();
+        let comments = std::mem::take(&mut self.comments);
+        self.comments = comments
+            .into_iter()
+            .zip(ends)
+            .filter(|((start, _), end)| live[*start..*end].contains(&true))
+            .map(|((start, comment), _)| (indices[start], comment))
+            .collect();
+    }
+}
+
+struct Builder {
+    gates: Vec<Gate>,
+    // The byte of every variable
+    values: HashMap<Variable, Byte>,
+    // Whether an operation panicked
+    panics: Signal,
+}
+
+impl Builder {
+    fn gate(&mut self, gate: Gate) -> Signal {
+        self.gates.push(gate);
+        Signal::Gate(self.gates.len() - 1)
+    }
+
+    fn and(&mut self, x: Signal, y: Signal) -> Signal {
+        match (x, y) {
+            (ZERO, _) | (_, ZERO) => ZERO,
+            (ONE, other) | (other, ONE) => other,
+            _ if x == y => x,
+            _ => self.gate(Gate::And(x, y)),
+        }
+    }
+
+    fn or(&mut self, x: Signal, y: Signal) -> Signal {
+        match (x, y) {
+            (ONE, _) | (_, ONE) => ONE,
+            (ZERO, other) | (other, ZERO) => other,
+            _ if x == y => x,
+            _ => self.gate(Gate::Or(x, y)),
+        }
+    }
+
+    fn xor(&mut self, x: Signal, y: Signal) -> Signal {
+        match (x, y) {
+            (ZERO, other) | (other, ZERO) => other,
+            (ONE, other) | (other, ONE) => self.not(other),
+            _ if x == y => ZERO,
+            _ => self.gate(Gate::Xor(x, y)),
+        }
+    }
+
+    fn not(&mut self, x: Signal) -> Signal {
+        match x {
+            Signal::Constant(val) => Signal::Constant(!val),
+            Signal::Gate(gate) => match self.gates[gate] {
+                Gate::Not(inner) => inner,
+                _ => self.gate(Gate::Not(x)),
+            },
+            _ => self.gate(Gate::Not(x)),
+        }
+    }
+
+    // The first signal if the condition holds, otherwise the second one
+    fn mux(&mut self, condition: Signal, then: Signal, otherwise: Signal) -> Signal {
+        let difference = self.xor(then, otherwise);
+        let selected = self.and(condition, difference);
+        self.xor(otherwise, selected)
+    }
+
+    // Whether any of the signals is high
+    fn any(&mut self, signals: &[Signal]) -> Signal {
+        signals.iter().fold(ZERO, |any, signal| self.or(any, *signal))
+    }
+
+    fn operand(&self, operand: &Operand) -> Byte {
+        match operand {
+            Operand::Constant(val) => std::array::from_fn(|bit| Signal::Constant(val >> bit & 1 == 1)),
+            Operand::Variable(variable) => self.values[variable],
+        }
+    }
+
+    // Adds two numbers of the same width and a carry. Returns the sum and the carry out.
+    fn add(&mut self, x: &[Signal], y: &[Signal], mut carry: Signal) -> (Vec<Signal>, Signal) {
+        let mut sum = Vec::with_capacity(x.len());
+        for (x, y) in x.iter().zip(y) {
+            let half = self.xor(*x, *y);
+            sum.push(self.xor(half, carry));
+            let generated = self.and(*x, *y);
+            let propagated = self.and(half, carry);
+            carry = self.or(generated, propagated);
+        }
+        (sum, carry)
+    }
+
+    // Subtracts two numbers of the same width. Returns the difference and whether it borrowed.
+    fn subtract(&mut self, x: &[Signal], y: &[Signal]) -> (Vec<Signal>, Signal) {
+        let complement: Vec<Signal> = y.iter().map(|bit| self.not(*bit)).collect();
+        let (difference, carry) = self.add(x, &complement, ONE);
+        (difference, self.not(carry))
+    }
+
+    // The 16 bits of the product of two bytes
+    fn multiply(&mut self, x: &Byte, y: &Byte) -> Vec<Signal> {
+        let mut product = vec![ZERO; 16];
+        for (shift, multiplier) in y.iter().enumerate() {
+            let partial: Vec<Signal> = x.iter().map(|bit| self.and(*bit, *multiplier)).collect();
+            let (sum, carry) = self.add(&product[shift..shift + 8], &partial, ZERO);
+            product.splice(shift..shift + 9, sum.into_iter().chain([carry]));
+        }
+        product
+    }
+
+    // The quotient of a restoring division, which is 255 for a divisor of zero
+    fn divide(&mut self, x: &Byte, y: &Byte) -> Byte {
+        let divisor: Vec<Signal> = y.iter().copied().chain([ZERO]).collect();
+        let mut remainder = vec![ZERO; 9];
+        let mut quotient = [ZERO; 8];
+        for bit in (0..8).rev() {
+            // remainder = remainder << 1 | x[bit]
+            remainder.pop();
+            remainder.insert(0, x[bit]);
+            let (difference, borrowed) = self.subtract(&remainder, &divisor);
+            quotient[bit] = self.not(borrowed);
+            remainder = remainder
+                .iter()
+                .zip(difference)
+                .map(|(kept, difference)| self.mux(quotient[bit], difference, *kept))
+                .collect();
+        }
+        quotient
+    }
+
+    // Shifts a byte by the three lowest bits of an amount, one stage per bit
+    fn shift(&mut self, x: &Byte, amount: &Byte, left: bool) -> Byte {
+        let mut shifted = *x;
+        for (stage, shifts) in amount[..3].iter().enumerate() {
+            let distance = 1 << stage;
+            let moved: Byte = std::array::from_fn(|bit| match left {
+                true if bit >= distance => shifted[bit - distance],
+                false if bit + distance < 8 => shifted[bit + distance],
+                _ => ZERO,
+            });
+            let stage: Vec<Signal> = moved
+                .iter()
+                .zip(shifted)
+                .map(|(moved, kept)| self.mux(*shifts, *moved, kept))
+                .collect();
+            shifted = byte(&stage);
+        }
+        shifted
+    }
+
+    fn operation(&mut self, operator: &Operator, behaviour: Behaviour, x: Byte, y: Byte) -> Byte {
+        if *operator == Operator::Divide {
+            let zero = self.any(&y);
+            let zero = self.not(zero);
+            self.panics = self.or(self.panics, zero);
+        }
+
+        // The wrapped result, whether it overflows, and the value it saturates to
+        let (wrapped, overflows, saturated): (Byte, Signal, bool) = match operator {
+            Operator::Add => {
+                let (sum, carry) = self.add(&x, &y, ZERO);
+                (byte(&sum), carry, true)
+            }
+            Operator::Subtract => {
+                let (difference, borrowed) = self.subtract(&x, &y);
+                (byte(&difference), borrowed, false)
+            }
+            Operator::Multiply => {
+                let product = self.multiply(&x, &y);
+                (byte(&product[..8]), self.any(&product[8..]), true)
+            }
+            Operator::ShiftLeft | Operator::ShiftRight => {
+                let shifted = self.shift(&x, &y, *operator == Operator::ShiftLeft);
+                (shifted, self.any(&y[3..]), false)
+            }
+            // Divisions and bitwise operations cannot overflow
+            _ if behaviour == Behaviour::Flag => return [ZERO; 8],
+            Operator::Divide => return self.divide(&x, &y),
+            Operator::BitAnd | Operator::BitOr | Operator::BitXor => {
+                let bits: Vec<Signal> = x
+                    .iter()
+                    .zip(y)
+                    .map(|(x, y)| match operator {
+                        Operator::BitAnd => self.and(*x, y),
+                        Operator::BitOr => self.or(*x, y),
+                        _ => self.xor(*x, y),
+                    })
+                    .collect();
+                return byte(&bits);
+            }
+        };
+
+        match behaviour {
+            Behaviour::Checked(_) => {
+                self.panics = self.or(self.panics, overflows);
+                wrapped
+            }
+            Behaviour::Wrapping => wrapped,
+            Behaviour::Saturating if saturated => wrapped.map(|bit| self.or(bit, overflows)),
+            Behaviour::Saturating => {
+                let fits = self.not(overflows);
+                wrapped.map(|bit| self.and(bit, fits))
+            }
+            Behaviour::Flag => {
+                let mut flag = [ZERO; 8];
+                flag[0] = overflows;
+                flag
+            }
+        }
+    }
+}
+
+fn byte(bits: &[Signal]) -> Byte {
+    std::array::from_fn(|bit| bits[bit])
+}
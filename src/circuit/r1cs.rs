@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use crate::arithmetic::Panic;
+use crate::circuit::field::{Field, MODULUS};
+
+// A rank-1 constraint system: every constraint requires A·w * B·w = C·w, where A, B and C are
+// linear combinations of the wires w
+// 	- Wire 0 is the constant one, the inputs follow it
+// 	- Every let binding has a wire, and the other wires hold the intermediate values of the operations
+// 	- The hints compute the wires from the inputs, in order. Some wires are not a function of the
+// 	  constraints' terms, e.g the quotient of a division, so the constraints alone do not say how to
+// 	  compute a witness.
+// 	- The constraints that fail when the program panics are tagged with the panic
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ConstraintSystem {
+    pub name: String,
+    // The name of every wire
+    pub wires: Vec<String>,
+    pub inputs: Vec<usize>,
+    // The wire of every binding, in program order
+    pub outputs: Vec<(String, usize)>,
+    pub hints: Vec<Hint>,
+    pub constraints: Vec<Constraint>,
+}
+
+// A linear combination of wires, from the wire to its coefficient. Coefficients are never zero.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Combination(pub BTreeMap<usize, Field>);
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Constraint {
+    pub a: Combination,
+    pub b: Combination,
+    pub c: Combination,
+    // The panic, and the index of the binding it happens in
+    pub panic: Option<(Panic, usize)>,
+}
+
+// How the witness generator computes wires
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Hint {
+    Value { output: usize, value: Combination },
+    Product { output: usize, left: Combination, right: Combination },
+    // The bits of a value, lowest first
+    Bits { value: Combination, bits: Vec<usize> },
+    // The inverse of a value, 0 if it is zero
+    Inverse { output: usize, value: Combination },
+    // The quotient and the remainder of an integer division. 0 and the dividend if the divisor is zero.
+    Division { dividend: Combination, divisor: Combination, quotient: usize, remainder: usize },
+}
+
+pub const ONE: usize = 0;
+
+impl Combination {
+    pub fn constant(val: Field) -> Self {
+        Combination::wire(ONE).scale(val)
+    }
+
+    pub fn wire(wire: usize) -> Self {
+        Combination(BTreeMap::from([(wire, Field::ONE)]))
+    }
+
+    // The value of the combination if it does not depend on any wire
+    pub fn as_constant(&self) -> Option<Field> {
+        match self.0.keys().find(|wire| **wire != ONE) {
+            Some(_) => None,
+            None => Some(self.0.get(&ONE).copied().unwrap_or(Field::ZERO)),
+        }
+    }
+
+    pub fn scale(&self, factor: Field) -> Self {
+        Combination(
+            self.0
+                .iter()
+                .map(|(wire, coefficient)| (*wire, *coefficient * factor))
+                .filter(|(_, coefficient)| *coefficient != Field::ZERO)
+                .collect(),
+        )
+    }
+
+    pub fn evaluate(&self, witness: &[Field]) -> Field {
+        self.0
+            .iter()
+            .fold(Field::ZERO, |sum, (wire, coefficient)| sum + *coefficient * witness[*wire])
+    }
+}
+
+impl std::ops::Add for Combination {
+    type Output = Combination;
+
+    fn add(mut self, other: Combination) -> Combination {
+        for (wire, coefficient) in other.0 {
+            let sum = self.0.get(&wire).copied().unwrap_or(Field::ZERO) + coefficient;
+            if sum == Field::ZERO {
+                self.0.remove(&wire);
+            } else {
+                self.0.insert(wire, sum);
+            }
+        }
+        self
+    }
+}
+
+impl std::ops::Sub for Combination {
+    type Output = Combination;
+
+    fn sub(self, other: Combination) -> Combination {
+        self + other.scale(-Field::ONE)
+    }
+}
+
+impl Constraint {
+    pub fn is_satisfied(&self, witness: &[Field]) -> bool {
+        self.a.evaluate(witness) * self.b.evaluate(witness) == self.c.evaluate(witness)
+    }
+}
+
+impl ConstraintSystem {
+    // Checks a witness against every constraint. Returns the index of the first one it does not satisfy.
+    pub fn check(&self, witness: &[Field]) -> Result<(), usize> {
+        assert_eq!(witness.len(), self.wires.len(), "one value per wire");
+        if witness[ONE] != Field::ONE {
+            return Err(0);
+        }
+        match self.constraints.iter().position(|constraint| !constraint.is_satisfied(witness)) {
+            Some(index) => Err(index),
+            None => Ok(()),
+        }
+    }
+
+    // The constraint system as JSON, with one constraint per line. Combinations map the index of a
+    // wire to its coefficient, which is a string since it may not fit in a double.
+    pub fn to_json(&self) -> String {
+        // Wire names are made of identifiers, which need no escaping
+        let wires: Vec<String> = self.wires.iter().map(|wire| format!("\"{}\"", wire)).collect();
+        let inputs: Vec<String> = self.inputs.iter().map(|wire| wire.to_string()).collect();
+        let outputs: Vec<String> = self
+            .outputs
+            .iter()
+            .map(|(binding, wire)| format!("{{\"binding\": \"{}\", \"wire\": {}}}", binding, wire))
+            .collect();
+
+        let mut json = String::from("{\n");
+        let _ = writeln!(json, "  \"name\": \"{}\",", self.name);
+        let _ = writeln!(json, "  \"field\": \"{}\",", MODULUS);
+        let _ = writeln!(json, "  \"wires\": [{}],", wires.join(", "));
+        let _ = writeln!(json, "  \"inputs\": [{}],", inputs.join(", "));
+        let _ = writeln!(json, "  \"outputs\": [{}],", outputs.join(", "));
+        json += "  \"constraints\": [\n";
+        for (index, constraint) in self.constraints.iter().enumerate() {
+            let _ = write!(
+                json,
+                "    {{\"a\": {}, \"b\": {}, \"c\": {}",
+                combination_json(&constraint.a),
+                combination_json(&constraint.b),
+                combination_json(&constraint.c)
+            );
+            if let Some((panic, binding)) = &constraint.panic {
+                let _ = write!(
+                    json,
+                    ", \"panic\": {{\"binding\": \"{}\", \"message\": \"{}\"}}",
+                    self.outputs[*binding].0,
+                    panic.message()
+                );
+            }
+            json += if index + 1 < self.constraints.len() { "},\n" } else { "}\n" };
+        }
+        json += "  ]\n}\n";
+        json
+    }
+}
+
+fn combination_json(combination: &Combination) -> String {
+    let terms: Vec<String> = combination
+        .0
+        .iter()
+        .map(|(wire, coefficient)| format!("\"{}\": \"{}\"", wire, coefficient))
+        .collect();
+    format!("{{{}}}", terms.join(", "))
+}
@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+use crate::circuit::field::Field;
+use crate::circuit::r1cs::{ConstraintSystem, Hint, ONE};
+use crate::interpreter::RuntimeError;
+
+// Computes the witness of a constraint system from the values of its inputs, in order
+// 	- The hints run in order, and compute every wire even after the program panicked.
+// 	  The wires are then checked against the constraints.
+// 	- An operation that panics still gives the next ones a u8, and a division checks its divisor
+// 	  before it divides, so the first constraint the witness does not satisfy is the panic.
+// 	  Any other one is a bug in the circuit.
+pub fn generate(system: &ConstraintSystem, inputs: &[u8]) -> Result<Vec<Field>, RuntimeError> {
+    assert_eq!(inputs.len(), system.inputs.len(), "one value per input");
+
+    let mut witness = vec![Field::ZERO; system.wires.len()];
+    witness[ONE] = Field::ONE;
+    for (wire, val) in system.inputs.iter().zip(inputs) {
+        witness[*wire] = Field::from(*val);
+    }
+
+    for hint in &system.hints {
+        match hint {
+            Hint::Value { output, value } => witness[*output] = value.evaluate(&witness),
+            Hint::Product { output, left, right } => witness[*output] = left.evaluate(&witness) * right.evaluate(&witness),
+            Hint::Bits { value, bits } => {
+                let value = value.evaluate(&witness).value();
+                for (index, bit) in bits.iter().enumerate() {
+                    witness[*bit] = Field::new(value >> index & 1);
+                }
+            }
+            Hint::Inverse { output, value } => witness[*output] = value.evaluate(&witness).inverse().unwrap_or(Field::ZERO),
+            Hint::Division { dividend, divisor, quotient, remainder } => {
+                let dividend = dividend.evaluate(&witness).value();
+                let divisor = divisor.evaluate(&witness).value();
+                witness[*quotient] = Field::new(dividend.checked_div(divisor).unwrap_or(0));
+                witness[*remainder] = Field::new(dividend.checked_rem(divisor).unwrap_or(dividend));
+            }
+        }
+    }
+
+    match system.check(&witness) {
+        Ok(()) => Ok(witness),
+        Err(index) => match &system.constraints[index].panic {
+            Some((panic, binding)) => Err(RuntimeError::Panic {
+                panic: panic.clone(),
+                variable: system.outputs[*binding].0.clone(),
+            }),
+            None => panic!("the witness does not satisfy the constraint {} of the circuit", index),
+        },
+    }
+}
+
+// Computes the witness of a constraint system on named arguments, like Interpreter::run.
+// Returns the value of every binding, in program order.
+pub fn run_named(system: &ConstraintSystem, arguments: &BTreeMap<String, u8>) -> Result<Vec<(String, u8)>, RuntimeError> {
+    let inputs: Vec<&String> = system.inputs.iter().map(|wire| &system.wires[*wire]).collect();
+    if let Some(name) = arguments.keys().find(|name| !inputs.contains(name)) {
+        return Err(RuntimeError::UnknownInput(name.clone()));
+    }
+    let values = inputs
+        .iter()
+        .map(|input| arguments.get(*input).copied().ok_or_else(|| RuntimeError::MissingInput(input.to_string())))
+        .collect::<Result<Vec<u8>, RuntimeError>>()?;
+
+    let witness = generate(system, &values)?;
+    Ok(system
+        .outputs
+        .iter()
+        .map(|(binding, wire)| (binding.clone(), witness[*wire].value() as u8))
+        .collect())
+}
@@ -0,0 +1,121 @@
+use crate::ast::{MethodKind, Operator};
+use crate::arithmetic::{OverflowMode, Panic};
+use crate::ir::instruction::{Arithmetic, Function, Variable};
+
+// The interface of compiled programs, shared by the native backends
+// 	- A program `fn main(a: u8, b: u8)` becomes the function
+//
+// 	  uint32_t rustic_main(uint8_t a, uint8_t b, uint8_t *bindings)
+//
+// 	  which stores the value of every let binding in `bindings`, in program order
+// 	- It returns a status: 0 if it ran to the end, otherwise the panic code in the low byte
+// 	  and the index of the binding that panicked in the next bytes. The bindings before it are stored.
+// 	- Operations are resolved to a Behaviour, so every backend panics, wraps and saturates
+// 	  like the interpreter
+
+pub const SYMBOL_PREFIX: &str = "rustic_";
+
+// What an operation does when its result does not fit in a u8
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Behaviour {
+    // Panics, e.g a plain operator in panic mode or checked_add(..).unwrap()
+    Checked(Panic),
+    Wrapping,
+    // Clamps to 0 or 255. Shifts by 8 bits or more give 0.
+    Saturating,
+    // The overflow flag as a u8
+    Flag,
+}
+
+// The behaviour of an operation under the given overflow mode
+pub fn behaviour(overflow: OverflowMode, operator: &Operator, arithmetic: &Arithmetic) -> Behaviour {
+    match arithmetic {
+        Arithmetic::Panicking => match overflow {
+            OverflowMode::Error | OverflowMode::Panic => Behaviour::Checked(Panic::Overflow(operator.clone())),
+            OverflowMode::Wrap => Behaviour::Wrapping,
+            // There are no saturating shifts
+            OverflowMode::Saturate if matches!(operator, Operator::ShiftLeft | Operator::ShiftRight) => {
+                Behaviour::Checked(Panic::Overflow(operator.clone()))
+            }
+            OverflowMode::Saturate => Behaviour::Saturating,
+        },
+        Arithmetic::Method(MethodKind::Wrapping | MethodKind::Overflowing) => Behaviour::Wrapping,
+        Arithmetic::Method(MethodKind::Saturating) => Behaviour::Saturating,
+        Arithmetic::Method(MethodKind::Checked) => Behaviour::Checked(Panic::Unwrap),
+        Arithmetic::Method(MethodKind::OverflowFlag) => Behaviour::Flag,
+    }
+}
+
+// The panic of a division by zero: checked_div returns None, which the unwrap panics on
+pub fn division_by_zero(arithmetic: &Arithmetic) -> Panic {
+    match arithmetic {
+        Arithmetic::Method(MethodKind::Checked) => Panic::Unwrap,
+        _ => Panic::DivisionByZero,
+    }
+}
+
+// The code of a panic, in the low byte of a status
+pub fn panic_code(panic: &Panic) -> u32 {
+    match panic {
+        Panic::Overflow(Operator::Add) => 1,
+        Panic::Overflow(Operator::Subtract) => 2,
+        Panic::Overflow(Operator::Multiply) => 3,
+        Panic::Overflow(Operator::Divide) => 4,
+        Panic::Overflow(Operator::ShiftLeft) => 5,
+        Panic::Overflow(Operator::ShiftRight) => 6,
+        Panic::Overflow(Operator::BitAnd | Operator::BitOr | Operator::BitXor) => unreachable!("bitwise operations cannot overflow"),
+        Panic::DivisionByZero => 7,
+        Panic::Unwrap => 8,
+    }
+}
+
+// The status of a panic in the initializer of a binding
+pub fn status(panic: &Panic, binding: usize) -> u32 {
+    panic_code(panic) | (binding as u32) << 8
+}
+
+// The panic and the binding of a status, None if it ran to the end
+pub fn decode_status(status: u32) -> Option<(Panic, usize)> {
+    let panic = match status & 0xff {
+        0 => return None,
+        1 => Panic::Overflow(Operator::Add),
+        2 => Panic::Overflow(Operator::Subtract),
+        3 => Panic::Overflow(Operator::Multiply),
+        4 => Panic::Overflow(Operator::Divide),
+        5 => Panic::Overflow(Operator::ShiftLeft),
+        6 => Panic::Overflow(Operator::ShiftRight),
+        7 => Panic::DivisionByZero,
+        8 => Panic::Unwrap,
+        code => panic!("unknown panic code {}", code),
+    };
+    Some((panic, (status >> 8) as usize))
+}
+
+// The let bindings of a function, in program order
+pub fn bindings(function: &Function) -> Vec<String> {
+    function
+        .instructions
+        .iter()
+        .filter_map(|instruction| match instruction.destination() {
+            Variable::Named(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// The index of the binding that every instruction initializes, which is the next one
+// an instruction assigns to. An initializer ends with an assignment to its binding.
+pub fn initialized_bindings(function: &Function) -> Vec<usize> {
+    let mut binding = 0;
+    function
+        .instructions
+        .iter()
+        .map(|instruction| {
+            let index = binding;
+            if let Variable::Named(_) = instruction.destination() {
+                binding += 1;
+            }
+            index
+        })
+        .collect()
+}
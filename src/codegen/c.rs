@@ -0,0 +1,173 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use crate::ast::Operator;
+use crate::arithmetic::{OverflowMode, Panic};
+use crate::codegen::abi::*;
+use crate::ir::instruction::{Function, Instruction, Operand, Variable};
+
+// A C backend, which translates a program into a standalone C99 function
+// 	- A program `fn main(a: u8, b: u8)` becomes `void rustic_main(uint8_t a, uint8_t b, uint8_t *bindings)`,
+// 	  which stores the value of every let binding in `bindings`, in program order
+// 	- Every instruction becomes a uint8_t local. Operands are promoted to int, so an overflow is
+// 	  checked on the exact result before it is converted back to uint8_t.
+// 	- A panic prints Rust's message and calls abort(). Code that includes the function can
+// 	  define rustic_panic as a macro to handle panics itself.
+// 	- Variables named like C keywords or the names the code uses get a leading underscore,
+// 	  which Rust identifiers cannot start with
+pub fn emit(function: &Function, overflow: OverflowMode) -> String {
+    let mut code = String::new();
+    let _ = writeln!(code, "// fn {}({})", function.name, signature(function));
+    code += "#include <stdint.h>\n#include <stdio.h>\n#include <stdlib.h>\n\n";
+    code += "#ifndef rustic_panic\n";
+    code += "static void rustic_panic(const char *binding, const char *message) {\n";
+    code += "    fprintf(stderr, \"thread 'main' panicked in the initializer of '%s':\\n%s\\n\", binding, message);\n";
+    code += "    abort();\n}\n#endif\n\n";
+
+    let parameters: Vec<String> = function
+        .inputs
+        .iter()
+        .map(|input| format!("uint8_t {}", name(input)))
+        .chain(["uint8_t *_bindings".to_string()])
+        .collect();
+    let _ = writeln!(code, "void {}{}({}) {{", SYMBOL_PREFIX, function.name, parameters.join(", "));
+
+    let bindings = bindings(function);
+    let mut body = String::new();
+    let mut declared: HashSet<&Variable> = HashSet::new();
+    // The operands the code reads
+    let mut read: HashSet<&Operand> = HashSet::new();
+    for (instruction, binding) in function.instructions.iter().zip(initialized_bindings(function)) {
+        let _ = writeln!(body, "    // {}", instruction);
+        let value = match instruction {
+            Instruction::Copy { source, .. } => {
+                read.insert(source);
+                operand(source)
+            }
+            Instruction::Binary { operator, arithmetic, left, right, .. } => {
+                read.insert(right);
+                let (x, y) = (operand(left), operand(right));
+                let panic = |panic: Panic| format!("        rustic_panic(\"{}\", \"{}\");\n", bindings[binding], panic.message());
+                if *operator == Operator::Divide {
+                    let _ = write!(body, "    if ({} == 0)\n{}", y, panic(division_by_zero(arithmetic)));
+                }
+
+                // The condition of an overflow, the wrapped result and the saturated one
+                let (overflows, wrapped, saturated) = match operator {
+                    Operator::Add => (Some(format!("{} + {} > 255", x, y)), format!("(uint8_t)({} + {})", x, y), "255"),
+                    Operator::Subtract => (Some(format!("{} < {}", x, y)), format!("(uint8_t)({} - {})", x, y), "0"),
+                    Operator::Multiply => (Some(format!("{} * {} > 255", x, y)), format!("(uint8_t)({} * {})", x, y), "255"),
+                    Operator::Divide => (None, format!("{} / {}", x, y), "255"),
+                    Operator::ShiftLeft => (Some(format!("{} >= 8", y)), format!("(uint8_t)({} << ({} & 7))", x, y), "0"),
+                    Operator::ShiftRight => (Some(format!("{} >= 8", y)), format!("{} >> ({} & 7)", x, y), "0"),
+                    Operator::BitAnd => (None, format!("{} & {}", x, y), "255"),
+                    Operator::BitOr => (None, format!("{} | {}", x, y), "255"),
+                    Operator::BitXor => (None, format!("{} ^ {}", x, y), "255"),
+                };
+                match (behaviour(overflow, operator, arithmetic), overflows) {
+                    // The flag of an operation that cannot overflow is 0, whatever its operands
+                    (Behaviour::Flag, None) => "0".to_string(),
+                    (behaviour, overflows) => {
+                        read.insert(left);
+                        match (behaviour, overflows) {
+                            (Behaviour::Checked(kind), Some(overflows)) => {
+                                let _ = write!(body, "    if ({})\n{}", overflows, panic(kind));
+                                wrapped
+                            }
+                            (Behaviour::Saturating, Some(overflows)) => format!("{} ? {} : {}", overflows, saturated, wrapped),
+                            (Behaviour::Flag, Some(overflows)) => overflows,
+                            _ => wrapped,
+                        }
+                    }
+                }
+            }
+        };
+
+        let destination = instruction.destination();
+        let variable = variable(destination);
+        if declared.insert(destination) {
+            let _ = writeln!(body, "    uint8_t {} = {};", variable, value);
+        } else {
+            let _ = writeln!(body, "    {} = {};", variable, value);
+        }
+        if let Variable::Named(binding) = destination {
+            let index = bindings.iter().position(|known| known == binding).expect("a binding");
+            let _ = writeln!(body, "    _bindings[{}] = {};", index, variable);
+        }
+    }
+
+    // Inputs that are never read would be unused parameters
+    for input in &function.inputs {
+        if !read.contains(&Operand::Variable(Variable::Named(input.clone()))) {
+            let _ = writeln!(code, "    (void){};", name(input));
+        }
+    }
+    code += &body;
+    code += "}\n";
+    code
+}
+
+// A main function that reads the inputs from the command-line arguments, calls the function
+// and prints every binding like `rustic run`
+pub fn emit_main(function: &Function) -> String {
+    let inputs = function.inputs.len();
+    let bindings = bindings(function);
+    let mut code = String::from("\nint main(int argc, char **argv) {\n");
+    let usage: String = function.inputs.iter().map(|input| format!(" <{}>", input)).collect();
+    let _ = writeln!(code, "    if (argc != {}) {{", inputs + 1);
+    let _ = writeln!(code, "        fprintf(stderr, \"usage: %s{}\\n\", argv[0]);", usage);
+    code += "        return 2;\n    }\n";
+
+    // Arrays cannot be empty
+    let _ = writeln!(code, "    uint8_t inputs[{}];", inputs.max(1));
+    let _ = writeln!(code, "    for (int i = 0; i < {}; i++) {{", inputs);
+    code += "        char *end;\n";
+    code += "        unsigned long val = strtoul(argv[i + 1], &end, 10);\n";
+    code += "        if (*argv[i + 1] == '\\0' || *end != '\\0' || val > 255) {\n";
+    code += "            fprintf(stderr, \"The argument '%s' is not a u8.\\n\", argv[i + 1]);\n";
+    code += "            return 2;\n        }\n";
+    code += "        inputs[i] = (uint8_t)val;\n    }\n\n";
+
+    let _ = writeln!(code, "    uint8_t bindings[{}];", bindings.len().max(1));
+    let arguments: String = (0..inputs).map(|index| format!("inputs[{}], ", index)).collect();
+    let _ = writeln!(code, "    {}{}({}bindings);", SYMBOL_PREFIX, function.name, arguments);
+    for (index, binding) in bindings.iter().enumerate() {
+        let _ = writeln!(code, "    printf(\"{} = %u\\n\", (unsigned)bindings[{}]);", binding, index);
+    }
+    code += "    return 0;\n}\n";
+    code
+}
+
+fn signature(function: &Function) -> String {
+    function.inputs.iter().map(|input| format!("{}: u8", input)).collect::<Vec<String>>().join(", ")
+}
+
+// The C keywords, and the names the generated code uses
+const RESERVED: [&str; 43] = [
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else", "enum", "extern", "float", "for",
+    "goto", "if", "inline", "int", "long", "register", "restrict", "return", "short", "signed", "sizeof", "static", "struct",
+    "switch", "typedef", "union", "unsigned", "void", "volatile", "while", "uint8_t", "rustic_panic", "fprintf", "stderr",
+    "abort", "NULL", "bool", "true", "false",
+];
+
+fn name(name: &str) -> String {
+    if RESERVED.contains(&name) {
+        format!("_{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn variable(variable: &Variable) -> String {
+    match variable {
+        Variable::Named(variable) => name(variable),
+        Variable::Temporary(index) => format!("_{}", index),
+        Variable::Versioned { .. } => unreachable!("programs are not in SSA form"),
+    }
+}
+
+fn operand(operand: &Operand) -> String {
+    match operand {
+        Operand::Constant(val) => val.to_string(),
+        Operand::Variable(variable) => self::variable(variable),
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use crate::ast::Operator;
+use crate::arithmetic::{OverflowMode, Panic};
+use crate::codegen::abi::*;
+use crate::ir::instruction::{Function, Instruction, Operand, Variable};
+
+// An LLVM IR backend, which prints a module in the textual format of `.ll` files
+// 	- A program `fn main(a: u8, b: u8)` becomes `i32 @rustic_main(i8 %a, i8 %b, i8* %_bindings)`,
+// 	  with the interface of the other native backends (see abi)
+// 	- Values are i8. Checked operations call the llvm.*.with.overflow.i8 intrinsics and branch
+// 	  to a panic block on overflow, like rustc lowers them. Panic blocks return the status.
+// 	- Variables are defined once, so every variable is an SSA value of the same name, or the constant
+// 	  it copies. The values computed on the way are named after their result, e.g %sum.overflow,
+// 	  and the other names the code adds start with an underscore, which identifiers cannot start with.
+// 	- Pointers are typed (i8*), which LLVM accepts up to version 16
+pub fn emit(function: &Function, overflow: OverflowMode) -> String {
+    let parameters: Vec<String> = function
+        .inputs
+        .iter()
+        .map(|input| format!("i8 zeroext %{}", input))
+        .chain(["i8* %_bindings".to_string()])
+        .collect();
+    let signature: Vec<String> = function.inputs.iter().map(|input| format!("{}: u8", input)).collect();
+
+    let mut emitter = Emitter {
+        overflow,
+        values: HashMap::new(),
+        body: String::from("_start:\n"),
+        panics: String::new(),
+        blocks: 0,
+        intrinsics: BTreeSet::new(),
+        bindings: bindings(function),
+        binding: 0,
+    };
+    for input in &function.inputs {
+        emitter.define(&Variable::Named(input.clone()));
+    }
+
+    for (instruction, binding) in function.instructions.iter().zip(initialized_bindings(function)) {
+        let _ = writeln!(emitter.body, "  ; {}", instruction);
+        emitter.binding = binding;
+        emitter.instruction(instruction);
+    }
+    emitter.body += "  ret i32 0\n";
+
+    let mut code = String::new();
+    let _ = writeln!(code, "; fn {}({})", function.name, signature.join(", "));
+    let _ = writeln!(code, "define i32 @{}{}({}) {{", SYMBOL_PREFIX, function.name, parameters.join(", "));
+    code += &emitter.body;
+    code += &emitter.panics;
+    code += "}\n";
+    if !emitter.intrinsics.is_empty() {
+        code += "\n";
+    }
+    for intrinsic in &emitter.intrinsics {
+        let _ = writeln!(code, "declare {}", intrinsic);
+    }
+    code
+}
+
+struct Emitter {
+    overflow: OverflowMode,
+    // The current value of every variable: the name of an SSA value, or a constant
+    values: HashMap<Variable, String>,
+    body: String,
+    // The panic blocks, after the body
+    panics: String,
+    blocks: usize,
+    // The declarations of the intrinsics the code calls
+    intrinsics: BTreeSet<String>,
+    bindings: Vec<String>,
+    // The index of the binding the current instruction initializes
+    binding: usize,
+}
+
+impl Emitter {
+    // Defines a variable as an SSA value of the same name
+    fn define(&mut self, variable: &Variable) -> String {
+        let name = value_name(variable);
+        self.values.insert(variable.clone(), name.clone());
+        name
+    }
+
+    fn operand(&self, operand: &Operand) -> String {
+        match operand {
+            Operand::Constant(val) => val.to_string(),
+            Operand::Variable(variable) => self.values[variable].clone(),
+        }
+    }
+
+    fn line(&mut self, line: String) {
+        let _ = writeln!(self.body, "  {}", line);
+    }
+
+    // Branches to a new panic block if the condition holds, and continues in a new block otherwise
+    fn panic_if(&mut self, condition: &str, panic: &Panic) {
+        self.blocks += 1;
+        let _ = write!(
+            self.body,
+            "  br i1 {}, label %_panic{}, label %_bb{}\n\n_bb{}:\n",
+            condition, self.blocks, self.blocks, self.blocks
+        );
+        let _ = write!(
+            self.panics,
+            "\n_panic{}:\n  ; {} in '{}'\n  ret i32 {}\n",
+            self.blocks,
+            panic.message(),
+            self.bindings[self.binding],
+            status(panic, self.binding)
+        );
+    }
+
+    // Calls the llvm.*.with.overflow.i8 intrinsic of an operator, and returns its overflow flag
+    fn with_overflow(&mut self, intrinsic: &str, base: &str, x: &str, y: &str) -> String {
+        self.intrinsics.insert(format!("{{ i8, i1 }} @llvm.{}.with.overflow.i8(i8, i8)", intrinsic));
+        self.line(format!("{}.pair = call {{ i8, i1 }} @llvm.{}.with.overflow.i8(i8 {}, i8 {})", base, intrinsic, x, y));
+        self.line(format!("{}.overflow = extractvalue {{ i8, i1 }} {}.pair, 1", base, base));
+        format!("{}.overflow", base)
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        let destination = instruction.destination();
+        let value = match instruction {
+            Instruction::Copy { source, .. } => {
+                let source = self.operand(source);
+                self.values.insert(destination.clone(), source.clone());
+                source
+            }
+            Instruction::Binary { operator, arithmetic, left, right, .. } => {
+                let (x, y) = (self.operand(left), self.operand(right));
+                // The values computed on the way are named after the result
+                let base = value_name(destination);
+
+                if *operator == Operator::Divide {
+                    self.line(format!("{}.zero = icmp eq i8 {}, 0", base, y));
+                    self.panic_if(&format!("{}.zero", base), &division_by_zero(arithmetic));
+                }
+
+                match self.operation(operator, behaviour(self.overflow, operator, arithmetic), &base, &x, &y) {
+                    Some(computed) => {
+                        let name = self.define(destination);
+                        self.line(format!("{} = {}", name, computed));
+                        name
+                    }
+                    // The flag of an operation that cannot overflow
+                    None => {
+                        self.values.insert(destination.clone(), "0".to_string());
+                        "0".to_string()
+                    }
+                }
+            }
+        };
+
+        if let Variable::Named(_) = destination {
+            let slot = format!("%_slot{}", self.binding);
+            self.line(format!("{} = getelementptr inbounds i8, i8* %_bindings, i64 {}", slot, self.binding));
+            self.line(format!("store i8 {}, i8* {}", value, slot));
+        }
+    }
+
+    // Emits the checks of an operation, and returns the instruction that computes its value.
+    // None if the value is the constant 0.
+    fn operation(&mut self, operator: &Operator, behaviour: Behaviour, base: &str, x: &str, y: &str) -> Option<String> {
+        let computed = match operator {
+            Operator::Add | Operator::Subtract | Operator::Multiply => {
+                let (intrinsic, wrapping, saturated) = match operator {
+                    Operator::Add => ("uadd", "add", 255),
+                    Operator::Subtract => ("usub", "sub", 0),
+                    _ => ("umul", "mul", 255),
+                };
+                match behaviour {
+                    Behaviour::Checked(panic) => {
+                        let overflows = self.with_overflow(intrinsic, base, x, y);
+                        self.panic_if(&overflows, &panic);
+                        format!("extractvalue {{ i8, i1 }} {}.pair, 0", base)
+                    }
+                    Behaviour::Wrapping => format!("{} i8 {}, {}", wrapping, x, y),
+                    Behaviour::Flag => format!("zext i1 {} to i8", self.with_overflow(intrinsic, base, x, y)),
+                    // There is no saturating multiplication intrinsic
+                    Behaviour::Saturating if *operator == Operator::Multiply => {
+                        let overflows = self.with_overflow(intrinsic, base, x, y);
+                        self.line(format!("{}.wrapped = extractvalue {{ i8, i1 }} {}.pair, 0", base, base));
+                        format!("select i1 {}, i8 {}, i8 {}.wrapped", overflows, saturated, base)
+                    }
+                    Behaviour::Saturating => {
+                        self.intrinsics.insert(format!("i8 @llvm.{}.sat.i8(i8, i8)", intrinsic));
+                        format!("call i8 @llvm.{}.sat.i8(i8 {}, i8 {})", intrinsic, x, y)
+                    }
+                }
+            }
+            Operator::ShiftLeft | Operator::ShiftRight => {
+                let shift = if *operator == Operator::ShiftLeft { "shl" } else { "lshr" };
+                let overflows = format!("{}.overflow", base);
+                if behaviour != Behaviour::Wrapping {
+                    self.line(format!("{} = icmp uge i8 {}, 8", overflows, y));
+                }
+                match behaviour {
+                    Behaviour::Checked(panic) => {
+                        self.panic_if(&overflows, &panic);
+                        format!("{} i8 {}, {}", shift, x, y)
+                    }
+                    Behaviour::Flag => format!("zext i1 {} to i8", overflows),
+                    // The amount is taken modulo 8, as shifting an i8 by 8 or more gives a poison value
+                    Behaviour::Wrapping => {
+                        self.line(format!("{}.amount = and i8 {}, 7", base, y));
+                        format!("{} i8 {}, {}.amount", shift, x, base)
+                    }
+                    Behaviour::Saturating => {
+                        self.line(format!("{}.amount = and i8 {}, 7", base, y));
+                        self.line(format!("{}.wrapped = {} i8 {}, {}.amount", base, shift, x, base));
+                        format!("select i1 {}, i8 0, i8 {}.wrapped", overflows, base)
+                    }
+                }
+            }
+            // Divisions and bitwise operations cannot overflow
+            _ if behaviour == Behaviour::Flag => return None,
+            Operator::Divide => format!("udiv i8 {}, {}", x, y),
+            Operator::BitAnd => format!("and i8 {}, {}", x, y),
+            Operator::BitOr => format!("or i8 {}, {}", x, y),
+            Operator::BitXor => format!("xor i8 {}, {}", x, y),
+        };
+        Some(computed)
+    }
+}
+
+fn value_name(variable: &Variable) -> String {
+    match variable {
+        Variable::Named(name) => format!("%{}", name),
+        Variable::Temporary(index) => format!("%_{}", index),
+        Variable::Versioned { .. } => unreachable!("programs are not in SSA form"),
+    }
+}
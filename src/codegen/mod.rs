@@ -0,0 +1,8 @@
+pub mod abi;
+pub mod x86_64;
+pub mod riscv;
+pub mod c;
+pub mod wasm;
+pub mod llvm;
+pub mod verilog;
+pub mod regalloc;
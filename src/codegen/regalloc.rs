@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use crate::ir::instruction::{Function, Operand, Variable};
+
+// A linear-scan register allocator over the variables of a function, for the native backends
+// 	- Positions number the points of the function: the inputs are defined at 0, and the
+// 	  instruction at index i is at i + 1
+// 	- The live interval of a variable goes from its definition to its last read. Programs have no
+// 	  control flow, so it covers exactly the positions where the variable is live.
+// 	  Variables that are never read have no interval, and need no register.
+// 	- Intervals are visited by start. The ones that ended are expired and give their register back,
+// 	  so an instruction can write its result to the register of an operand it reads for the last time.
+// 	- When every register is taken, the interval that ends last is spilled, like in Poletto and Sarkar's
+// 	  algorithm: a spilled variable lives in memory for its whole interval
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Interval {
+    pub variable: Variable,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Location<R> {
+    Register(R),
+    Spilled,
+}
+
+pub struct Allocation<R> {
+    // The intervals, by start
+    pub intervals: Vec<Interval>,
+    // The location of every variable with an interval
+    pub locations: HashMap<Variable, Location<R>>,
+}
+
+impl<R: Copy + PartialEq> Allocation<R> {
+    // The register of a variable, None if it is spilled or never read
+    pub fn register(&self, variable: &Variable) -> Option<R> {
+        match self.locations.get(variable) {
+            Some(Location::Register(register)) => Some(*register),
+            _ => None,
+        }
+    }
+
+    // The registers that hold a variable, in the order they were given to the allocator
+    pub fn used_registers(&self, registers: &[R]) -> Vec<R> {
+        registers
+            .iter()
+            .filter(|register| self.locations.values().any(|location| *location == Location::Register(**register)))
+            .copied()
+            .collect()
+    }
+}
+
+// The live interval of every variable that is read, by start
+pub fn live_intervals(function: &Function) -> Vec<Interval> {
+    let mut definitions: Vec<(Variable, usize)> = function
+        .inputs
+        .iter()
+        .map(|input| (Variable::Named(input.clone()), 0))
+        .collect();
+    let mut last_reads = HashMap::new();
+    for (index, instruction) in function.instructions.iter().enumerate() {
+        for operand in instruction.operands() {
+            if let Operand::Variable(variable) = operand {
+                last_reads.insert(variable.clone(), index + 1);
+            }
+        }
+        definitions.push((instruction.destination().clone(), index + 1));
+    }
+
+    definitions
+        .into_iter()
+        .filter_map(|(variable, start)| {
+            let end = *last_reads.get(&variable)?;
+            Some(Interval { variable, start, end })
+        })
+        .collect()
+}
+
+// Allocates the given registers to the variables of a function. Registers are taken in order.
+pub fn allocate<R: Copy + PartialEq>(function: &Function, registers: &[R]) -> Allocation<R> {
+    let intervals = live_intervals(function);
+    let mut locations = HashMap::new();
+    // The interval that holds every register
+    let mut holders: Vec<Option<usize>> = vec![None; registers.len()];
+
+    for (index, interval) in intervals.iter().enumerate() {
+        for holder in holders.iter_mut() {
+            if holder.is_some_and(|held| intervals[held].end <= interval.start) {
+                *holder = None;
+            }
+        }
+
+        let register = match holders.iter().position(|holder| holder.is_none()) {
+            Some(free) => Some(free),
+            None => {
+                // Spills the interval that ends last, which may be this one
+                let last = (0..registers.len()).max_by_key(|register| holders[*register].map(|held| intervals[held].end));
+                match last {
+                    Some(register) if intervals[holders[register].unwrap()].end > interval.end => {
+                        let spilled = &intervals[holders[register].unwrap()];
+                        locations.insert(spilled.variable.clone(), Location::Spilled);
+                        Some(register)
+                    }
+                    _ => None,
+                }
+            }
+        };
+
+        match register {
+            Some(register) => {
+                holders[register] = Some(index);
+                locations.insert(interval.variable.clone(), Location::Register(registers[register]));
+            }
+            None => {
+                locations.insert(interval.variable.clone(), Location::Spilled);
+            }
+        }
+    }
+
+    Allocation { intervals, locations }
+}
+
+impl<R: std::fmt::Display> std::fmt::Display for Allocation<R> {
+    // One interval per line, with its location, e.g `sum  [1, 3]  rbx`
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let names: Vec<String> = self.intervals.iter().map(|interval| interval.variable.to_string()).collect();
+        let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+        let ranges: Vec<String> = self.intervals.iter().map(|interval| format!("[{}, {}]", interval.start, interval.end)).collect();
+        let range_width = ranges.iter().map(|range| range.len()).max().unwrap_or(0);
+
+        let lines: Vec<String> = self
+            .intervals
+            .iter()
+            .zip(names.iter().zip(&ranges))
+            .map(|(interval, (name, range))| {
+                let location = match &self.locations[&interval.variable] {
+                    Location::Register(register) => register.to_string(),
+                    Location::Spilled => "spilled".to_string(),
+                };
+                format!("{:width$}  {:range_width$}  {}", name, range, location, width = width, range_width = range_width)
+            })
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use crate::ast::Operator;
+use crate::arithmetic::OverflowMode;
+use crate::codegen::abi::*;
+use crate::codegen::regalloc::{allocate, Allocation, Location};
+use crate::codegen::riscv::isa::*;
+use crate::ir::instruction::{self as ir, Function, Operand, Variable};
+
+// An RV32IM backend
+// 	- The function follows the standard calling convention: the inputs and then the pointer
+// 	  to the bindings are passed in a0 to a7, and on the stack after that. The status is returned in a0.
+// 	- Variables live in the registers the allocator gives them (see regalloc). Spilled inputs and
+// 	  temporaries live in the stack frame, and spilled bindings in the bindings buffer, whose
+// 	  address is kept in t6. Every binding is also stored in the buffer when it is initialized.
+// 	- Every instruction loads its left operand in t0 and its right operand in t1, computes the
+// 	  result in t0 and stores it. u8 values are zero-extended in registers, so an addition or a
+// 	  multiplication overflows if its result has a bit above the lowest 8, which is read into t2.
+// 	- Every panic loads its status in a stub at the end of the function, and jumps to the epilogue
+pub struct Assembly {
+    pub name: String,
+    pub symbol: String,
+    pub inputs: Vec<String>,
+    pub bindings: Vec<String>,
+    pub lines: Vec<Line>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Line {
+    Label(String),
+    Comment(String),
+    Instruction(Instruction),
+}
+
+impl Assembly {
+    // The machine code of the function, which starts at its first word
+    pub fn code(&self) -> Vec<u32> {
+        self.instructions().map(|instruction| instruction.encode()).collect()
+    }
+
+    fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Instruction(instruction) => Some(instruction),
+            _ => None,
+        })
+    }
+
+    // The offset in bytes of every label from the start of the function
+    fn labels(&self) -> HashMap<String, i32> {
+        let mut labels = HashMap::new();
+        let mut address = 0;
+        for line in &self.lines {
+            match line {
+                Line::Label(label) => {
+                    labels.insert(label.clone(), address);
+                }
+                Line::Instruction(_) => address += 4,
+                Line::Comment(_) => {}
+            }
+        }
+        labels
+    }
+}
+
+pub fn emit(function: &Function, overflow: OverflowMode) -> Assembly {
+    let symbol = format!("{}{}", SYMBOL_PREFIX, function.name);
+    let mut emitter = Emitter {
+        overflow,
+        symbol: symbol.clone(),
+        locations: HashMap::new(),
+        bindings: HashMap::new(),
+        lines: Vec::new(),
+        jumps: Vec::new(),
+        panics: Vec::new(),
+    };
+
+    // Variables in registers, then a byte of the frame for every spilled input and temporary,
+    // below the saved registers. Bindings that are spilled are read back from the bindings buffer.
+    let allocation = allocation(function);
+    let saved: Vec<Register> = allocation
+        .used_registers(&REGISTERS)
+        .into_iter()
+        .filter(|register| !TEMPORARIES.contains(register))
+        .collect();
+    let bindings = bindings(function);
+    let mut slots = 0;
+    let mut variables: Vec<Variable> = function.inputs.iter().map(|input| Variable::Named(input.clone())).collect();
+    variables.extend(function.instructions.iter().map(|instruction| instruction.destination().clone()));
+    for variable in variables {
+        let location = match (allocation.locations.get(&variable), &variable) {
+            (Some(Location::Register(register)), _) => Home::Register(*register),
+            (_, Variable::Named(name)) if bindings.contains(name) => continue,
+            // Inputs that are never read are not kept
+            (None, Variable::Named(_)) => continue,
+            _ => {
+                slots += 1;
+                Home::Memory(Register::SP, slots - 1)
+            }
+        };
+        emitter.locations.insert(variable, location);
+    }
+    for (index, binding) in bindings.iter().enumerate() {
+        let variable = Variable::Named(binding.clone());
+        let slot = Home::Memory(Register::T6, index as i32);
+        emitter.locations.entry(variable.clone()).or_insert(slot);
+        emitter.bindings.insert(variable, slot);
+    }
+    // Keeps the stack aligned to 16 bytes
+    let frame = (slots + 4 * saved.len() as i32 + 15) / 16 * 16;
+    assert!(frame < 2048 && bindings.len() < 2048, "the frame and the bindings are addressed with 12 bit offsets");
+    // The saved registers are at the top of the frame
+    let save_slot = |index: usize| frame - 4 * (index as i32 + 1);
+
+    if frame > 0 {
+        emitter.immediate(ImmediateOp::Addi, Register::SP, Register::SP, -frame);
+    }
+    for (index, register) in saved.iter().enumerate() {
+        emitter.push(Instruction::Store { operation: StoreOp::Sw, rs1: Register::SP, rs2: *register, offset: save_slot(index) });
+    }
+    // Arguments on the stack are above the frame
+    let stack_argument = |index: usize| frame + 4 * (index as i32 - 8);
+    for (index, input) in function.inputs.iter().enumerate() {
+        let Some(location) = emitter.locations.get(&Variable::Named(input.clone())).copied() else {
+            continue;
+        };
+        let source = match Register::argument(index) {
+            Some(register) => register,
+            None => {
+                emitter.push(Instruction::Load { operation: LoadOp::Lbu, rd: Register::T0, rs1: Register::SP, offset: stack_argument(index) });
+                Register::T0
+            }
+        };
+        emitter.store(source, location);
+    }
+    let pointer = function.inputs.len();
+    match Register::argument(pointer) {
+        Some(register) => emitter.immediate(ImmediateOp::Addi, Register::T6, register, 0),
+        None => emitter.push(Instruction::Load { operation: LoadOp::Lw, rd: Register::T6, rs1: Register::SP, offset: stack_argument(pointer) }),
+    }
+
+    for (instruction, binding) in function.instructions.iter().zip(initialized_bindings(function)) {
+        emitter.instruction(instruction, binding);
+    }
+
+    let epilogue = format!(".L{}_return", symbol);
+    emitter.load_immediate(Register::A0, 0);
+    emitter.lines.push(Line::Label(epilogue.clone()));
+    for (index, register) in saved.iter().enumerate() {
+        emitter.push(Instruction::Load { operation: LoadOp::Lw, rd: *register, rs1: Register::SP, offset: save_slot(index) });
+    }
+    if frame > 0 {
+        emitter.immediate(ImmediateOp::Addi, Register::SP, Register::SP, frame);
+    }
+    emitter.push(Instruction::Jalr { rd: Register::ZERO, rs1: Register::RA, offset: 0 });
+    for (label, status) in std::mem::take(&mut emitter.panics) {
+        emitter.lines.push(Line::Label(label));
+        emitter.load_immediate(Register::A0, status as i32);
+        emitter.jump(Instruction::Jal { rd: Register::ZERO, offset: 0 }, &epilogue);
+    }
+
+    let mut assembly = Assembly {
+        name: function.name.clone(),
+        symbol,
+        inputs: function.inputs.clone(),
+        bindings,
+        lines: emitter.lines,
+    };
+
+    // Resolve the offsets of the jumps
+    let labels = assembly.labels();
+    let mut address = 0;
+    let mut jumps = emitter.jumps.into_iter().peekable();
+    for (index, line) in assembly.lines.iter_mut().enumerate() {
+        if let Line::Instruction(instruction) = line {
+            if let Some((_, label)) = jumps.next_if(|(jump, _)| *jump == index) {
+                let target = labels[&label] - address;
+                match instruction {
+                    Instruction::Branch { offset, .. } | Instruction::Jal { offset, .. } => *offset = target,
+                    _ => unreachable!("only branches and jumps have a target"),
+                }
+            }
+            address += 4;
+        }
+    }
+    assembly
+}
+
+// The registers variables are allocated to: t3 to t5, then the saved registers s1 to s11
+pub const REGISTERS: [Register; 14] = [
+    Register(28),
+    Register(29),
+    Register(30),
+    Register(9),
+    Register(18),
+    Register(19),
+    Register(20),
+    Register(21),
+    Register(22),
+    Register(23),
+    Register(24),
+    Register(25),
+    Register(26),
+    Register(27),
+];
+const TEMPORARIES: [Register; 3] = [Register(28), Register(29), Register(30)];
+
+// The registers of the variables of a function
+pub fn allocation(function: &Function) -> Allocation<Register> {
+    allocate(function, &REGISTERS)
+}
+
+// Where a variable lives: a register, or a base register and an offset
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Home {
+    Register(Register),
+    Memory(Register, i32),
+}
+
+struct Emitter {
+    overflow: OverflowMode,
+    symbol: String,
+    // Where every variable is read from
+    locations: HashMap<Variable, Home>,
+    // The slot of every binding in the bindings buffer
+    bindings: HashMap<Variable, Home>,
+    lines: Vec<Line>,
+    // The line and target label of every branch and jump
+    jumps: Vec<(usize, String)>,
+    // The label and status of every panic stub
+    panics: Vec<(String, u32)>,
+}
+
+impl Emitter {
+    fn push(&mut self, instruction: Instruction) {
+        self.lines.push(Line::Instruction(instruction));
+    }
+
+    fn register(&mut self, operation: RegisterOp, rd: Register, rs1: Register, rs2: Register) {
+        self.push(Instruction::Register { operation, rd, rs1, rs2 });
+    }
+
+    fn immediate(&mut self, operation: ImmediateOp, rd: Register, rs1: Register, imm: i32) {
+        self.push(Instruction::Immediate { operation, rd, rs1, imm });
+    }
+
+    // li, with a lui for the upper bits of values that do not fit in 12 bits
+    fn load_immediate(&mut self, rd: Register, val: i32) {
+        if (-2048..2048).contains(&val) {
+            return self.immediate(ImmediateOp::Addi, rd, Register::ZERO, val);
+        }
+        // addi sign-extends the lower bits, which the upper ones make up for
+        let lower = (val << 20) >> 20;
+        self.push(Instruction::Lui { rd, imm: ((val - lower) as u32 >> 12) as i32 });
+        self.immediate(ImmediateOp::Addi, rd, rd, lower);
+    }
+
+    fn jump(&mut self, instruction: Instruction, label: &str) {
+        self.jumps.push((self.lines.len(), label.to_string()));
+        self.push(instruction);
+    }
+
+    // Branches to the stub of a panic when the register is not zero, or zero
+    fn panic_if(&mut self, operation: BranchOp, register: Register, status: u32) {
+        let label = match self.panics.iter().find(|(_, known)| *known == status) {
+            Some((label, _)) => label.clone(),
+            None => {
+                let label = format!(".L{}_panic{}", self.symbol, self.panics.len());
+                self.panics.push((label.clone(), status));
+                label
+            }
+        };
+        self.jump(Instruction::Branch { operation, rs1: register, rs2: Register::ZERO, offset: 0 }, &label);
+    }
+
+    fn load(&mut self, operand: &Operand, rd: Register) {
+        match operand {
+            Operand::Constant(val) => self.load_immediate(rd, *val as i32),
+            Operand::Variable(variable) => match self.locations[variable] {
+                Home::Register(register) => self.immediate(ImmediateOp::Addi, rd, register, 0),
+                Home::Memory(rs1, offset) => self.push(Instruction::Load { operation: LoadOp::Lbu, rd, rs1, offset }),
+            },
+        }
+    }
+
+    fn store(&mut self, source: Register, home: Home) {
+        match home {
+            Home::Register(register) => self.immediate(ImmediateOp::Addi, register, source, 0),
+            Home::Memory(rs1, offset) => self.push(Instruction::Store { operation: StoreOp::Sb, rs1, rs2: source, offset }),
+        }
+    }
+
+    fn instruction(&mut self, instruction: &ir::Instruction, binding: usize) {
+        self.lines.push(Line::Comment(instruction.to_string()));
+        let (t0, t1, t2) = (Register::T0, Register::T1, Register::T2);
+        match instruction {
+            ir::Instruction::Copy { source, .. } => self.load(source, t0),
+            ir::Instruction::Binary { operator, arithmetic, left, right, .. } => {
+                self.load(left, t0);
+                self.load(right, t1);
+                let behaviour = behaviour(self.overflow, operator, arithmetic);
+                match operator {
+                    Operator::Add => {
+                        self.register(RegisterOp::Add, t0, t0, t1);
+                        self.immediate(ImmediateOp::Srli, t2, t0, 8);
+                        self.carry(&behaviour, binding, true);
+                    }
+                    Operator::Subtract => {
+                        self.register(RegisterOp::Sltu, t2, t0, t1);
+                        self.register(RegisterOp::Sub, t0, t0, t1);
+                        self.carry(&behaviour, binding, false);
+                    }
+                    Operator::Multiply => {
+                        self.register(RegisterOp::Mul, t0, t0, t1);
+                        self.immediate(ImmediateOp::Srli, t2, t0, 8);
+                        self.register(RegisterOp::Sltu, t2, Register::ZERO, t2);
+                        self.carry(&behaviour, binding, true);
+                    }
+                    Operator::Divide => {
+                        self.panic_if(BranchOp::Beq, t1, status(&division_by_zero(arithmetic), binding));
+                        // A u8 division never overflows
+                        match behaviour {
+                            Behaviour::Flag => self.load_immediate(t0, 0),
+                            _ => self.register(RegisterOp::Divu, t0, t0, t1),
+                        }
+                    }
+                    Operator::ShiftLeft | Operator::ShiftRight => {
+                        // t2 is 1 if the amount is less than 8
+                        self.immediate(ImmediateOp::Sltiu, t2, t1, 8);
+                        if let Behaviour::Flag = behaviour {
+                            self.immediate(ImmediateOp::Xori, t0, t2, 1);
+                        } else {
+                            match &behaviour {
+                                Behaviour::Checked(panic) => self.panic_if(BranchOp::Beq, t2, status(panic, binding)),
+                                _ => self.immediate(ImmediateOp::Andi, t1, t1, 7),
+                            }
+                            if *operator == Operator::ShiftLeft {
+                                self.register(RegisterOp::Sll, t0, t0, t1);
+                                self.immediate(ImmediateOp::Andi, t0, t0, 255);
+                            } else {
+                                self.register(RegisterOp::Srl, t0, t0, t1);
+                            }
+                            // Larger amounts give 0
+                            if let Behaviour::Saturating = behaviour {
+                                self.register(RegisterOp::Sub, t2, Register::ZERO, t2);
+                                self.register(RegisterOp::And, t0, t0, t2);
+                            }
+                        }
+                    }
+                    Operator::BitAnd | Operator::BitOr | Operator::BitXor => match behaviour {
+                        Behaviour::Flag => self.load_immediate(t0, 0),
+                        _ => {
+                            let operation = match operator {
+                                Operator::BitAnd => RegisterOp::And,
+                                Operator::BitOr => RegisterOp::Or,
+                                _ => RegisterOp::Xor,
+                            };
+                            self.register(operation, t0, t0, t1);
+                        }
+                    },
+                }
+            }
+        }
+        let destination = instruction.destination();
+        if let Some(slot) = self.bindings.get(destination).copied() {
+            self.store(t0, slot);
+        }
+        if let Some(location) = self.locations.get(destination).copied() {
+            if self.bindings.get(destination) != Some(&location) {
+                self.store(t0, location);
+            }
+        }
+    }
+
+    // Handles the overflow of an addition, subtraction or multiplication, which is 1 in t2.
+    // Overflows saturate to 255 if `upwards`, and to 0 otherwise.
+    fn carry(&mut self, behaviour: &Behaviour, binding: usize, upwards: bool) {
+        let (t0, t2) = (Register::T0, Register::T2);
+        match behaviour {
+            Behaviour::Checked(panic) => self.panic_if(BranchOp::Bne, t2, status(panic, binding)),
+            Behaviour::Wrapping => self.immediate(ImmediateOp::Andi, t0, t0, 255),
+            // Sets every bit, then keeps the lowest 8
+            Behaviour::Saturating if upwards => {
+                self.register(RegisterOp::Sub, t2, Register::ZERO, t2);
+                self.register(RegisterOp::Or, t0, t0, t2);
+                self.immediate(ImmediateOp::Andi, t0, t0, 255);
+            }
+            // Clears every bit
+            Behaviour::Saturating => {
+                self.immediate(ImmediateOp::Addi, t2, t2, -1);
+                self.register(RegisterOp::And, t0, t0, t2);
+            }
+            Behaviour::Flag => self.immediate(ImmediateOp::Addi, t0, t2, 0),
+        }
+    }
+}
+
+impl std::fmt::Display for Assembly {
+    // GNU assembler code, with the targets of branches and jumps as labels
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let inputs = self.inputs.iter().map(|input| format!("{}: u8", input)).collect::<Vec<String>>().join(", ");
+        writeln!(f, "# fn {}({})", self.name, inputs)?;
+        writeln!(f, "\t.text\n\t.globl\t{}\n\t.type\t{}, @function\n{}:", self.symbol, self.symbol, self.symbol)?;
+
+        let targets: HashMap<i32, String> = self.labels().into_iter().map(|(label, address)| (address, label)).collect();
+        let mut address = 0;
+        for line in &self.lines {
+            match line {
+                Line::Label(label) => writeln!(f, "{}:", label)?,
+                Line::Comment(comment) => writeln!(f, "\t# {}", comment)?,
+                Line::Instruction(instruction) => {
+                    let target = match instruction {
+                        Instruction::Branch { offset, .. } | Instruction::Jal { offset, .. } => targets[&(address + offset)].clone(),
+                        _ => String::new(),
+                    };
+                    writeln!(f, "\t{}", instruction.format(&target))?;
+                    address += 4;
+                }
+            }
+        }
+        write!(f, "\t.size\t{}, .-{}", self.symbol, self.symbol)
+    }
+}
@@ -0,0 +1,358 @@
+// The RV32IM instruction set: the 32-bit base integer instructions and the multiplication extension
+// 	- Instructions are encoded to and decoded from their 32-bit machine words
+// 	- Immediates are kept as the signed values they stand for, e.g the offset of a branch in bytes
+// 	- fence and the CSR instructions are left out, ecall and ebreak stop the simulator
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, PartialOrd, Ord)]
+pub struct Register(pub u8);
+
+impl Register {
+    pub const ZERO: Register = Register(0);
+    pub const RA: Register = Register(1);
+    pub const SP: Register = Register(2);
+    pub const T0: Register = Register(5);
+    pub const T1: Register = Register(6);
+    pub const T2: Register = Register(7);
+    pub const A0: Register = Register(10);
+    pub const T6: Register = Register(31);
+
+    // The argument registers a0 to a7
+    pub fn argument(index: usize) -> Option<Register> {
+        (index < 8).then(|| Register(10 + index as u8))
+    }
+}
+
+const NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7", "s2", "s3",
+    "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+// rd = rs1 op rs2
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum RegisterOp {
+    Add,
+    Sub,
+    Sll,
+    Slt,
+    Sltu,
+    Xor,
+    Srl,
+    Sra,
+    Or,
+    And,
+    Mul,
+    Mulh,
+    Mulhsu,
+    Mulhu,
+    Div,
+    Divu,
+    Rem,
+    Remu,
+}
+
+// rd = rs1 op imm
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ImmediateOp {
+    Addi,
+    Slti,
+    Sltiu,
+    Xori,
+    Ori,
+    Andi,
+    Slli,
+    Srli,
+    Srai,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum LoadOp {
+    Lb,
+    Lh,
+    Lw,
+    Lbu,
+    Lhu,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum StoreOp {
+    Sb,
+    Sh,
+    Sw,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum BranchOp {
+    Beq,
+    Bne,
+    Blt,
+    Bge,
+    Bltu,
+    Bgeu,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Instruction {
+    Register { operation: RegisterOp, rd: Register, rs1: Register, rs2: Register },
+    Immediate { operation: ImmediateOp, rd: Register, rs1: Register, imm: i32 },
+    // rd = imm << 12
+    Lui { rd: Register, imm: i32 },
+    // rd = pc + (imm << 12)
+    Auipc { rd: Register, imm: i32 },
+    // rd = memory[rs1 + offset]
+    Load { operation: LoadOp, rd: Register, rs1: Register, offset: i32 },
+    // memory[rs1 + offset] = rs2
+    Store { operation: StoreOp, rs1: Register, rs2: Register, offset: i32 },
+    // Jumps by offset bytes if the condition holds
+    Branch { operation: BranchOp, rs1: Register, rs2: Register, offset: i32 },
+    // rd = pc + 4, then jumps by offset bytes
+    Jal { rd: Register, offset: i32 },
+    // rd = pc + 4, then jumps to rs1 + offset
+    Jalr { rd: Register, rs1: Register, offset: i32 },
+    Ecall,
+    Ebreak,
+}
+
+const REGISTER_OPS: [(RegisterOp, &str, u32, u32); 18] = [
+    (RegisterOp::Add, "add", 0, 0x00),
+    (RegisterOp::Sub, "sub", 0, 0x20),
+    (RegisterOp::Sll, "sll", 1, 0x00),
+    (RegisterOp::Slt, "slt", 2, 0x00),
+    (RegisterOp::Sltu, "sltu", 3, 0x00),
+    (RegisterOp::Xor, "xor", 4, 0x00),
+    (RegisterOp::Srl, "srl", 5, 0x00),
+    (RegisterOp::Sra, "sra", 5, 0x20),
+    (RegisterOp::Or, "or", 6, 0x00),
+    (RegisterOp::And, "and", 7, 0x00),
+    (RegisterOp::Mul, "mul", 0, 0x01),
+    (RegisterOp::Mulh, "mulh", 1, 0x01),
+    (RegisterOp::Mulhsu, "mulhsu", 2, 0x01),
+    (RegisterOp::Mulhu, "mulhu", 3, 0x01),
+    (RegisterOp::Div, "div", 4, 0x01),
+    (RegisterOp::Divu, "divu", 5, 0x01),
+    (RegisterOp::Rem, "rem", 6, 0x01),
+    (RegisterOp::Remu, "remu", 7, 0x01),
+];
+
+// The shifts keep their kind in the upper bits of the immediate
+const IMMEDIATE_OPS: [(ImmediateOp, &str, u32, u32); 9] = [
+    (ImmediateOp::Addi, "addi", 0, 0),
+    (ImmediateOp::Slti, "slti", 2, 0),
+    (ImmediateOp::Sltiu, "sltiu", 3, 0),
+    (ImmediateOp::Xori, "xori", 4, 0),
+    (ImmediateOp::Ori, "ori", 6, 0),
+    (ImmediateOp::Andi, "andi", 7, 0),
+    (ImmediateOp::Slli, "slli", 1, 0x00),
+    (ImmediateOp::Srli, "srli", 5, 0x00),
+    (ImmediateOp::Srai, "srai", 5, 0x20),
+];
+
+const LOAD_OPS: [(LoadOp, &str, u32); 5] = [
+    (LoadOp::Lb, "lb", 0),
+    (LoadOp::Lh, "lh", 1),
+    (LoadOp::Lw, "lw", 2),
+    (LoadOp::Lbu, "lbu", 4),
+    (LoadOp::Lhu, "lhu", 5),
+];
+
+const STORE_OPS: [(StoreOp, &str, u32); 3] = [(StoreOp::Sb, "sb", 0), (StoreOp::Sh, "sh", 1), (StoreOp::Sw, "sw", 2)];
+
+const BRANCH_OPS: [(BranchOp, &str, u32); 6] = [
+    (BranchOp::Beq, "beq", 0),
+    (BranchOp::Bne, "bne", 1),
+    (BranchOp::Blt, "blt", 4),
+    (BranchOp::Bge, "bge", 5),
+    (BranchOp::Bltu, "bltu", 6),
+    (BranchOp::Bgeu, "bgeu", 7),
+];
+
+const OP: u32 = 0b0110011;
+const OP_IMM: u32 = 0b0010011;
+const LUI: u32 = 0b0110111;
+const AUIPC: u32 = 0b0010111;
+const LOAD: u32 = 0b0000011;
+const STORE: u32 = 0b0100011;
+const BRANCH: u32 = 0b1100011;
+const JAL: u32 = 0b1101111;
+const JALR: u32 = 0b1100111;
+const ECALL: u32 = 0x0000_0073;
+const EBREAK: u32 = 0x0010_0073;
+
+fn fields(rd: Register, funct3: u32, rs1: Register) -> u32 {
+    (rd.0 as u32) << 7 | funct3 << 12 | (rs1.0 as u32) << 15
+}
+
+// The bits from `high` down to `low` of a value
+fn bits(value: u32, high: u32, low: u32) -> u32 {
+    (value >> low) & ((1 << (high - low + 1)) - 1)
+}
+
+// Sign-extends the lowest `width` bits of a value
+fn sign_extend(value: u32, width: u32) -> i32 {
+    ((value << (32 - width)) as i32) >> (32 - width)
+}
+
+impl Instruction {
+    // The machine word of an instruction. Immediates have to fit in their field.
+    pub fn encode(&self) -> u32 {
+        match *self {
+            Instruction::Register { operation, rd, rs1, rs2 } => {
+                let (_, _, funct3, funct7) = REGISTER_OPS.iter().find(|entry| entry.0 == operation).unwrap();
+                OP | fields(rd, *funct3, rs1) | (rs2.0 as u32) << 20 | funct7 << 25
+            }
+            Instruction::Immediate { operation, rd, rs1, imm } => {
+                let (_, _, funct3, kind) = IMMEDIATE_OPS.iter().find(|entry| entry.0 == operation).unwrap();
+                assert!((-2048..2048).contains(&imm), "immediate out of range: {}", imm);
+                OP_IMM | fields(rd, *funct3, rs1) | (imm as u32 & 0xfff) << 20 | kind << 25
+            }
+            Instruction::Lui { rd, imm } => LUI | (rd.0 as u32) << 7 | (imm as u32) << 12,
+            Instruction::Auipc { rd, imm } => AUIPC | (rd.0 as u32) << 7 | (imm as u32) << 12,
+            Instruction::Load { operation, rd, rs1, offset } => {
+                let (_, _, funct3) = LOAD_OPS.iter().find(|entry| entry.0 == operation).unwrap();
+                assert!((-2048..2048).contains(&offset), "offset out of range: {}", offset);
+                LOAD | fields(rd, *funct3, rs1) | (offset as u32 & 0xfff) << 20
+            }
+            Instruction::Store { operation, rs1, rs2, offset } => {
+                let (_, _, funct3) = STORE_OPS.iter().find(|entry| entry.0 == operation).unwrap();
+                assert!((-2048..2048).contains(&offset), "offset out of range: {}", offset);
+                let offset = offset as u32;
+                STORE | bits(offset, 4, 0) << 7 | funct3 << 12 | (rs1.0 as u32) << 15 | (rs2.0 as u32) << 20 | bits(offset, 11, 5) << 25
+            }
+            Instruction::Branch { operation, rs1, rs2, offset } => {
+                let (_, _, funct3) = BRANCH_OPS.iter().find(|entry| entry.0 == operation).unwrap();
+                assert!((-4096..4096).contains(&offset) && offset % 2 == 0, "branch out of range: {}", offset);
+                let offset = offset as u32;
+                BRANCH
+                    | bits(offset, 11, 11) << 7
+                    | bits(offset, 4, 1) << 8
+                    | funct3 << 12
+                    | (rs1.0 as u32) << 15
+                    | (rs2.0 as u32) << 20
+                    | bits(offset, 10, 5) << 25
+                    | bits(offset, 12, 12) << 31
+            }
+            Instruction::Jal { rd, offset } => {
+                assert!((-(1 << 20)..1 << 20).contains(&offset) && offset % 2 == 0, "jump out of range: {}", offset);
+                let offset = offset as u32;
+                JAL | (rd.0 as u32) << 7
+                    | bits(offset, 19, 12) << 12
+                    | bits(offset, 11, 11) << 20
+                    | bits(offset, 10, 1) << 21
+                    | bits(offset, 20, 20) << 31
+            }
+            Instruction::Jalr { rd, rs1, offset } => {
+                assert!((-2048..2048).contains(&offset), "offset out of range: {}", offset);
+                JALR | fields(rd, 0, rs1) | (offset as u32 & 0xfff) << 20
+            }
+            Instruction::Ecall => ECALL,
+            Instruction::Ebreak => EBREAK,
+        }
+    }
+
+    // The instruction of a machine word, None if it is not an RV32IM instruction
+    pub fn decode(word: u32) -> Option<Instruction> {
+        let rd = Register(bits(word, 11, 7) as u8);
+        let funct3 = bits(word, 14, 12);
+        let rs1 = Register(bits(word, 19, 15) as u8);
+        let rs2 = Register(bits(word, 24, 20) as u8);
+        let funct7 = bits(word, 31, 25);
+        let imm = sign_extend(bits(word, 31, 20), 12);
+
+        match bits(word, 6, 0) {
+            OP => REGISTER_OPS
+                .iter()
+                .find(|entry| entry.2 == funct3 && entry.3 == funct7)
+                .map(|entry| Instruction::Register { operation: entry.0, rd, rs1, rs2 }),
+            OP_IMM => {
+                let shift = matches!(funct3, 1 | 5);
+                let kind = if shift { funct7 } else { 0 };
+                let imm = if shift { bits(word, 24, 20) as i32 } else { imm };
+                IMMEDIATE_OPS
+                    .iter()
+                    .find(|entry| entry.2 == funct3 && entry.3 == kind)
+                    .map(|entry| Instruction::Immediate { operation: entry.0, rd, rs1, imm })
+            }
+            LUI => Some(Instruction::Lui { rd, imm: (word >> 12) as i32 }),
+            AUIPC => Some(Instruction::Auipc { rd, imm: (word >> 12) as i32 }),
+            LOAD => LOAD_OPS
+                .iter()
+                .find(|entry| entry.2 == funct3)
+                .map(|entry| Instruction::Load { operation: entry.0, rd, rs1, offset: imm }),
+            STORE => {
+                let offset = sign_extend(bits(word, 31, 25) << 5 | bits(word, 11, 7), 12);
+                STORE_OPS
+                    .iter()
+                    .find(|entry| entry.2 == funct3)
+                    .map(|entry| Instruction::Store { operation: entry.0, rs1, rs2, offset })
+            }
+            BRANCH => {
+                let offset = bits(word, 31, 31) << 12 | bits(word, 7, 7) << 11 | bits(word, 30, 25) << 5 | bits(word, 11, 8) << 1;
+                BRANCH_OPS.iter().find(|entry| entry.2 == funct3).map(|entry| Instruction::Branch {
+                    operation: entry.0,
+                    rs1,
+                    rs2,
+                    offset: sign_extend(offset, 13),
+                })
+            }
+            JAL => {
+                let offset = bits(word, 31, 31) << 20 | bits(word, 19, 12) << 12 | bits(word, 20, 20) << 11 | bits(word, 30, 21) << 1;
+                Some(Instruction::Jal { rd, offset: sign_extend(offset, 21) })
+            }
+            JALR if funct3 == 0 => Some(Instruction::Jalr { rd, rs1, offset: imm }),
+            _ if word == ECALL => Some(Instruction::Ecall),
+            _ if word == EBREAK => Some(Instruction::Ebreak),
+            _ => None,
+        }
+    }
+
+    // The mnemonic and operands, with `target` in place of the offset of a branch or jump
+    pub fn format(&self, target: &str) -> String {
+        match *self {
+            Instruction::Register { operation, rd, rs1, rs2 } => {
+                let name = REGISTER_OPS.iter().find(|entry| entry.0 == operation).unwrap().1;
+                format!("{}\t{}, {}, {}", name, rd, rs1, rs2)
+            }
+            Instruction::Immediate { operation: ImmediateOp::Addi, rd, rs1: Register::ZERO, imm } => format!("li\t{}, {}", rd, imm),
+            Instruction::Immediate { operation, rd, rs1, imm } => {
+                let name = IMMEDIATE_OPS.iter().find(|entry| entry.0 == operation).unwrap().1;
+                format!("{}\t{}, {}, {}", name, rd, rs1, imm)
+            }
+            Instruction::Lui { rd, imm } => format!("lui\t{}, {}", rd, imm),
+            Instruction::Auipc { rd, imm } => format!("auipc\t{}, {}", rd, imm),
+            Instruction::Load { operation, rd, rs1, offset } => {
+                let name = LOAD_OPS.iter().find(|entry| entry.0 == operation).unwrap().1;
+                format!("{}\t{}, {}({})", name, rd, offset, rs1)
+            }
+            Instruction::Store { operation, rs1, rs2, offset } => {
+                let name = STORE_OPS.iter().find(|entry| entry.0 == operation).unwrap().1;
+                format!("{}\t{}, {}({})", name, rs2, offset, rs1)
+            }
+            Instruction::Branch { operation, rs1, rs2, .. } => {
+                let name = BRANCH_OPS.iter().find(|entry| entry.0 == operation).unwrap().1;
+                format!("{}\t{}, {}, {}", name, rs1, rs2, target)
+            }
+            Instruction::Jal { rd: Register::ZERO, .. } => format!("j\t{}", target),
+            Instruction::Jal { rd, .. } => format!("jal\t{}, {}", rd, target),
+            Instruction::Jalr { rd: Register::ZERO, rs1: Register::RA, offset: 0 } => "ret".to_string(),
+            Instruction::Jalr { rd, rs1, offset } => format!("jalr\t{}, {}({})", rd, offset, rs1),
+            Instruction::Ecall => "ecall".to_string(),
+            Instruction::Ebreak => "ebreak".to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Register {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", NAMES[self.0 as usize])
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    // Branches and jumps show their offset, e.g beq t0, zero, 12
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let offset = match self {
+            Instruction::Branch { offset, .. } | Instruction::Jal { offset, .. } => offset.to_string(),
+            _ => String::new(),
+        };
+        write!(f, "{}", self.format(&offset))
+    }
+}
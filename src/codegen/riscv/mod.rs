@@ -0,0 +1,3 @@
+pub mod isa;
+pub mod emit;
+pub mod simulator;
@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+use crate::codegen::abi::decode_status;
+use crate::codegen::riscv::emit::Assembly;
+use crate::interpreter::RuntimeError;
+use crate::codegen::riscv::isa::*;
+
+// An RV32IM instruction set simulator
+// 	- The memory is a flat array of bytes from address 0, in little endian
+// 	- Instructions are fetched from memory and decoded from their machine words, so the
+// 	  simulator runs the encoded code rather than the instructions the backend built
+// 	- Division by zero and signed overflow give the results the specification defines, they do not trap
+// 	- ecall, ebreak, illegal instructions and accesses outside of the memory stop the simulation
+pub struct Simulator {
+    pub registers: [u32; 32],
+    pub pc: u32,
+    memory: Vec<u8>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Fault {
+    IllegalInstruction { address: u32, word: u32 },
+    MisalignedFetch(u32),
+    OutOfBounds(u32),
+    // ecall or ebreak
+    Trap(u32),
+    // The call did not return within the number of steps
+    StepLimit,
+    // The call returned with a different value in sp or in a saved register
+    Clobbered(Register),
+}
+
+// The address calls return to. Reaching it ends the call.
+const RETURN: u32 = 0xffff_fff0;
+
+// s0 to s11, which a function must restore before it returns
+const SAVED: [Register; 12] = [
+    Register(8),
+    Register(9),
+    Register(18),
+    Register(19),
+    Register(20),
+    Register(21),
+    Register(22),
+    Register(23),
+    Register(24),
+    Register(25),
+    Register(26),
+    Register(27),
+];
+
+impl Simulator {
+    pub fn new(memory_size: usize) -> Self {
+        Simulator {
+            registers: [0; 32],
+            pc: 0,
+            memory: vec![0; memory_size],
+        }
+    }
+
+    pub fn read(&self, address: u32, length: usize) -> Result<&[u8], Fault> {
+        let start = address as usize;
+        self.memory.get(start..start + length).ok_or(Fault::OutOfBounds(address))
+    }
+
+    pub fn write(&mut self, address: u32, bytes: &[u8]) -> Result<(), Fault> {
+        let start = address as usize;
+        self.memory
+            .get_mut(start..start + bytes.len())
+            .ok_or(Fault::OutOfBounds(address))?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub fn write_words(&mut self, address: u32, words: &[u32]) -> Result<(), Fault> {
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        self.write(address, &bytes)
+    }
+
+    fn read_value(&self, address: u32, length: usize) -> Result<u32, Fault> {
+        let mut bytes = [0; 4];
+        bytes[..length].copy_from_slice(self.read(address, length)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn set(&mut self, register: Register, val: u32) {
+        // x0 is always zero
+        if register != Register::ZERO {
+            self.registers[register.0 as usize] = val;
+        }
+    }
+
+    fn get(&self, register: Register) -> u32 {
+        self.registers[register.0 as usize]
+    }
+
+    // Executes the instruction at pc
+    pub fn step(&mut self) -> Result<(), Fault> {
+        if !self.pc.is_multiple_of(4) {
+            return Err(Fault::MisalignedFetch(self.pc));
+        }
+        let word = self.read_value(self.pc, 4)?;
+        let instruction = Instruction::decode(word).ok_or(Fault::IllegalInstruction { address: self.pc, word })?;
+        let mut next = self.pc.wrapping_add(4);
+
+        match instruction {
+            Instruction::Register { operation, rd, rs1, rs2 } => {
+                let val = register_operation(operation, self.get(rs1), self.get(rs2));
+                self.set(rd, val);
+            }
+            Instruction::Immediate { operation, rd, rs1, imm } => {
+                let left = self.get(rs1);
+                let val = match operation {
+                    ImmediateOp::Addi => left.wrapping_add(imm as u32),
+                    ImmediateOp::Slti => ((left as i32) < imm) as u32,
+                    ImmediateOp::Sltiu => (left < imm as u32) as u32,
+                    ImmediateOp::Xori => left ^ imm as u32,
+                    ImmediateOp::Ori => left | imm as u32,
+                    ImmediateOp::Andi => left & imm as u32,
+                    ImmediateOp::Slli => left << (imm & 31),
+                    ImmediateOp::Srli => left >> (imm & 31),
+                    ImmediateOp::Srai => ((left as i32) >> (imm & 31)) as u32,
+                };
+                self.set(rd, val);
+            }
+            Instruction::Lui { rd, imm } => self.set(rd, (imm as u32) << 12),
+            Instruction::Auipc { rd, imm } => self.set(rd, self.pc.wrapping_add((imm as u32) << 12)),
+            Instruction::Load { operation, rd, rs1, offset } => {
+                let address = self.get(rs1).wrapping_add(offset as u32);
+                let val = match operation {
+                    LoadOp::Lb => self.read_value(address, 1)? as i8 as i32 as u32,
+                    LoadOp::Lh => self.read_value(address, 2)? as i16 as i32 as u32,
+                    LoadOp::Lw => self.read_value(address, 4)?,
+                    LoadOp::Lbu => self.read_value(address, 1)?,
+                    LoadOp::Lhu => self.read_value(address, 2)?,
+                };
+                self.set(rd, val);
+            }
+            Instruction::Store { operation, rs1, rs2, offset } => {
+                let address = self.get(rs1).wrapping_add(offset as u32);
+                let length = match operation {
+                    StoreOp::Sb => 1,
+                    StoreOp::Sh => 2,
+                    StoreOp::Sw => 4,
+                };
+                let bytes = self.get(rs2).to_le_bytes();
+                self.write(address, &bytes[..length])?;
+            }
+            Instruction::Branch { operation, rs1, rs2, offset } => {
+                let (left, right) = (self.get(rs1), self.get(rs2));
+                let taken = match operation {
+                    BranchOp::Beq => left == right,
+                    BranchOp::Bne => left != right,
+                    BranchOp::Blt => (left as i32) < right as i32,
+                    BranchOp::Bge => left as i32 >= right as i32,
+                    BranchOp::Bltu => left < right,
+                    BranchOp::Bgeu => left >= right,
+                };
+                if taken {
+                    next = self.pc.wrapping_add(offset as u32);
+                }
+            }
+            Instruction::Jal { rd, offset } => {
+                self.set(rd, next);
+                next = self.pc.wrapping_add(offset as u32);
+            }
+            Instruction::Jalr { rd, rs1, offset } => {
+                let target = self.get(rs1).wrapping_add(offset as u32) & !1;
+                self.set(rd, next);
+                next = target;
+            }
+            Instruction::Ecall | Instruction::Ebreak => return Err(Fault::Trap(self.pc)),
+        }
+        self.pc = next;
+        Ok(())
+    }
+
+    // Calls the function at `entry` with the given arguments, following the calling convention,
+    // with the stack at the end of the memory. Returns a0. The saved registers start with
+    // arbitrary values, which the function must restore.
+    pub fn call(&mut self, entry: u32, arguments: &[u32], max_steps: usize) -> Result<u32, Fault> {
+        self.registers = [0; 32];
+        for register in SAVED {
+            self.set(register, 0x5a5a_0000 | register.0 as u32);
+        }
+        let stack_arguments = arguments.len().saturating_sub(8) as u32;
+        let sp = (self.memory.len() as u32 - 4 * stack_arguments) & !15;
+        for (index, argument) in arguments.iter().enumerate() {
+            match Register::argument(index) {
+                Some(register) => self.set(register, *argument),
+                None => self.write_words(sp + 4 * (index as u32 - 8), &[*argument])?,
+            }
+        }
+        self.set(Register::SP, sp);
+        self.set(Register::RA, RETURN);
+        self.pc = entry;
+
+        for _ in 0..max_steps {
+            if self.pc == RETURN {
+                if self.get(Register::SP) != sp {
+                    return Err(Fault::Clobbered(Register::SP));
+                }
+                if let Some(register) = SAVED.into_iter().find(|register| self.get(*register) != 0x5a5a_0000 | register.0 as u32) {
+                    return Err(Fault::Clobbered(register));
+                }
+                return Ok(self.get(Register::A0));
+            }
+            self.step()?;
+        }
+        Err(Fault::StepLimit)
+    }
+}
+
+fn register_operation(operation: RegisterOp, left: u32, right: u32) -> u32 {
+    let (signed_left, signed_right) = (left as i32, right as i32);
+    match operation {
+        RegisterOp::Add => left.wrapping_add(right),
+        RegisterOp::Sub => left.wrapping_sub(right),
+        RegisterOp::Sll => left << (right & 31),
+        RegisterOp::Slt => (signed_left < signed_right) as u32,
+        RegisterOp::Sltu => (left < right) as u32,
+        RegisterOp::Xor => left ^ right,
+        RegisterOp::Srl => left >> (right & 31),
+        RegisterOp::Sra => (signed_left >> (right & 31)) as u32,
+        RegisterOp::Or => left | right,
+        RegisterOp::And => left & right,
+        RegisterOp::Mul => left.wrapping_mul(right),
+        RegisterOp::Mulh => ((signed_left as i64 * signed_right as i64) >> 32) as u32,
+        RegisterOp::Mulhsu => ((signed_left as i64 * right as i64) >> 32) as u32,
+        RegisterOp::Mulhu => ((left as u64 * right as u64) >> 32) as u32,
+        RegisterOp::Div if right == 0 => u32::MAX,
+        RegisterOp::Div => signed_left.wrapping_div(signed_right) as u32,
+        RegisterOp::Divu if right == 0 => u32::MAX,
+        RegisterOp::Divu => left / right,
+        RegisterOp::Rem if right == 0 => left,
+        RegisterOp::Rem => signed_left.wrapping_rem(signed_right) as u32,
+        RegisterOp::Remu if right == 0 => left,
+        RegisterOp::Remu => left % right,
+    }
+}
+
+// A compiled function loaded in a simulator, with its code at address 0 and its bindings after it
+pub struct Harness {
+    simulator: Simulator,
+    inputs: Vec<String>,
+    bindings: Vec<String>,
+    bindings_address: u32,
+}
+
+// The stack of a call
+const STACK_SIZE: usize = 64 * 1024;
+const MAX_STEPS: usize = 10_000_000;
+
+impl Harness {
+    pub fn new(assembly: &Assembly) -> Self {
+        let code = assembly.code();
+        let bindings_address = (4 * code.len()) as u32;
+        let mut simulator = Simulator::new(4 * code.len() + assembly.bindings.len() + STACK_SIZE);
+        simulator.write_words(0, &code).expect("the memory holds the code");
+        Harness {
+            simulator,
+            inputs: assembly.inputs.clone(),
+            bindings: assembly.bindings.clone(),
+            bindings_address,
+        }
+    }
+
+    // Calls the function on the values of its inputs. Returns the status and the bindings.
+    pub fn call(&mut self, inputs: &[u8]) -> Result<(u32, Vec<u8>), Fault> {
+        assert_eq!(inputs.len(), self.inputs.len(), "one value per input");
+        self.simulator.write(self.bindings_address, &vec![0; self.bindings.len()])?;
+
+        let mut arguments: Vec<u32> = inputs.iter().map(|val| *val as u32).collect();
+        arguments.push(self.bindings_address);
+        let status = self.simulator.call(0, &arguments, MAX_STEPS)?;
+        Ok((status, self.simulator.read(self.bindings_address, self.bindings.len())?.to_vec()))
+    }
+
+    // Calls the function on named arguments, like Interpreter::run. Returns the value of every binding, in program order.
+    pub fn run_named(&mut self, arguments: &BTreeMap<String, u8>) -> Result<Vec<(String, u8)>, RuntimeError> {
+        if let Some(name) = arguments.keys().find(|name| !self.inputs.contains(name)) {
+            return Err(RuntimeError::UnknownInput(name.clone()));
+        }
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| arguments.get(input).copied().ok_or_else(|| RuntimeError::MissingInput(input.clone())))
+            .collect::<Result<Vec<u8>, RuntimeError>>()?;
+
+        let (status, values) = self.call(&inputs).unwrap_or_else(|fault| panic!("the generated code faulted: {:?}", fault));
+        match decode_status(status) {
+            None => Ok(self.bindings.iter().cloned().zip(values).collect()),
+            Some((panic, binding)) => Err(RuntimeError::Panic {
+                panic,
+                variable: self.bindings[binding].clone(),
+            }),
+        }
+    }
+}
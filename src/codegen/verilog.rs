@@ -0,0 +1,82 @@
+use std::fmt::Write;
+use crate::circuit::netlist::{Gate, Netlist, Signal};
+use crate::codegen::abi::SYMBOL_PREFIX;
+
+// A Verilog backend, which prints the netlist of a program as a combinational module of gate primitives
+// 	- A program `fn main(a: u8, b: u8)` becomes `module rustic_main`, with an 8-bit input port for every
+// 	  input and an 8-bit output port for every binding the netlist outputs (see Netlist::select)
+// 	- The `overflow` output is high when the program panics, and the other outputs are then meaningless
+// 	- The gates drive the bits of the `_n` vector, in order
+// 	- Ports named like Verilog keywords or like the overflow flag get a leading underscore,
+// 	  which identifiers cannot start with
+pub fn emit(netlist: &Netlist) -> String {
+    let signature: Vec<String> = netlist.inputs.iter().map(|input| format!("{}: u8", input)).collect();
+    let ports: Vec<String> = netlist
+        .inputs
+        .iter()
+        .map(|input| format!("    input wire [7:0] {}", name(input)))
+        .chain(netlist.outputs.iter().map(|(binding, _)| format!("    output wire [7:0] {}", name(binding))))
+        .chain(["    output wire overflow".to_string()])
+        .collect();
+
+    let mut code = String::new();
+    let _ = writeln!(code, "// fn {}({})", netlist.name, signature.join(", "));
+    let _ = writeln!(code, "module {}{} (\n{}\n);", SYMBOL_PREFIX, netlist.name, ports.join(",\n"));
+    if !netlist.gates.is_empty() {
+        let _ = writeln!(code, "    wire [{}:0] _n;", netlist.gates.len() - 1);
+    }
+
+    let mut comments = netlist.comments.iter().peekable();
+    for (index, gate) in netlist.gates.iter().enumerate() {
+        while let Some((_, comment)) = comments.next_if(|(start, _)| *start == index) {
+            let _ = write!(code, "\n    // {}\n", comment);
+        }
+        let _ = match gate {
+            Gate::And(x, y) => writeln!(code, "    and (_n[{}], {}, {});", index, signal(netlist, x), signal(netlist, y)),
+            Gate::Or(x, y) => writeln!(code, "    or (_n[{}], {}, {});", index, signal(netlist, x), signal(netlist, y)),
+            Gate::Xor(x, y) => writeln!(code, "    xor (_n[{}], {}, {});", index, signal(netlist, x), signal(netlist, y)),
+            Gate::Not(x) => writeln!(code, "    not (_n[{}], {});", index, signal(netlist, x)),
+        };
+    }
+
+    code += "\n";
+    for (binding, byte) in &netlist.outputs {
+        // The highest bit comes first in a concatenation
+        let bits: Vec<String> = byte.iter().rev().map(|bit| signal(netlist, bit)).collect();
+        let _ = writeln!(code, "    assign {} = {{{}}};", name(binding), bits.join(", "));
+    }
+    let _ = writeln!(code, "    assign overflow = {};", signal(netlist, &netlist.overflow));
+    code += "endmodule\n";
+    code
+}
+
+fn signal(netlist: &Netlist, signal: &Signal) -> String {
+    match signal {
+        Signal::Constant(val) => format!("1'b{}", *val as u8),
+        Signal::Input(input, bit) => format!("{}[{}]", name(&netlist.inputs[*input]), bit),
+        Signal::Gate(gate) => format!("_n[{}]", gate),
+    }
+}
+
+// The keywords of Verilog-2005, and the name of the overflow flag
+const RESERVED: [&str; 125] = [
+    "always", "and", "assign", "automatic", "begin", "buf", "bufif0", "bufif1", "case", "casex", "casez", "cell", "cmos",
+    "config", "deassign", "default", "defparam", "design", "disable", "edge", "else", "end", "endcase", "endconfig",
+    "endfunction", "endgenerate", "endmodule", "endprimitive", "endspecify", "endtable", "endtask", "event", "for", "force",
+    "forever", "fork", "function", "generate", "genvar", "highz0", "highz1", "if", "ifnone", "incdir", "include", "initial",
+    "inout", "input", "instance", "integer", "join", "large", "liblist", "library", "localparam", "macromodule", "medium",
+    "module", "nand", "negedge", "nmos", "nor", "noshowcancelled", "not", "notif0", "notif1", "or", "output", "parameter",
+    "pmos", "posedge", "primitive", "pull0", "pull1", "pulldown", "pullup", "pulsestyle_ondetect", "pulsestyle_onevent",
+    "rcmos", "real", "realtime", "reg", "release", "repeat", "rnmos", "rpmos", "rtran", "rtranif0", "rtranif1", "scalared",
+    "showcancelled", "signed", "small", "specify", "specparam", "strong0", "strong1", "supply0", "supply1", "table", "task",
+    "time", "tran", "tranif0", "tranif1", "tri", "tri0", "tri1", "triand", "trior", "trireg", "unsigned", "use", "uwire",
+    "vectored", "wait", "wand", "weak0", "weak1", "while", "wire", "wor", "xnor", "xor", "overflow",
+];
+
+fn name(name: &str) -> String {
+    if RESERVED.contains(&name) {
+        format!("_{}", name)
+    } else {
+        name.to_string()
+    }
+}
@@ -0,0 +1,399 @@
+use crate::codegen::wasm::module::*;
+
+// The binary format of a module
+// 	- header: the magic bytes "\0asm", then the version 1 as a u32, little endian
+// 	- the type, function, export and code sections of the single function, then a custom
+// 	  "name" section with the names of the function and of its locals
+// 	- Integers are LEB128, unsigned except for the constants of i32.const
+// 	- Comments are not part of the binary format
+// 	- Decoding reads back the subset the backend emits, and rejects anything else
+pub const MAGIC: [u8; 4] = *b"\0asm";
+pub const VERSION: u32 = 1;
+
+const TYPE_SECTION: u8 = 1;
+const FUNCTION_SECTION: u8 = 3;
+const EXPORT_SECTION: u8 = 7;
+const CODE_SECTION: u8 = 10;
+const CUSTOM_SECTION: u8 = 0;
+
+const FUNCTION_TYPE: u8 = 0x60;
+const I32: u8 = 0x7f;
+const EMPTY_BLOCK: u8 = 0x40;
+const FUNCTION_EXPORT: u8 = 0x00;
+
+const FUNCTION_NAMES: u8 = 1;
+const LOCAL_NAMES: u8 = 2;
+
+const UNREACHABLE: u8 = 0x00;
+const IF: u8 = 0x04;
+const END: u8 = 0x0b;
+const SELECT: u8 = 0x1b;
+const LOCAL_GET: u8 = 0x20;
+const LOCAL_SET: u8 = 0x21;
+const I32_CONST: u8 = 0x41;
+const I32_EQZ: u8 = 0x45;
+
+pub fn encode(module: &Module) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+
+    let mut types = Vec::new();
+    write_u32(&mut types, 1);
+    types.push(FUNCTION_TYPE);
+    write_u32(&mut types, module.params);
+    types.extend(std::iter::repeat_n(I32, module.params as usize));
+    write_u32(&mut types, module.results);
+    types.extend(std::iter::repeat_n(I32, module.results as usize));
+    write_section(&mut bytes, TYPE_SECTION, &types);
+
+    // One function, of the first type
+    write_section(&mut bytes, FUNCTION_SECTION, &[1, 0]);
+
+    let mut exports = Vec::new();
+    write_u32(&mut exports, 1);
+    write_name(&mut exports, &module.export);
+    exports.extend_from_slice(&[FUNCTION_EXPORT, 0]);
+    write_section(&mut bytes, EXPORT_SECTION, &exports);
+
+    let mut body = Vec::new();
+    if module.locals > 0 {
+        write_u32(&mut body, 1);
+        write_u32(&mut body, module.locals);
+        body.push(I32);
+    } else {
+        write_u32(&mut body, 0);
+    }
+    for instruction in &module.body {
+        write_instruction(&mut body, instruction);
+    }
+    body.push(END);
+    let mut code = Vec::new();
+    write_u32(&mut code, 1);
+    write_u32(&mut code, body.len() as u32);
+    code.extend_from_slice(&body);
+    write_section(&mut bytes, CODE_SECTION, &code);
+
+    let mut names = Vec::new();
+    write_name(&mut names, "name");
+    let mut function_names = Vec::new();
+    write_u32(&mut function_names, 1);
+    write_u32(&mut function_names, 0);
+    write_name(&mut function_names, &module.export);
+    write_section(&mut names, FUNCTION_NAMES, &function_names);
+    let mut local_names = Vec::new();
+    write_u32(&mut local_names, 1);
+    write_u32(&mut local_names, 0);
+    write_u32(&mut local_names, module.names.len() as u32);
+    for (index, name) in module.names.iter().enumerate() {
+        write_u32(&mut local_names, index as u32);
+        write_name(&mut local_names, name);
+    }
+    write_section(&mut names, LOCAL_NAMES, &local_names);
+    write_section(&mut bytes, CUSTOM_SECTION, &names);
+
+    bytes
+}
+
+fn write_instruction(bytes: &mut Vec<u8>, instruction: &Instruction) {
+    match instruction {
+        Instruction::Unreachable => bytes.push(UNREACHABLE),
+        Instruction::If => bytes.extend_from_slice(&[IF, EMPTY_BLOCK]),
+        Instruction::End => bytes.push(END),
+        Instruction::Select => bytes.push(SELECT),
+        Instruction::LocalGet(local) => {
+            bytes.push(LOCAL_GET);
+            write_u32(bytes, *local);
+        }
+        Instruction::LocalSet(local) => {
+            bytes.push(LOCAL_SET);
+            write_u32(bytes, *local);
+        }
+        Instruction::I32Const(val) => {
+            bytes.push(I32_CONST);
+            write_i32(bytes, *val);
+        }
+        Instruction::I32Eqz => bytes.push(I32_EQZ),
+        Instruction::Binary(operation) => bytes.push(operation.opcode()),
+    }
+}
+
+fn write_section(bytes: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    bytes.push(id);
+    write_u32(bytes, contents.len() as u32);
+    bytes.extend_from_slice(contents);
+}
+
+fn write_name(bytes: &mut Vec<u8>, name: &str) {
+    write_u32(bytes, name.len() as u32);
+    bytes.extend_from_slice(name.as_bytes());
+}
+
+fn write_u32(bytes: &mut Vec<u8>, mut val: u32) {
+    loop {
+        let byte = (val & 0x7f) as u8;
+        val >>= 7;
+        if val == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn write_i32(bytes: &mut Vec<u8>, mut val: i32) {
+    loop {
+        let byte = (val & 0x7f) as u8;
+        val >>= 7;
+        // The sign bit of the last byte must match the sign of the value
+        if (val == 0 && byte & 0x40 == 0) || (val == -1 && byte & 0x40 != 0) {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Module, String> {
+    if !bytes.starts_with(&MAGIC) {
+        return Err("Not a WebAssembly module.".to_string());
+    }
+    let mut reader = Reader { bytes, offset: MAGIC.len() };
+    let version = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+    if version != VERSION {
+        return Err(format!("Unsupported WebAssembly version {}, expected {}.", version, VERSION));
+    }
+
+    let mut signature = None;
+    let mut export = None;
+    let mut code = None;
+    let mut names = None;
+    let mut previous = 0;
+    while reader.offset < bytes.len() {
+        let id = reader.u8()?;
+        let length = reader.u32()? as usize;
+        let mut section = Reader { bytes: reader.take(length)?, offset: 0 };
+        if id != CUSTOM_SECTION {
+            if id <= previous {
+                return Err(format!("Invalid WebAssembly: section {} is out of order.", id));
+            }
+            previous = id;
+        }
+
+        match id {
+            TYPE_SECTION => {
+                section.expect_count(1, "type")?;
+                section.expect(FUNCTION_TYPE, "a function type")?;
+                let params = section.types()?;
+                let results = section.types()?;
+                signature = Some((params, results));
+            }
+            FUNCTION_SECTION => {
+                section.expect_count(1, "function")?;
+                section.expect_count(0, "type index")?;
+            }
+            EXPORT_SECTION => {
+                section.expect_count(1, "export")?;
+                let name = section.name()?;
+                section.expect(FUNCTION_EXPORT, "a function export")?;
+                section.expect_count(0, "function index")?;
+                export = Some(name);
+            }
+            CODE_SECTION => {
+                section.expect_count(1, "function body")?;
+                let length = section.u32()? as usize;
+                let mut body = Reader { bytes: section.take(length)?, offset: 0 };
+                code = Some(read_body(&mut body)?);
+            }
+            CUSTOM_SECTION => {
+                if section.name()? == "name" {
+                    names = Some(read_names(&mut section)?);
+                }
+                section.offset = section.bytes.len();
+            }
+            _ => return Err(format!("Invalid WebAssembly: unsupported section {}.", id)),
+        }
+        if section.offset != section.bytes.len() {
+            return Err(format!("Invalid WebAssembly: section {} has trailing bytes.", id));
+        }
+    }
+
+    let (params, results) = signature.ok_or("Invalid WebAssembly: the type section is missing.")?;
+    let export = export.ok_or("Invalid WebAssembly: the export section is missing.")?;
+    let (locals, body) = code.ok_or("Invalid WebAssembly: the code section is missing.")?;
+
+    // Locals without a name are named after their index
+    let mut local_names: Vec<String> = (0..params + locals).map(|index| index.to_string()).collect();
+    for (index, name) in names.unwrap_or_default() {
+        if let Some(local) = local_names.get_mut(index as usize) {
+            *local = name;
+        }
+    }
+
+    Ok(Module {
+        export,
+        params,
+        results,
+        locals,
+        names: local_names,
+        body,
+        comments: Vec::new(),
+    })
+}
+
+// The number of locals and the instructions of a function body
+fn read_body(reader: &mut Reader) -> Result<(u32, Vec<Instruction>), String> {
+    let mut locals: u32 = 0;
+    for _ in 0..reader.u32()? {
+        let count = reader.u32()?;
+        reader.expect(I32, "the type i32")?;
+        locals = locals.checked_add(count).ok_or("Invalid WebAssembly: too many locals.")?;
+    }
+
+    let mut body = Vec::new();
+    loop {
+        let offset = reader.offset;
+        let instruction = match reader.u8()? {
+            UNREACHABLE => Instruction::Unreachable,
+            IF => {
+                reader.expect(EMPTY_BLOCK, "an empty block type")?;
+                Instruction::If
+            }
+            END => Instruction::End,
+            SELECT => Instruction::Select,
+            LOCAL_GET => Instruction::LocalGet(reader.u32()?),
+            LOCAL_SET => Instruction::LocalSet(reader.u32()?),
+            I32_CONST => Instruction::I32Const(reader.i32()?),
+            I32_EQZ => Instruction::I32Eqz,
+            opcode => match BinaryOp::ALL.iter().find(|operation| operation.opcode() == opcode) {
+                Some(operation) => Instruction::Binary(*operation),
+                None => {
+                    return Err(format!("Invalid WebAssembly: unsupported opcode 0x{:02x} at offset {} of the body.", opcode, offset));
+                }
+            },
+        };
+        // The body ends with the end of the function, which is the last byte
+        if instruction == Instruction::End && reader.offset == reader.bytes.len() {
+            return Ok((locals, body));
+        }
+        body.push(instruction);
+    }
+}
+
+// The local names of the function, from the name section
+fn read_names(reader: &mut Reader) -> Result<Vec<(u32, String)>, String> {
+    let mut names = Vec::new();
+    while reader.offset < reader.bytes.len() {
+        let id = reader.u8()?;
+        let length = reader.u32()? as usize;
+        let mut subsection = Reader { bytes: reader.take(length)?, offset: 0 };
+        if id != LOCAL_NAMES {
+            continue;
+        }
+        for _ in 0..subsection.u32()? {
+            let function = subsection.u32()?;
+            for _ in 0..subsection.u32()? {
+                let index = subsection.u32()?;
+                let name = subsection.name()?;
+                if function == 0 {
+                    names.push((index, name));
+                }
+            }
+        }
+    }
+    Ok(names)
+}
+
+fn truncated() -> String {
+    "Invalid WebAssembly: the module is truncated.".to_string()
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .offset
+            .checked_add(length)
+            .and_then(|end| self.bytes.get(self.offset..end))
+            .ok_or_else(truncated)?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn expect(&mut self, expected: u8, what: &str) -> Result<(), String> {
+        match self.u8()? {
+            byte if byte == expected => Ok(()),
+            byte => Err(format!("Invalid WebAssembly: expected {}, found 0x{:02x}.", what, byte)),
+        }
+    }
+
+    // Only modules with a single function are supported
+    fn expect_count(&mut self, expected: u32, what: &str) -> Result<(), String> {
+        match self.u32()? {
+            count if count == expected => Ok(()),
+            count => Err(format!("Invalid WebAssembly: unsupported {} {}, expected {}.", what, count, expected)),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut val: u64 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.u8()?;
+            val |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return u32::try_from(val).map_err(|_| "Invalid WebAssembly: an integer is too large.".to_string());
+            }
+        }
+        Err("Invalid WebAssembly: an integer is too long.".to_string())
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        let mut val: i64 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.u8()?;
+            val |= ((byte & 0x7f) as i64) << shift;
+            if byte & 0x80 == 0 {
+                // Extends the sign bit of the last byte
+                if byte & 0x40 != 0 {
+                    val |= -1 << (shift + 7);
+                }
+                return i32::try_from(val).map_err(|_| "Invalid WebAssembly: an integer is too large.".to_string());
+            }
+        }
+        Err("Invalid WebAssembly: an integer is too long.".to_string())
+    }
+
+    // A vector of value types, which must all be i32
+    fn types(&mut self) -> Result<u32, String> {
+        let count = self.u32()?;
+        for _ in 0..count {
+            self.expect(I32, "the type i32")?;
+        }
+        Ok(count)
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let length = self.u32()? as usize;
+        String::from_utf8(self.take(length)?.to_vec()).map_err(|_| "Invalid WebAssembly: a name is not UTF-8.".to_string())
+    }
+}
+
+// The bytes of a module in hexadecimal, 16 per line, each line starting with its offset
+pub fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(line, chunk)| {
+            let chunk: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("{:08x}  {}", line * 16, chunk.join(" "))
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use crate::ast::Operator;
+use crate::arithmetic::OverflowMode;
+use crate::codegen::abi::*;
+use crate::codegen::wasm::module::*;
+use crate::ir::instruction::{self as ir, Function, Operand, Variable};
+
+// A WebAssembly backend
+// 	- A program `fn main(a: u8, b: u8)` becomes a module exporting `main`, which takes an i32 for
+// 	  every input and returns an i32 for every binding, in program order
+// 	- Only the lowest 8 bits of the parameters are used, and values are kept between 0 and 255
+// 	- Every variable is a local. A result that may overflow is kept in the %wide local while its
+// 	  overflow is checked, e.g a sum overflows if it is above 255, then it is masked to 8 bits.
+// 	- Panics trap with `unreachable`, like Rust programs compiled to WebAssembly
+pub fn emit(function: &Function, overflow: OverflowMode) -> Module {
+    let bindings = bindings(function);
+    let temporaries = function
+        .instructions
+        .iter()
+        .filter_map(|instruction| match instruction.destination() {
+            Variable::Temporary(index) => Some(index + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+
+    let variables: Vec<Variable> = function
+        .inputs
+        .iter()
+        .chain(&bindings)
+        .map(|name| Variable::Named(name.clone()))
+        .chain((0..temporaries).map(Variable::Temporary))
+        .collect();
+    let mut names: Vec<String> = variables.iter().map(|variable| variable.to_string()).collect();
+    names.push("%wide".to_string());
+
+    let mut emitter = Emitter {
+        overflow,
+        locals: variables.iter().enumerate().map(|(index, variable)| (variable.clone(), index as u32)).collect(),
+        wide: variables.len() as u32,
+        body: Vec::new(),
+        comments: Vec::new(),
+    };
+
+    for index in 0..function.inputs.len() as u32 {
+        emitter.body.extend([
+            Instruction::LocalGet(index),
+            Instruction::I32Const(255),
+            Instruction::Binary(BinaryOp::And),
+            Instruction::LocalSet(index),
+        ]);
+    }
+    for instruction in &function.instructions {
+        emitter.instruction(instruction);
+    }
+    for binding in &bindings {
+        let local = emitter.locals[&Variable::Named(binding.clone())];
+        emitter.body.push(Instruction::LocalGet(local));
+    }
+
+    Module {
+        export: function.name.clone(),
+        params: function.inputs.len() as u32,
+        results: bindings.len() as u32,
+        locals: (names.len() - function.inputs.len()) as u32,
+        names,
+        body: emitter.body,
+        comments: emitter.comments,
+    }
+}
+
+struct Emitter {
+    overflow: OverflowMode,
+    // The index of the local of every variable
+    locals: HashMap<Variable, u32>,
+    wide: u32,
+    body: Vec<Instruction>,
+    comments: Vec<(usize, String)>,
+}
+
+impl Emitter {
+    fn push(&mut self, instructions: &[Instruction]) {
+        self.body.extend_from_slice(instructions);
+    }
+
+    fn operand(&self, operand: &Operand) -> Instruction {
+        match operand {
+            Operand::Constant(val) => Instruction::I32Const(*val as i32),
+            Operand::Variable(variable) => Instruction::LocalGet(self.locals[variable]),
+        }
+    }
+
+    // Traps if the condition on the stack is not zero
+    fn trap_if(&mut self) {
+        self.push(&[Instruction::If, Instruction::Unreachable, Instruction::End]);
+    }
+
+    fn instruction(&mut self, instruction: &ir::Instruction) {
+        self.comments.push((self.body.len(), instruction.to_string()));
+        match instruction {
+            ir::Instruction::Copy { source, .. } => {
+                let source = self.operand(source);
+                self.push(&[source]);
+            }
+            ir::Instruction::Binary { operator, arithmetic, left, right, .. } => {
+                let (x, y) = (self.operand(left), self.operand(right));
+                let wide = self.wide;
+                let behaviour = behaviour(self.overflow, operator, arithmetic);
+
+                if *operator == Operator::Divide {
+                    self.push(&[y, Instruction::I32Eqz]);
+                    self.trap_if();
+                }
+
+                // The operation on the operands, and the instructions that push whether its result overflowed
+                // and the saturated value. Operations that cannot overflow have results that fit in 8 bits.
+                let wide_overflows = vec![Instruction::LocalGet(wide), Instruction::I32Const(255), Instruction::Binary(BinaryOp::GtU)];
+                let (operation, overflow) = match operator {
+                    Operator::Add => (vec![x, y, Instruction::Binary(BinaryOp::Add)], Some((wide_overflows, 255))),
+                    Operator::Multiply => (vec![x, y, Instruction::Binary(BinaryOp::Mul)], Some((wide_overflows, 255))),
+                    Operator::Subtract => (
+                        vec![x, y, Instruction::Binary(BinaryOp::Sub)],
+                        Some((vec![x, y, Instruction::Binary(BinaryOp::LtU)], 0)),
+                    ),
+                    // The amount is taken modulo 8, not modulo 32 like WebAssembly does
+                    Operator::ShiftLeft | Operator::ShiftRight => {
+                        let shift = if *operator == Operator::ShiftLeft { BinaryOp::Shl } else { BinaryOp::ShrU };
+                        (
+                            vec![x, y, Instruction::I32Const(7), Instruction::Binary(BinaryOp::And), Instruction::Binary(shift)],
+                            Some((vec![y, Instruction::I32Const(8), Instruction::Binary(BinaryOp::GeU)], 0)),
+                        )
+                    }
+                    Operator::Divide => (vec![x, y, Instruction::Binary(BinaryOp::DivU)], None),
+                    Operator::BitAnd => (vec![x, y, Instruction::Binary(BinaryOp::And)], None),
+                    Operator::BitOr => (vec![x, y, Instruction::Binary(BinaryOp::Or)], None),
+                    Operator::BitXor => (vec![x, y, Instruction::Binary(BinaryOp::Xor)], None),
+                };
+                let masked = [Instruction::LocalGet(wide), Instruction::I32Const(255), Instruction::Binary(BinaryOp::And)];
+
+                match (behaviour, overflow) {
+                    // Sums and products are compared to 255 directly, without keeping them
+                    (Behaviour::Flag, Some(_)) if matches!(operator, Operator::Add | Operator::Multiply) => {
+                        self.push(&operation);
+                        self.push(&[Instruction::I32Const(255), Instruction::Binary(BinaryOp::GtU)]);
+                    }
+                    (Behaviour::Flag, Some((overflows, _))) => self.push(&overflows),
+                    (Behaviour::Flag, None) => self.push(&[Instruction::I32Const(0)]),
+                    (_, None) => self.push(&operation),
+                    (Behaviour::Checked(_), Some((overflows, _))) => {
+                        self.push(&operation);
+                        self.push(&[Instruction::LocalSet(wide)]);
+                        self.push(&overflows);
+                        self.trap_if();
+                        self.push(&masked);
+                    }
+                    // select takes its first value if the condition is not zero
+                    (Behaviour::Saturating, Some((overflows, saturated))) => {
+                        self.push(&operation);
+                        self.push(&[Instruction::LocalSet(wide), Instruction::I32Const(saturated)]);
+                        self.push(&masked);
+                        self.push(&overflows);
+                        self.push(&[Instruction::Select]);
+                    }
+                    (Behaviour::Wrapping, Some(_)) => {
+                        self.push(&operation);
+                        self.push(&[Instruction::I32Const(255), Instruction::Binary(BinaryOp::And)]);
+                    }
+                }
+            }
+        }
+        let destination = self.locals[instruction.destination()];
+        self.push(&[Instruction::LocalSet(destination)]);
+    }
+}
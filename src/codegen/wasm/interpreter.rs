@@ -0,0 +1,101 @@
+use crate::codegen::wasm::module::*;
+
+// An interpreter for the modules of the backend, following the semantics of the specification
+// 	- Arithmetic wraps modulo 2^32, shift amounts are taken modulo 32 and comparisons produce 0 or 1
+// 	- Division by zero and `unreachable` trap, which stops the execution
+// 	- The module must be valid (see validate), and be called with one argument per parameter
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Trap {
+    Unreachable,
+    IntegerDivideByZero,
+}
+
+impl Trap {
+    pub fn message(&self) -> &'static str {
+        match self {
+            Trap::Unreachable => "unreachable",
+            Trap::IntegerDivideByZero => "integer divide by zero",
+        }
+    }
+}
+
+// Calls the exported function of a module. Returns its results.
+pub fn invoke(module: &Module, arguments: &[i32]) -> Result<Vec<i32>, Trap> {
+    assert_eq!(arguments.len(), module.params as usize, "one argument per parameter");
+
+    let mut locals = arguments.to_vec();
+    locals.resize(module.names.len(), 0);
+    let ends = matching_ends(&module.body);
+
+    let mut stack: Vec<i32> = Vec::new();
+    let mut pc = 0;
+    while pc < module.body.len() {
+        match module.body[pc] {
+            Instruction::Unreachable => return Err(Trap::Unreachable),
+            Instruction::If => {
+                // Skips the block if the condition is zero
+                if pop(&mut stack) == 0 {
+                    pc = ends[pc];
+                }
+            }
+            Instruction::End => {}
+            Instruction::Select => {
+                let condition = pop(&mut stack);
+                let second = pop(&mut stack);
+                let first = pop(&mut stack);
+                stack.push(if condition != 0 { first } else { second });
+            }
+            Instruction::LocalGet(local) => stack.push(locals[local as usize]),
+            Instruction::LocalSet(local) => locals[local as usize] = pop(&mut stack),
+            Instruction::I32Const(val) => stack.push(val),
+            Instruction::I32Eqz => {
+                let val = pop(&mut stack);
+                stack.push((val == 0) as i32);
+            }
+            Instruction::Binary(operation) => {
+                let right = pop(&mut stack) as u32;
+                let left = pop(&mut stack) as u32;
+                stack.push(binary(operation, left, right)? as i32);
+            }
+        }
+        pc += 1;
+    }
+
+    // The results are the values left on the stack
+    Ok(stack.split_off(stack.len() - module.results as usize))
+}
+
+fn pop(stack: &mut Vec<i32>) -> i32 {
+    stack.pop().expect("a valid module")
+}
+
+fn binary(operation: BinaryOp, left: u32, right: u32) -> Result<u32, Trap> {
+    Ok(match operation {
+        BinaryOp::LtU => (left < right) as u32,
+        BinaryOp::GtU => (left > right) as u32,
+        BinaryOp::GeU => (left >= right) as u32,
+        BinaryOp::Add => left.wrapping_add(right),
+        BinaryOp::Sub => left.wrapping_sub(right),
+        BinaryOp::Mul => left.wrapping_mul(right),
+        BinaryOp::DivU => left.checked_div(right).ok_or(Trap::IntegerDivideByZero)?,
+        BinaryOp::And => left & right,
+        BinaryOp::Or => left | right,
+        BinaryOp::Xor => left ^ right,
+        BinaryOp::Shl => left.wrapping_shl(right),
+        BinaryOp::ShrU => left.wrapping_shr(right),
+    })
+}
+
+// The index of the end of every if block, by the index of its if
+fn matching_ends(body: &[Instruction]) -> Vec<usize> {
+    let mut ends = vec![0; body.len()];
+    let mut open = Vec::new();
+    for (index, instruction) in body.iter().enumerate() {
+        match instruction {
+            Instruction::If => open.push(index),
+            Instruction::End => ends[open.pop().expect("a valid module")] = index,
+            _ => {}
+        }
+    }
+    ends
+}
@@ -0,0 +1,5 @@
+pub mod module;
+pub mod emit;
+pub mod binary;
+pub mod validate;
+pub mod interpreter;
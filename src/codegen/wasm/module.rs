@@ -0,0 +1,145 @@
+// The subset of WebAssembly that the backend emits: a module with a single exported function
+// over i32 values, with locals, constants, integer arithmetic, comparisons, select, and
+// `if` blocks without results
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Module {
+    // The name the function is exported as
+    pub export: String,
+    pub params: u32,
+    pub results: u32,
+    // The locals after the parameters
+    pub locals: u32,
+    // The name of every parameter and local
+    pub names: Vec<String>,
+    // The body, without the end of the function
+    pub body: Vec<Instruction>,
+    // Comments shown before the instruction at their index, in the text format only
+    pub comments: Vec<(usize, String)>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Instruction {
+    Unreachable,
+    // An if block without results
+    If,
+    End,
+    Select,
+    LocalGet(u32),
+    LocalSet(u32),
+    I32Const(i32),
+    I32Eqz,
+    Binary(BinaryOp),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum BinaryOp {
+    LtU,
+    GtU,
+    GeU,
+    Add,
+    Sub,
+    Mul,
+    DivU,
+    And,
+    Or,
+    Xor,
+    Shl,
+    ShrU,
+}
+
+impl BinaryOp {
+    pub const ALL: [BinaryOp; 12] = [
+        BinaryOp::LtU,
+        BinaryOp::GtU,
+        BinaryOp::GeU,
+        BinaryOp::Add,
+        BinaryOp::Sub,
+        BinaryOp::Mul,
+        BinaryOp::DivU,
+        BinaryOp::And,
+        BinaryOp::Or,
+        BinaryOp::Xor,
+        BinaryOp::Shl,
+        BinaryOp::ShrU,
+    ];
+
+    pub fn opcode(self) -> u8 {
+        match self {
+            BinaryOp::LtU => 0x49,
+            BinaryOp::GtU => 0x4b,
+            BinaryOp::GeU => 0x4f,
+            BinaryOp::Add => 0x6a,
+            BinaryOp::Sub => 0x6b,
+            BinaryOp::Mul => 0x6c,
+            BinaryOp::DivU => 0x6e,
+            BinaryOp::And => 0x71,
+            BinaryOp::Or => 0x72,
+            BinaryOp::Xor => 0x73,
+            BinaryOp::Shl => 0x74,
+            BinaryOp::ShrU => 0x76,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BinaryOp::LtU => "i32.lt_u",
+            BinaryOp::GtU => "i32.gt_u",
+            BinaryOp::GeU => "i32.ge_u",
+            BinaryOp::Add => "i32.add",
+            BinaryOp::Sub => "i32.sub",
+            BinaryOp::Mul => "i32.mul",
+            BinaryOp::DivU => "i32.div_u",
+            BinaryOp::And => "i32.and",
+            BinaryOp::Or => "i32.or",
+            BinaryOp::Xor => "i32.xor",
+            BinaryOp::Shl => "i32.shl",
+            BinaryOp::ShrU => "i32.shr_u",
+        }
+    }
+}
+
+impl std::fmt::Display for Module {
+    // The text format, with names for the function and its locals
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let params = self.params as usize;
+        write!(f, "(module\n  (func ${} (export \"{}\")", self.export, self.export)?;
+        for name in &self.names[..params] {
+            write!(f, " (param ${} i32)", name)?;
+        }
+        if self.results > 0 {
+            write!(f, " (result{})", " i32".repeat(self.results as usize))?;
+        }
+        writeln!(f)?;
+        for name in &self.names[params..] {
+            writeln!(f, "    (local ${} i32)", name)?;
+        }
+
+        let mut comments = self.comments.iter().peekable();
+        let mut depth = 0;
+        for (index, instruction) in self.body.iter().enumerate() {
+            while let Some((_, comment)) = comments.next_if(|(position, _)| *position == index) {
+                writeln!(f, "    {};; {}", "  ".repeat(depth), comment)?;
+            }
+            if *instruction == Instruction::End {
+                depth = depth.saturating_sub(1);
+            }
+            let text = match instruction {
+                Instruction::Unreachable => "unreachable".to_string(),
+                Instruction::If => "if".to_string(),
+                Instruction::End => "end".to_string(),
+                Instruction::Select => "select".to_string(),
+                Instruction::LocalGet(local) => format!("local.get ${}", self.names[*local as usize]),
+                Instruction::LocalSet(local) => format!("local.set ${}", self.names[*local as usize]),
+                Instruction::I32Const(val) => format!("i32.const {}", val),
+                Instruction::I32Eqz => "i32.eqz".to_string(),
+                Instruction::Binary(operation) => operation.name().to_string(),
+            };
+            writeln!(f, "    {}{}", "  ".repeat(depth), text)?;
+            if *instruction == Instruction::If {
+                depth += 1;
+            }
+        }
+        write!(f, "  )\n)")
+    }
+}
@@ -0,0 +1,125 @@
+use crate::codegen::wasm::module::*;
+
+// Checks that a module is valid, following the validation algorithm of the specification
+// 	- Every value is an i32, so the operand stack only needs its height
+// 	- Every block has a frame with the height of the stack at its start and its results:
+// 	  the function returns its results, and if blocks have none
+// 	- After `unreachable`, the rest of the block is unreachable and may pop values that were
+// 	  never pushed
+// 	- Every local must exist, and every block must end with exactly its results on the stack
+pub fn validate(module: &Module) -> Result<(), String> {
+    let locals = module.params as u64 + module.locals as u64;
+    if module.names.len() as u64 != locals {
+        return Err(format!("The module names {} locals, but has {}.", module.names.len(), locals));
+    }
+
+    let mut validator = Validator {
+        height: 0,
+        frames: vec![Frame {
+            height: 0,
+            results: module.results as usize,
+            unreachable: false,
+        }],
+    };
+    for (index, instruction) in module.body.iter().enumerate() {
+        validator
+            .instruction(instruction, locals)
+            .map_err(|message| format!("Invalid instruction {} ({:?}): {}.", index, instruction, message))?;
+    }
+    if validator.frames.len() > 1 {
+        return Err("An if block is not closed.".to_string());
+    }
+    validator.end().map_err(|message| format!("Invalid end of the function: {}.", message))
+}
+
+struct Frame {
+    height: usize,
+    results: usize,
+    unreachable: bool,
+}
+
+struct Validator {
+    height: usize,
+    frames: Vec<Frame>,
+}
+
+impl Validator {
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("the function frame is never popped")
+    }
+
+    fn push(&mut self, count: usize) {
+        self.height += count;
+    }
+
+    fn pop(&mut self, count: usize) -> Result<(), String> {
+        for _ in 0..count {
+            let frame = self.frames.last().expect("the function frame is never popped");
+            if self.height == frame.height {
+                if !frame.unreachable {
+                    return Err("the stack is empty".to_string());
+                }
+            } else {
+                self.height -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), String> {
+        let frame = self.frames.last().expect("the function frame is never popped");
+        let (height, results) = (frame.height, frame.results);
+        self.pop(results)?;
+        if self.height != height {
+            return Err(format!("{} values are left on the stack", self.height - height));
+        }
+        self.push(results);
+        Ok(())
+    }
+
+    fn instruction(&mut self, instruction: &Instruction, locals: u64) -> Result<(), String> {
+        if let Instruction::LocalGet(local) | Instruction::LocalSet(local) = instruction {
+            if *local as u64 >= locals {
+                return Err(format!("local {} does not exist", local));
+            }
+        }
+
+        match instruction {
+            Instruction::Unreachable => {
+                let height = self.frame().height;
+                self.height = height;
+                self.frame().unreachable = true;
+            }
+            Instruction::If => {
+                self.pop(1)?;
+                self.frames.push(Frame {
+                    height: self.height,
+                    results: 0,
+                    unreachable: false,
+                });
+            }
+            Instruction::End => {
+                if self.frames.len() == 1 {
+                    return Err("there is no block to end".to_string());
+                }
+                self.end()?;
+                self.frames.pop();
+            }
+            Instruction::Select => {
+                self.pop(3)?;
+                self.push(1);
+            }
+            Instruction::LocalGet(_) | Instruction::I32Const(_) => self.push(1),
+            Instruction::LocalSet(_) => self.pop(1)?,
+            Instruction::I32Eqz => {
+                self.pop(1)?;
+                self.push(1);
+            }
+            Instruction::Binary(_) => {
+                self.pop(2)?;
+                self.push(1);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use crate::ast::Operator;
+use crate::arithmetic::OverflowMode;
+use crate::codegen::abi::*;
+use crate::codegen::regalloc::{allocate, Allocation, Location};
+use crate::ir::instruction::{Function, Instruction, Operand, Variable};
+
+// An x86-64 backend, which emits GNU assembler code in AT&T syntax
+// 	- The function follows the System V calling convention: the inputs and then the pointer
+// 	  to the bindings are passed in rdi, rsi, rdx, rcx, r8 and r9, and on the stack after that
+// 	- Variables live in the registers the allocator gives them (see regalloc). Spilled inputs and
+// 	  temporaries live in the stack frame, and spilled bindings in the bindings buffer, whose
+// 	  address is kept in r11. Every binding is also stored in the buffer when it is initialized.
+// 	- Every instruction loads its left operand in al and its right operand in cl, and stores al.
+// 	  Overflows are read from the carry flag, which unsigned byte operations set.
+// 	- Every panic returns its status from a stub at the end of the function
+pub fn emit(function: &Function, overflow: OverflowMode) -> String {
+    let symbol = format!("{}{}", SYMBOL_PREFIX, function.name);
+    let allocation = allocation(function);
+    let saved: Vec<&str> = allocation
+        .used_registers(&REGISTERS)
+        .into_iter()
+        .filter(|register| CALLEE_SAVED.contains(register))
+        .collect();
+    let mut emitter = Emitter {
+        overflow,
+        symbol: symbol.clone(),
+        locations: HashMap::new(),
+        bindings: HashMap::new(),
+        saved: saved.clone(),
+        code: String::new(),
+        panics: Vec::new(),
+    };
+
+    // Variables in registers, then a byte of the frame for every spilled input and temporary,
+    // below the saved registers. Bindings that are spilled are read back from the bindings buffer.
+    let mut slots = 0;
+    let mut variables: Vec<Variable> = function.inputs.iter().map(|input| Variable::Named(input.clone())).collect();
+    variables.extend(function.instructions.iter().map(|instruction| instruction.destination().clone()));
+    let bindings = bindings(function);
+    for variable in variables {
+        let location = match (allocation.locations.get(&variable), &variable) {
+            (Some(Location::Register(register)), _) => format!("%{}", byte_register(register)),
+            (_, Variable::Named(name)) if bindings.contains(name) => continue,
+            // Inputs that are never read are not kept
+            (None, Variable::Named(_)) => continue,
+            _ => {
+                slots += 1;
+                format!("-{}(%rbp)", 8 * saved.len() + slots)
+            }
+        };
+        emitter.locations.insert(variable, location);
+    }
+    for (index, binding) in bindings.into_iter().enumerate() {
+        let variable = Variable::Named(binding);
+        let slot = format!("{}(%r11)", index);
+        emitter.locations.entry(variable.clone()).or_insert(slot.clone());
+        emitter.bindings.insert(variable, slot);
+    }
+    // Keeps the stack aligned to 16 bytes
+    let frame = (8 * saved.len() + slots).div_ceil(16) * 16 - 8 * saved.len();
+
+    emitter.line("pushq", "%rbp");
+    emitter.line("movq", "%rsp, %rbp");
+    for register in &saved {
+        emitter.line("pushq", &format!("%{}", register));
+    }
+    if frame > 0 {
+        emitter.line("subq", &format!("${}, %rsp", frame));
+    }
+    for (index, input) in function.inputs.iter().enumerate() {
+        let Some(location) = emitter.locations.get(&Variable::Named(input.clone())).cloned() else {
+            continue;
+        };
+        match ARGUMENTS.get(index) {
+            Some((_, byte)) => emitter.line("movb", &format!("%{}, {}", byte, location)),
+            None => {
+                emitter.line("movzbl", &format!("{}, %eax", stack_argument(index)));
+                emitter.line("movb", &format!("%al, {}", location));
+            }
+        }
+    }
+    let pointer = function.inputs.len();
+    match ARGUMENTS.get(pointer) {
+        Some((register, _)) => emitter.line("movq", &format!("%{}, %r11", register)),
+        None => emitter.line("movq", &format!("{}, %r11", stack_argument(pointer))),
+    }
+
+    for (instruction, binding) in function.instructions.iter().zip(initialized_bindings(function)) {
+        emitter.instruction(instruction, binding);
+    }
+
+    emitter.line("xorl", "%eax, %eax");
+    emitter.ret();
+    for (label, status) in std::mem::take(&mut emitter.panics) {
+        let _ = writeln!(emitter.code, "{}:", label);
+        emitter.line("movl", &format!("${}, %eax", status));
+        emitter.ret();
+    }
+
+    let inputs = function
+        .inputs
+        .iter()
+        .map(|input| format!("{}: u8", input))
+        .collect::<Vec<String>>()
+        .join(", ");
+    format!(
+        "# fn {}({})\n\t.text\n\t.globl\t{}\n\t.type\t{}, @function\n{}:\n{}\t.size\t{}, .-{}\n\t.section\t.note.GNU-stack,\"\",@progbits\n",
+        function.name, inputs, symbol, symbol, symbol, emitter.code, symbol, symbol
+    )
+}
+
+// The registers variables are allocated to. r10 is free once the arguments are read,
+// and the others are saved by the function.
+pub const REGISTERS: [&str; 6] = ["r10", "rbx", "r12", "r13", "r14", "r15"];
+const CALLEE_SAVED: [&str; 5] = ["rbx", "r12", "r13", "r14", "r15"];
+
+// The registers of the variables of a function
+pub fn allocation(function: &Function) -> Allocation<&'static str> {
+    allocate(function, &REGISTERS)
+}
+
+// The lowest byte of an allocated register
+fn byte_register(register: &str) -> String {
+    match register {
+        "rbx" => "bl".to_string(),
+        register => format!("{}b", register),
+    }
+}
+
+// The registers of the first integer arguments, and their lowest byte
+const ARGUMENTS: [(&str, &str); 6] = [("rdi", "dil"), ("rsi", "sil"), ("rdx", "dl"), ("rcx", "cl"), ("r8", "r8b"), ("r9", "r9b")];
+
+// An argument passed on the stack, above the return address and the saved rbp
+fn stack_argument(index: usize) -> String {
+    format!("{}(%rbp)", 16 + 8 * (index - ARGUMENTS.len()))
+}
+
+struct Emitter {
+    overflow: OverflowMode,
+    symbol: String,
+    // The operand every variable is read from
+    locations: HashMap<Variable, String>,
+    // The slot of every binding in the bindings buffer
+    bindings: HashMap<Variable, String>,
+    // The callee-saved registers the function uses, in the order they are pushed
+    saved: Vec<&'static str>,
+    code: String,
+    // The label and status of every panic stub
+    panics: Vec<(String, u32)>,
+}
+
+impl Emitter {
+    fn line(&mut self, mnemonic: &str, operands: &str) {
+        let _ = match operands {
+            "" => writeln!(self.code, "\t{}", mnemonic),
+            _ => writeln!(self.code, "\t{}\t{}", mnemonic, operands),
+        };
+    }
+
+    fn load(&mut self, operand: &Operand, register: &str) {
+        match operand {
+            Operand::Constant(val) => self.line("movl", &format!("${}, {}", val, register)),
+            Operand::Variable(variable) => {
+                let location = self.locations[variable].clone();
+                self.line("movzbl", &format!("{}, {}", location, register));
+            }
+        }
+    }
+
+    // Restores the saved registers and returns
+    fn ret(&mut self) {
+        if self.saved.is_empty() {
+            self.line("leave", "");
+        } else {
+            self.line("leaq", &format!("-{}(%rbp), %rsp", 8 * self.saved.len()));
+            for register in self.saved.clone().iter().rev() {
+                self.line("popq", &format!("%{}", register));
+            }
+            self.line("popq", "%rbp");
+        }
+        self.line("ret", "");
+    }
+
+    // Jumps to the stub of a panic when the condition holds
+    fn panic_if(&mut self, condition: &str, status: u32) {
+        let label = match self.panics.iter().find(|(_, known)| *known == status) {
+            Some((label, _)) => label.clone(),
+            None => {
+                let label = format!(".L{}_panic{}", self.symbol, self.panics.len());
+                self.panics.push((label.clone(), status));
+                label
+            }
+        };
+        self.line(&format!("j{}", condition), &label);
+    }
+
+    fn instruction(&mut self, instruction: &Instruction, binding: usize) {
+        let _ = writeln!(self.code, "\t# {}", instruction);
+        match instruction {
+            Instruction::Copy { source, .. } => self.load(source, "%eax"),
+            Instruction::Binary { operator, arithmetic, left, right, .. } => {
+                self.load(left, "%eax");
+                self.load(right, "%ecx");
+                let behaviour = behaviour(self.overflow, operator, arithmetic);
+                match operator {
+                    Operator::Add | Operator::Subtract | Operator::Multiply => {
+                        let (saturated, operation) = match operator {
+                            Operator::Add => (255, "addb\t%cl, %al"),
+                            Operator::Subtract => (0, "subb\t%cl, %al"),
+                            // ax = al * cl, with the carry set if ah is not zero
+                            _ => (255, "mulb\t%cl"),
+                        };
+                        let _ = writeln!(self.code, "\t{}", operation);
+                        self.carry(&behaviour, binding, saturated);
+                    }
+                    Operator::Divide => {
+                        self.line("testb", "%cl, %cl");
+                        self.panic_if("z", status(&division_by_zero(arithmetic), binding));
+                        // A u8 division never overflows
+                        match behaviour {
+                            Behaviour::Flag => self.line("xorl", "%eax, %eax"),
+                            _ => self.line("divb", "%cl"),
+                        }
+                    }
+                    Operator::ShiftLeft | Operator::ShiftRight => {
+                        let shift = if *operator == Operator::ShiftLeft { "shlb" } else { "shrb" };
+                        match behaviour {
+                            Behaviour::Checked(panic) => {
+                                self.line("cmpb", "$8, %cl");
+                                self.panic_if("ae", status(&panic, binding));
+                                self.line(shift, "%cl, %al");
+                            }
+                            // The amount is taken modulo 8, not modulo 32 like x86 does
+                            Behaviour::Wrapping => {
+                                self.line("andb", "$7, %cl");
+                                self.line(shift, "%cl, %al");
+                            }
+                            Behaviour::Saturating => {
+                                self.line("movl", "%ecx, %edx");
+                                self.line("andb", "$7, %cl");
+                                self.line(shift, "%cl, %al");
+                                self.line("cmpb", "$8, %dl");
+                                self.line("movl", "$0, %ecx");
+                                self.line("cmovael", "%ecx, %eax");
+                            }
+                            Behaviour::Flag => {
+                                self.line("cmpb", "$8, %cl");
+                                self.line("setae", "%al");
+                            }
+                        }
+                    }
+                    Operator::BitAnd | Operator::BitOr | Operator::BitXor => match behaviour {
+                        Behaviour::Flag => self.line("xorl", "%eax, %eax"),
+                        _ => {
+                            let mnemonic = match operator {
+                                Operator::BitAnd => "andb",
+                                Operator::BitOr => "orb",
+                                _ => "xorb",
+                            };
+                            self.line(mnemonic, "%cl, %al");
+                        }
+                    },
+                }
+            }
+        }
+        let destination = instruction.destination();
+        if let Some(slot) = self.bindings.get(destination).cloned() {
+            self.line("movb", &format!("%al, {}", slot));
+        }
+        if let Some(location) = self.locations.get(destination).cloned() {
+            if self.bindings.get(destination) != Some(&location) {
+                self.line("movb", &format!("%al, {}", location));
+            }
+        }
+    }
+
+    // Handles the carry flag of an addition, subtraction or multiplication, which is set on overflow
+    fn carry(&mut self, behaviour: &Behaviour, binding: usize, saturated: u8) {
+        match behaviour {
+            Behaviour::Checked(panic) => self.panic_if("c", status(panic, binding)),
+            Behaviour::Wrapping => {}
+            // mov leaves the flags alone
+            Behaviour::Saturating => {
+                self.line("movl", &format!("${}, %edx", saturated));
+                self.line("cmovcl", "%edx, %eax");
+            }
+            Behaviour::Flag => self.line("setc", "%al"),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::ast::Operator;
+use crate::dataflow::framework::*;
+use crate::ir::cfg::Cfg;
+use crate::ir::instruction::{Arithmetic, Instruction, Operand, Variable};
+
+// Available expressions as a forward "must" analysis
+// 	- An operation is available at a point if every path to the point computes it,
+// 	  and none of its operands is redefined after that
+// 	- Nothing is available at the entry
+// 	- An instruction kills the operations that read its destination, and makes its own operation available
+pub struct AvailableExpressions;
+
+// An operation on operands, e.g add x, 1
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
+pub struct Operation {
+    pub operator: Operator,
+    pub arithmetic: Arithmetic,
+    pub left: Operand,
+    pub right: Operand,
+}
+
+impl Operation {
+    fn reads(&self, variable: &Variable) -> bool {
+        [&self.left, &self.right]
+            .iter()
+            .any(|operand| matches!(operand, Operand::Variable(read) if read == variable))
+    }
+}
+
+impl Analysis for AvailableExpressions {
+    type Fact = MustSet<Operation>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn boundary(&self, _cfg: &Cfg) -> MustSet<Operation> {
+        MustSet(Some(Default::default()))
+    }
+
+    fn initial(&self, _cfg: &Cfg) -> MustSet<Operation> {
+        MustSet(None)
+    }
+
+    fn transfer(&self, _location: Location, instruction: &Instruction, available: &mut MustSet<Operation>) {
+        let Some(operations) = &mut available.0 else {
+            return;
+        };
+
+        let destination = instruction.destination();
+        operations.retain(|operation| !operation.reads(destination));
+
+        if let Instruction::Binary { operator, arithmetic, left, right, .. } = instruction {
+            let operation = Operation {
+                operator: operator.clone(),
+                arithmetic: arithmetic.clone(),
+                left: left.clone(),
+                right: right.clone(),
+            };
+            // x = add x, 1 does not make add x, 1 available
+            if !operation.reads(destination) {
+                operations.insert(operation);
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use crate::arithmetic::OverflowMode;
+use crate::dataflow::framework::*;
+use crate::ir::cfg::Cfg;
+use crate::ir::instruction::{Instruction, Operand, Variable};
+use crate::ir::sccp::{evaluate_instruction, Lattice as Value};
+
+// Constant propagation as a forward analysis
+// 	- The fact at a point maps every variable defined so far to its value: a constant, or overdefined
+// 	- Where paths meet, a variable keeps its constant only if every path agrees on it
+// 	- Unlike sccp, both sides of every branch are assumed to be taken
+pub struct Constants {
+    overflow: OverflowMode,
+}
+
+// The values of the variables at a point. None if no path reaches the point.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ConstantFacts(pub Option<BTreeMap<Variable, Value>>);
+
+impl ConstantFacts {
+    pub fn value(&self, variable: &Variable) -> Value {
+        match &self.0 {
+            Some(values) => values.get(variable).copied().unwrap_or(Value::Undefined),
+            None => Value::Undefined,
+        }
+    }
+
+    pub fn operand(&self, operand: &Operand) -> Value {
+        match operand {
+            Operand::Constant(val) => Value::Constant(*val),
+            Operand::Variable(variable) => self.value(variable),
+        }
+    }
+}
+
+impl Lattice for ConstantFacts {
+    fn join(&mut self, other: &Self) -> bool {
+        let Some(other_values) = &other.0 else {
+            return false;
+        };
+        let Some(values) = &mut self.0 else {
+            self.0 = other.0.clone();
+            return true;
+        };
+
+        let mut changed = false;
+        for (variable, value) in other_values {
+            let current = values.get(variable).copied().unwrap_or(Value::Undefined);
+            let joined = current.meet(*value);
+            if joined != current {
+                values.insert(variable.clone(), joined);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+impl Constants {
+    pub fn new(overflow: OverflowMode) -> Self {
+        Constants { overflow }
+    }
+}
+
+impl Analysis for Constants {
+    type Fact = ConstantFacts;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    // The inputs are unknown
+    fn boundary(&self, cfg: &Cfg) -> ConstantFacts {
+        ConstantFacts(Some(
+            cfg.inputs
+                .iter()
+                .map(|input| (Variable::Named(input.clone()), Value::Overdefined))
+                .collect(),
+        ))
+    }
+
+    fn initial(&self, _cfg: &Cfg) -> ConstantFacts {
+        ConstantFacts(None)
+    }
+
+    fn transfer(&self, _location: Location, instruction: &Instruction, fact: &mut ConstantFacts) {
+        let value = match instruction {
+            Instruction::Copy { source, .. } => fact.operand(source),
+            Instruction::Binary { left, right, .. } => match (fact.operand(left), fact.operand(right)) {
+                (Value::Constant(left), Value::Constant(right)) => {
+                    match evaluate_instruction(self.overflow, instruction, left, right) {
+                        Ok(result) => Value::Constant(result),
+                        Err(_) => Value::Overdefined,
+                    }
+                }
+                (Value::Undefined, _) | (_, Value::Undefined) => Value::Undefined,
+                _ => Value::Overdefined,
+            },
+        };
+
+        if let Some(values) = &mut fact.0 {
+            values.insert(instruction.destination().clone(), value);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// A generic worklist solver for dataflow analyses over a control-flow graph
+// 	- An analysis describes:
+// 		- the facts it computes, which form a lattice: facts of different paths are joined
+// 		  where the paths meet, and joining only ever moves up the lattice
+// 		- its direction: forward analyses flow from the entry along the edges,
+// 		  backward analyses flow from the returns against them
+// 		- the fact at the boundary (the entry, or the returns) and the initial fact of every other block
+// 		- how an instruction (and a terminator) transforms a fact
+// 	- The solver visits blocks from a worklist until no fact changes, in reverse postorder
+// 	  for forward analyses and in postorder for backward ones, so that loops converge quickly
+// 	- Graphs are expected out of SSA form: phi nodes are ignored
+use std::collections::{BTreeSet, VecDeque};
+use crate::ir::cfg::{BlockId, Cfg, Terminator};
+use crate::ir::instruction::Instruction;
+
+pub trait Lattice: Clone + PartialEq {
+    // Joins the fact of another path into this one. Returns whether this fact changed.
+    fn join(&mut self, other: &Self) -> bool;
+}
+
+// Sets joined by union, for "may" analyses: a fact holds if it holds along some path
+impl<T: Ord + Clone> Lattice for BTreeSet<T> {
+    fn join(&mut self, other: &Self) -> bool {
+        let size = self.len();
+        self.extend(other.iter().cloned());
+        self.len() != size
+    }
+}
+
+// Sets joined by intersection, for "must" analyses: a fact holds if it holds along every path.
+// None is the set of everything, the fact of blocks that no path has reached yet.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct MustSet<T: Ord + Clone>(pub Option<BTreeSet<T>>);
+
+impl<T: Ord + Clone> Lattice for MustSet<T> {
+    fn join(&mut self, other: &Self) -> bool {
+        match (&mut self.0, &other.0) {
+            (_, None) => false,
+            (None, Some(other)) => {
+                self.0 = Some(other.clone());
+                true
+            }
+            (Some(set), Some(other)) => {
+                let size = set.len();
+                set.retain(|element| other.contains(element));
+                set.len() != size
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+// The position of an instruction in a graph
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone, Copy)]
+pub struct Location {
+    pub block: BlockId,
+    pub index: usize,
+}
+
+pub trait Analysis {
+    type Fact: Lattice;
+
+    fn direction(&self) -> Direction;
+
+    // The fact at the entry (forward) or at the returns (backward)
+    fn boundary(&self, cfg: &Cfg) -> Self::Fact;
+
+    // The fact of every block before the solver reaches it
+    fn initial(&self, cfg: &Cfg) -> Self::Fact;
+
+    // Transforms the fact before an instruction into the fact after it,
+    // in the direction of the analysis
+    fn transfer(&self, location: Location, instruction: &Instruction, fact: &mut Self::Fact);
+
+    fn transfer_terminator(&self, _block: BlockId, _terminator: &Terminator, _fact: &mut Self::Fact) {}
+}
+
+// The facts at the start and at the end of every block, in program order
+// whatever the direction of the analysis
+#[derive(Debug, Clone)]
+pub struct Solution<F> {
+    pub start: Vec<F>,
+    pub end: Vec<F>,
+}
+
+pub fn solve<A: Analysis>(analysis: &A, cfg: &Cfg) -> Solution<A::Fact> {
+    let blocks = cfg.blocks.len();
+    let predecessors = cfg.predecessors();
+    let mut start = vec![analysis.initial(cfg); blocks];
+    let mut end = vec![analysis.initial(cfg); blocks];
+
+    let mut order = cfg.reverse_postorder();
+    if analysis.direction() == Direction::Backward {
+        order.reverse();
+    }
+    let mut worklist: VecDeque<BlockId> = order.into_iter().collect();
+    let mut queued = vec![false; blocks];
+    for block in &worklist {
+        queued[*block] = true;
+    }
+
+    while let Some(block) = worklist.pop_front() {
+        queued[block] = false;
+
+        let changed = match analysis.direction() {
+            Direction::Forward => {
+                let mut input = if block == 0 { analysis.boundary(cfg) } else { analysis.initial(cfg) };
+                for predecessor in &predecessors[block] {
+                    input.join(&end[*predecessor]);
+                }
+                start[block] = input.clone();
+
+                for (index, instruction) in cfg.blocks[block].instructions.iter().enumerate() {
+                    analysis.transfer(Location { block, index }, instruction, &mut input);
+                }
+                analysis.transfer_terminator(block, &cfg.blocks[block].terminator, &mut input);
+
+                let changed = end[block] != input;
+                end[block] = input;
+                changed
+            }
+            Direction::Backward => {
+                let successors = cfg.successors(block);
+                let mut output = if successors.is_empty() { analysis.boundary(cfg) } else { analysis.initial(cfg) };
+                for successor in &successors {
+                    output.join(&start[*successor]);
+                }
+                end[block] = output.clone();
+
+                analysis.transfer_terminator(block, &cfg.blocks[block].terminator, &mut output);
+                for (index, instruction) in cfg.blocks[block].instructions.iter().enumerate().rev() {
+                    analysis.transfer(Location { block, index }, instruction, &mut output);
+                }
+
+                let changed = start[block] != output;
+                start[block] = output;
+                changed
+            }
+        };
+
+        if changed {
+            let dependents = match analysis.direction() {
+                Direction::Forward => cfg.successors(block),
+                Direction::Backward => predecessors[block].clone(),
+            };
+            for dependent in dependents {
+                if !queued[dependent] {
+                    queued[dependent] = true;
+                    worklist.push_back(dependent);
+                }
+            }
+        }
+    }
+
+    Solution { start, end }
+}
+
+// The facts before every instruction of a block, in program order, followed by the fact at its end
+pub fn instruction_facts<A: Analysis>(analysis: &A, cfg: &Cfg, solution: &Solution<A::Fact>, block: BlockId) -> Vec<A::Fact> {
+    let instructions = &cfg.blocks[block].instructions;
+
+    match analysis.direction() {
+        Direction::Forward => {
+            let mut fact = solution.start[block].clone();
+            let mut facts = vec![fact.clone()];
+            for (index, instruction) in instructions.iter().enumerate() {
+                analysis.transfer(Location { block, index }, instruction, &mut fact);
+                facts.push(fact.clone());
+            }
+            facts
+        }
+        Direction::Backward => {
+            let mut fact = solution.end[block].clone();
+            analysis.transfer_terminator(block, &cfg.blocks[block].terminator, &mut fact);
+            let mut facts = vec![fact.clone()];
+            for (index, instruction) in instructions.iter().enumerate().rev() {
+                analysis.transfer(Location { block, index }, instruction, &mut fact);
+                facts.push(fact.clone());
+            }
+            facts.reverse();
+            facts
+        }
+    }
+}
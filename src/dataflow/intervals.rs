@@ -0,0 +1,324 @@
+use std::collections::BTreeMap;
+use crate::ast::{MethodKind, Operator};
+use crate::arithmetic::{OverflowMode, Panic};
+use crate::dataflow::framework::*;
+use crate::ir::cfg::Cfg;
+use crate::ir::instruction::{Arithmetic, Instruction, Operand, Variable};
+
+// Interval analysis over u8, as a forward analysis
+// 	- The fact at a point maps every variable defined so far to the range [lo, hi] of the values it may hold
+// 	- Inputs range over every u8, unless a narrower range is given
+// 	- Every operation first computes the range of its exact result over the integers, which tells
+// 	  whether it overflows for none, some or all of the values of its operands. Division by zero and
+// 	  shifts by 8 bits or more are found from the range of the right operand.
+// 	- The range of the result then follows the semantics of the operation: a panicking operation only
+// 	  keeps the values that did not overflow, a wrapping one wraps them and a saturating one clamps them
+// 	- An operation that always panics has no result, so the operations depending on it are not checked
+// 	- Where paths meet, ranges are joined into the smallest range covering both. Ranges only grow
+// 	  and are bounded by [0, 255], so loops converge without widening.
+pub struct Intervals {
+    overflow: OverflowMode,
+    inputs: BTreeMap<String, Interval>,
+}
+
+// The values from lo to hi, both included. Exact results of operations may lie outside of u8.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Interval {
+    pub lo: i32,
+    pub hi: i32,
+}
+
+impl Interval {
+    pub fn new(lo: i32, hi: i32) -> Self {
+        Interval { lo, hi }
+    }
+
+    pub fn constant(val: u8) -> Self {
+        Interval::new(val as i32, val as i32)
+    }
+
+    // Every u8
+    pub fn full() -> Self {
+        Interval::new(0, u8::MAX as i32)
+    }
+
+    pub fn hull(self, other: Interval) -> Interval {
+        Interval::new(self.lo.min(other.lo), self.hi.max(other.hi))
+    }
+
+    pub fn intersect(self, other: Interval) -> Option<Interval> {
+        let interval = Interval::new(self.lo.max(other.lo), self.hi.min(other.hi));
+        (interval.lo <= interval.hi).then_some(interval)
+    }
+
+    // Whether every value of this interval fits in a u8
+    pub fn fits(self) -> bool {
+        self.lo >= 0 && self.hi <= u8::MAX as i32
+    }
+
+    // Whether no value of this interval fits in a u8
+    pub fn never_fits(self) -> bool {
+        self.hi < 0 || self.lo > u8::MAX as i32
+    }
+
+    // The values modulo 256, which only keep their bounds if they do not cross a multiple of 256
+    fn wrap(self) -> Interval {
+        if self.lo.div_euclid(256) == self.hi.div_euclid(256) {
+            Interval::new(self.lo.rem_euclid(256), self.hi.rem_euclid(256))
+        } else {
+            Interval::full()
+        }
+    }
+
+    fn clamp(self) -> Interval {
+        let max = u8::MAX as i32;
+        Interval::new(self.lo.clamp(0, max), self.hi.clamp(0, max))
+    }
+}
+
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
+
+// Whether an operation panics, given the ranges of its operands
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Verdict {
+    // Proven not to panic for any value of the operands
+    Safe,
+    // Panics for some values of the operands
+    MayPanic(Panic),
+    // Panics for every value of the operands
+    AlwaysPanics(Panic),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Outcome {
+    pub verdict: Verdict,
+    // The range of the result over the integers, for the values of the right operand that
+    // do not divide by zero or shift by 8 bits or more. None if there are no such values.
+    pub exact: Option<Interval>,
+    // The range of the result when the operation does not panic. None if it always panics.
+    pub result: Option<Interval>,
+}
+
+// The ranges of the variables at a point. None if no path reaches the point.
+// A variable without a range has not been defined yet, or is defined by an operation that always panics.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct IntervalFacts(pub Option<BTreeMap<Variable, Interval>>);
+
+impl IntervalFacts {
+    pub fn range(&self, variable: &Variable) -> Option<Interval> {
+        self.0.as_ref().and_then(|ranges| ranges.get(variable).copied())
+    }
+
+    pub fn operand(&self, operand: &Operand) -> Option<Interval> {
+        match operand {
+            Operand::Constant(val) => Some(Interval::constant(*val)),
+            Operand::Variable(variable) => self.range(variable),
+        }
+    }
+}
+
+impl Lattice for IntervalFacts {
+    fn join(&mut self, other: &Self) -> bool {
+        let Some(other_ranges) = &other.0 else {
+            return false;
+        };
+        let Some(ranges) = &mut self.0 else {
+            self.0 = other.0.clone();
+            return true;
+        };
+
+        let mut changed = false;
+        for (variable, range) in other_ranges {
+            let joined = match ranges.get(variable) {
+                Some(current) => current.hull(*range),
+                None => *range,
+            };
+            if ranges.get(variable) != Some(&joined) {
+                ranges.insert(variable.clone(), joined);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+// What an operation does with a result that does not fit in a u8
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Behaviour {
+    Panic,
+    Wrap,
+    Saturate,
+    // Returns whether it overflowed
+    Flag,
+}
+
+// For how many values of the operands something happens
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum Certainty {
+    Never,
+    Sometimes,
+    Always,
+}
+
+impl Intervals {
+    pub fn new(overflow: OverflowMode) -> Self {
+        Intervals {
+            overflow,
+            inputs: BTreeMap::new(),
+        }
+    }
+
+    // Narrows the values an input may take
+    pub fn with_input(mut self, name: &str, range: Interval) -> Self {
+        self.inputs.insert(name.to_string(), range);
+        self
+    }
+
+    // Evaluates an instruction over the ranges of its operands.
+    // None if an operand has no range: the instruction is never reached.
+    pub fn outcome(&self, instruction: &Instruction, fact: &IntervalFacts) -> Option<Outcome> {
+        match instruction {
+            Instruction::Copy { source, .. } => {
+                let range = fact.operand(source)?;
+                Some(Outcome {
+                    verdict: Verdict::Safe,
+                    exact: Some(range),
+                    result: Some(range),
+                })
+            }
+            Instruction::Binary { operator, arithmetic, left, right, .. } => {
+                Some(self.binary(operator, arithmetic, fact.operand(left)?, fact.operand(right)?))
+            }
+        }
+    }
+
+    fn binary(&self, operator: &Operator, arithmetic: &Arithmetic, left: Interval, right: Interval) -> Outcome {
+        let behaviour = match arithmetic {
+            Arithmetic::Panicking => match self.overflow {
+                OverflowMode::Error | OverflowMode::Panic => Behaviour::Panic,
+                OverflowMode::Wrap => Behaviour::Wrap,
+                OverflowMode::Saturate => Behaviour::Saturate,
+            },
+            Arithmetic::Method(MethodKind::Wrapping | MethodKind::Overflowing) => Behaviour::Wrap,
+            Arithmetic::Method(MethodKind::Saturating) => Behaviour::Saturate,
+            Arithmetic::Method(MethodKind::Checked) => Behaviour::Panic,
+            Arithmetic::Method(MethodKind::OverflowFlag) => Behaviour::Flag,
+        };
+        let shift = matches!(operator, Operator::ShiftLeft | Operator::ShiftRight);
+        // There are no saturating shifts, they panic like checked ones
+        let behaviour = if shift && behaviour == Behaviour::Saturate { Behaviour::Panic } else { behaviour };
+        let wrapping_shift = shift && matches!(behaviour, Behaviour::Wrap | Behaviour::Flag);
+
+        // checked_add returns None instead of panicking, the unwrap panics instead
+        let checked = *arithmetic == Arithmetic::Method(MethodKind::Checked);
+        let panic = |panic: Panic| if checked { Panic::Unwrap } else { panic };
+
+        // The values of the right operand that do not panic whatever the left operand: divisors
+        // other than zero and shifts by less than 8 bits. Wrapping shifts take the amount modulo 8.
+        let valid = match operator {
+            Operator::Divide => right.intersect(Interval::new(1, 255)),
+            _ if wrapping_shift && right.hi >= 8 => Some(Interval::new(0, 7)),
+            _ if shift => right.intersect(Interval::new(0, 7)),
+            _ => Some(right),
+        };
+        let invalid = match valid {
+            None => Certainty::Always,
+            Some(valid) if valid == right || wrapping_shift => Certainty::Never,
+            Some(_) => Certainty::Sometimes,
+        };
+        let invalid_panic = match operator {
+            Operator::Divide => panic(Panic::DivisionByZero),
+            _ => panic(Panic::Overflow(operator.clone())),
+        };
+
+        let exact = valid.map(|right| match operator {
+            Operator::Add => Interval::new(left.lo + right.lo, left.hi + right.hi),
+            Operator::Subtract => Interval::new(left.lo - right.hi, left.hi - right.lo),
+            Operator::Multiply => Interval::new(left.lo * right.lo, left.hi * right.hi),
+            Operator::Divide => Interval::new(left.lo / right.hi, left.hi / right.lo),
+            Operator::ShiftLeft => Interval::new(left.lo << right.lo, left.hi << right.hi),
+            Operator::ShiftRight => Interval::new(left.lo >> right.hi, left.hi >> right.lo),
+            // Bitwise operations cannot set bits above the highest bit of their operands
+            Operator::BitAnd => Interval::new(0, left.hi.min(right.hi)),
+            Operator::BitOr => Interval::new(left.lo.max(right.lo), ones_below(left.hi.max(right.hi))),
+            Operator::BitXor => Interval::new(0, ones_below(left.hi.max(right.hi))),
+        });
+
+        // Only additions, subtractions and multiplications overflow by their result
+        let overflow = match (operator, exact) {
+            (Operator::Add | Operator::Subtract | Operator::Multiply, Some(exact)) if exact.never_fits() => Certainty::Always,
+            (Operator::Add | Operator::Subtract | Operator::Multiply, Some(exact)) if !exact.fits() => Certainty::Sometimes,
+            _ => Certainty::Never,
+        };
+
+        let verdict = match (invalid, overflow) {
+            (Certainty::Always, _) => Verdict::AlwaysPanics(invalid_panic),
+            (_, Certainty::Always) if behaviour == Behaviour::Panic => Verdict::AlwaysPanics(panic(Panic::Overflow(operator.clone()))),
+            (Certainty::Sometimes, _) => Verdict::MayPanic(invalid_panic),
+            (_, Certainty::Sometimes) if behaviour == Behaviour::Panic => Verdict::MayPanic(panic(Panic::Overflow(operator.clone()))),
+            _ => Verdict::Safe,
+        };
+
+        let result = match (&verdict, exact) {
+            (Verdict::AlwaysPanics(_), _) | (_, None) => None,
+            (_, Some(exact)) => Some(match (behaviour, operator) {
+                (Behaviour::Flag, _) => match overflow {
+                    Certainty::Never => Interval::new(0, 0),
+                    Certainty::Sometimes => Interval::new(0, 1),
+                    Certainty::Always => Interval::new(1, 1),
+                },
+                // The bits shifted out are lost, without overflowing
+                (_, Operator::ShiftLeft) if !exact.fits() => Interval::full(),
+                (_, Operator::ShiftLeft | Operator::ShiftRight | Operator::Divide | Operator::BitAnd | Operator::BitOr | Operator::BitXor) => exact,
+                (Behaviour::Panic, _) => exact.intersect(Interval::full()).expect("does not always overflow"),
+                (Behaviour::Wrap, _) => exact.wrap(),
+                (Behaviour::Saturate, _) => exact.clamp(),
+            }),
+        };
+
+        Outcome { verdict, exact, result }
+    }
+}
+
+// The value with every bit set up to the highest bit of a value, e.g 0b0111 for 0b0101
+fn ones_below(val: i32) -> i32 {
+    (val as u32 + 1).next_power_of_two() as i32 - 1
+}
+
+impl Analysis for Intervals {
+    type Fact = IntervalFacts;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn boundary(&self, cfg: &Cfg) -> IntervalFacts {
+        IntervalFacts(Some(
+            cfg.inputs
+                .iter()
+                .map(|input| {
+                    let range = self.inputs.get(input).copied().unwrap_or(Interval::full());
+                    (Variable::Named(input.clone()), range)
+                })
+                .collect(),
+        ))
+    }
+
+    fn initial(&self, _cfg: &Cfg) -> IntervalFacts {
+        IntervalFacts(None)
+    }
+
+    fn transfer(&self, _location: Location, instruction: &Instruction, fact: &mut IntervalFacts) {
+        let result = self.outcome(instruction, fact).and_then(|outcome| outcome.result);
+        if let Some(ranges) = &mut fact.0 {
+            match result {
+                Some(range) => ranges.insert(instruction.destination().clone(), range),
+                None => ranges.remove(instruction.destination()),
+            };
+        }
+    }
+}
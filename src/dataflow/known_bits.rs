@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+use crate::ast::{Method, MethodKind, Operator, Program};
+use crate::arithmetic::*;
+use crate::dataflow::framework::*;
+use crate::ir::cfg::Cfg;
+use crate::ir::instruction::{Arithmetic, Instruction, Operand, Variable};
+use crate::ir::lower::lower;
+
+// Known bits analysis, as a forward analysis
+// 	- The fact at a point maps every variable defined so far to the bits of its value that are known
+// 	  to be zero and the bits known to be one. The other bits may be either.
+// 	- It complements the interval analysis: x << 1u8 is in [0, 255], but its lowest bit is known to be zero
+// 	- An operation that does not panic has the wrapped result of its operands, so additions,
+// 	  subtractions and multiplications propagate what is known of their low bits upwards.
+// 	  Saturated results and shifts by an unknown amount only keep what clamping and shifting cannot change.
+// 	- Operations on constants are evaluated. One that panics has no known bits.
+// 	- Where paths meet, only the bits that are known and equal on both paths stay known
+pub struct KnownBits {
+    overflow: OverflowMode,
+}
+
+// The known bits of a u8 value. A bit is never set in both masks.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Bits {
+    pub zeros: u8,
+    pub ones: u8,
+}
+
+impl Bits {
+    pub fn unknown() -> Self {
+        Bits { zeros: 0, ones: 0 }
+    }
+
+    pub fn constant(val: u8) -> Self {
+        Bits { zeros: !val, ones: val }
+    }
+
+    // The bits that are known, either way
+    pub fn known(self) -> u8 {
+        self.zeros | self.ones
+    }
+
+    // The value, if every bit is known
+    pub fn value(self) -> Option<u8> {
+        (self.known() == u8::MAX).then_some(self.ones)
+    }
+
+    // The smallest and largest values with these bits
+    pub fn min(self) -> u8 {
+        self.ones
+    }
+
+    pub fn max(self) -> u8 {
+        !self.zeros
+    }
+
+    // The bits known on both sides
+    pub fn join(self, other: Bits) -> Bits {
+        Bits {
+            zeros: self.zeros & other.zeros,
+            ones: self.ones & other.ones,
+        }
+    }
+
+    fn not(self) -> Bits {
+        Bits {
+            zeros: self.ones,
+            ones: self.zeros,
+        }
+    }
+
+    // The known bits of left + right + carry, wrapping
+    fn add(self, other: Bits, carry: bool) -> Bits {
+        // The sums when every unknown bit is one, and when every unknown bit is zero
+        let sum_zeros = self.max().wrapping_add(other.max()).wrapping_add(carry as u8);
+        let sum_ones = self.min().wrapping_add(other.min()).wrapping_add(carry as u8);
+
+        // A carry into a bit is known if it is the same in both sums
+        let carry_zeros = !(sum_zeros ^ self.zeros ^ other.zeros);
+        let carry_ones = sum_ones ^ self.ones ^ other.ones;
+        let known = self.known() & other.known() & (carry_zeros | carry_ones);
+
+        Bits {
+            zeros: !sum_zeros & known,
+            ones: sum_ones & known,
+        }
+    }
+
+    // The known bits of left * right, wrapping
+    fn multiply(self, other: Bits) -> Bits {
+        // Trailing zeros add up
+        let trailing_zeros = (self.zeros.trailing_ones() + other.zeros.trailing_ones()).min(8);
+        let zeros = low_bits(trailing_zeros);
+
+        // The low bits of a product only depend on the low bits of its operands
+        let low = self.known().trailing_ones().min(other.known().trailing_ones());
+        let product = self.ones.wrapping_mul(other.ones);
+
+        Bits {
+            zeros: zeros | (!product & low_bits(low)),
+            ones: product & low_bits(low) & !zeros,
+        }
+    }
+
+    fn shift_left(self, bits: u32) -> Bits {
+        Bits {
+            zeros: (self.zeros << bits) | low_bits(bits),
+            ones: self.ones << bits,
+        }
+    }
+
+    fn shift_right(self, bits: u32) -> Bits {
+        Bits {
+            zeros: (self.zeros >> bits) | !(u8::MAX >> bits),
+            ones: self.ones >> bits,
+        }
+    }
+}
+
+// The lowest `count` bits
+fn low_bits(count: u32) -> u8 {
+    if count >= 8 { u8::MAX } else { (1u8 << count) - 1 }
+}
+
+// The highest `count` bits
+fn high_bits(count: u32) -> u8 {
+    !low_bits(8 - count.min(8))
+}
+
+impl std::fmt::Display for Bits {
+    // The bits from the highest, with ? for the unknown ones, e.g 0000???0
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for bit in (0..8).rev() {
+            let mask = 1u8 << bit;
+            let symbol = if self.ones & mask != 0 {
+                '1'
+            } else if self.zeros & mask != 0 {
+                '0'
+            } else {
+                '?'
+            };
+            write!(f, "{}", symbol)?;
+        }
+        Ok(())
+    }
+}
+
+// The known bits of an operation on operands with known bits, under the given overflow mode
+pub fn operation_bits(overflow: OverflowMode, operator: &Operator, arithmetic: &Arithmetic, left: Bits, right: Bits) -> Bits {
+    if let (Some(left), Some(right)) = (left.value(), right.value()) {
+        let result = match arithmetic {
+            Arithmetic::Panicking => evaluate_operator(overflow, operator, left, right),
+            Arithmetic::Method(kind) => evaluate_method(
+                &Method {
+                    kind: kind.clone(),
+                    operator: operator.clone(),
+                },
+                left,
+                right,
+            ),
+        };
+        return result.map(Bits::constant).unwrap_or(Bits::unknown());
+    }
+
+    let saturates = match arithmetic {
+        Arithmetic::Panicking => overflow == OverflowMode::Saturate,
+        Arithmetic::Method(MethodKind::Saturating) => true,
+        Arithmetic::Method(MethodKind::OverflowFlag) => {
+            // A bool cast to u8
+            return Bits { zeros: !1, ones: 0 };
+        }
+        Arithmetic::Method(_) => false,
+    };
+    // Wrapping shifts take the amount modulo 8, the others panic on larger amounts
+    let wraps = overflow == OverflowMode::Wrap && *arithmetic == Arithmetic::Panicking;
+
+    match operator {
+        Operator::Add | Operator::Subtract | Operator::Multiply if saturates => Bits::unknown(),
+        Operator::Add => left.add(right, false),
+        // a - b is a + !b + 1
+        Operator::Subtract => left.add(right.not(), true),
+        Operator::Multiply => left.multiply(right),
+        Operator::Divide => match right.value() {
+            Some(divisor) if divisor.is_power_of_two() => left.shift_right(divisor.trailing_zeros()),
+            // The quotient is at most the dividend
+            _ => Bits {
+                zeros: high_bits(left.zeros.leading_ones()),
+                ones: 0,
+            },
+        },
+        Operator::ShiftLeft | Operator::ShiftRight => {
+            let amount = match right.value() {
+                Some(bits) if wraps => Some(bits as u32 % 8),
+                Some(bits) if bits < 8 => Some(bits as u32),
+                Some(_) => return Bits::unknown(),
+                None => None,
+            };
+            match (operator, amount) {
+                (Operator::ShiftLeft, Some(bits)) => left.shift_left(bits),
+                (_, Some(bits)) => left.shift_right(bits),
+                // Shifting left keeps the trailing zeros, and shifting right the leading ones
+                (Operator::ShiftLeft, None) => Bits {
+                    zeros: low_bits(left.zeros.trailing_ones()),
+                    ones: 0,
+                },
+                (_, None) => Bits {
+                    zeros: high_bits(left.zeros.leading_ones()),
+                    ones: 0,
+                },
+            }
+        }
+        Operator::BitAnd => Bits {
+            zeros: left.zeros | right.zeros,
+            ones: left.ones & right.ones,
+        },
+        Operator::BitOr => Bits {
+            zeros: left.zeros & right.zeros,
+            ones: left.ones | right.ones,
+        },
+        Operator::BitXor => {
+            let known = left.known() & right.known();
+            let val = left.ones ^ right.ones;
+            Bits {
+                zeros: !val & known,
+                ones: val & known,
+            }
+        }
+    }
+}
+
+// The known bits of the variables at a point. None if no path reaches the point.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct BitsFacts(pub Option<BTreeMap<Variable, Bits>>);
+
+impl BitsFacts {
+    pub fn bits(&self, variable: &Variable) -> Bits {
+        self.0
+            .as_ref()
+            .and_then(|bits| bits.get(variable).copied())
+            .unwrap_or(Bits::unknown())
+    }
+
+    pub fn operand(&self, operand: &Operand) -> Bits {
+        match operand {
+            Operand::Constant(val) => Bits::constant(*val),
+            Operand::Variable(variable) => self.bits(variable),
+        }
+    }
+}
+
+impl Lattice for BitsFacts {
+    fn join(&mut self, other: &Self) -> bool {
+        let Some(other_bits) = &other.0 else {
+            return false;
+        };
+        let Some(bits) = &mut self.0 else {
+            self.0 = other.0.clone();
+            return true;
+        };
+
+        let mut changed = false;
+        for (variable, other) in other_bits {
+            let joined = match bits.get(variable) {
+                Some(current) => current.join(*other),
+                None => *other,
+            };
+            if bits.get(variable) != Some(&joined) {
+                bits.insert(variable.clone(), joined);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+impl KnownBits {
+    pub fn new(overflow: OverflowMode) -> Self {
+        KnownBits { overflow }
+    }
+}
+
+impl Analysis for KnownBits {
+    type Fact = BitsFacts;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    // Nothing is known of the inputs
+    fn boundary(&self, cfg: &Cfg) -> BitsFacts {
+        BitsFacts(Some(
+            cfg.inputs
+                .iter()
+                .map(|input| (Variable::Named(input.clone()), Bits::unknown()))
+                .collect(),
+        ))
+    }
+
+    fn initial(&self, _cfg: &Cfg) -> BitsFacts {
+        BitsFacts(None)
+    }
+
+    fn transfer(&self, _location: Location, instruction: &Instruction, fact: &mut BitsFacts) {
+        let bits = match instruction {
+            Instruction::Copy { source, .. } => fact.operand(source),
+            Instruction::Binary { operator, arithmetic, left, right, .. } => {
+                operation_bits(self.overflow, operator, arithmetic, fact.operand(left), fact.operand(right))
+            }
+        };
+        if let Some(facts) = &mut fact.0 {
+            facts.insert(instruction.destination().clone(), bits);
+        }
+    }
+}
+
+// The known bits of every input and let binding of a program, by name
+pub fn variable_bits(program: &Program, overflow: OverflowMode) -> BTreeMap<String, Bits> {
+    let analysis = KnownBits::new(overflow);
+    let cfg = Cfg::from_function(&lower(program));
+    let solution = solve(&analysis, &cfg);
+
+    // Programs have no control flow, so the facts at the end of the entry block hold every variable
+    let Some(facts) = &solution.end[0].0 else {
+        return BTreeMap::new();
+    };
+    facts
+        .iter()
+        .filter_map(|(variable, bits)| match variable {
+            Variable::Named(name) => Some((name.clone(), *bits)),
+            _ => None,
+        })
+        .collect()
+}
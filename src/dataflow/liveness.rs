@@ -0,0 +1,49 @@
+use std::collections::BTreeSet;
+use crate::dataflow::framework::*;
+use crate::ir::cfg::{BlockId, Cfg, Terminator};
+use crate::ir::instruction::{Instruction, Operand, Variable};
+
+// Liveness as a backward analysis
+// 	- A variable is live at a point if its value may be read later
+// 	- The outputs of the program are live at its returns
+// 	- An instruction kills its destination and makes its operands live
+pub struct Liveness {
+    outputs: BTreeSet<Variable>,
+}
+
+impl Liveness {
+    pub fn new(outputs: BTreeSet<Variable>) -> Self {
+        Liveness { outputs }
+    }
+}
+
+impl Analysis for Liveness {
+    type Fact = BTreeSet<Variable>;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn boundary(&self, _cfg: &Cfg) -> BTreeSet<Variable> {
+        self.outputs.clone()
+    }
+
+    fn initial(&self, _cfg: &Cfg) -> BTreeSet<Variable> {
+        BTreeSet::new()
+    }
+
+    fn transfer(&self, _location: Location, instruction: &Instruction, live: &mut BTreeSet<Variable>) {
+        live.remove(instruction.destination());
+        for operand in instruction.operands() {
+            if let Operand::Variable(variable) = operand {
+                live.insert(variable.clone());
+            }
+        }
+    }
+
+    fn transfer_terminator(&self, _block: BlockId, terminator: &Terminator, live: &mut BTreeSet<Variable>) {
+        if let Terminator::Branch { condition: Operand::Variable(variable), .. } = terminator {
+            live.insert(variable.clone());
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod framework;
+pub mod constants;
+pub mod liveness;
+pub mod reaching;
+pub mod available;
+pub mod intervals;
+pub mod known_bits;
@@ -0,0 +1,70 @@
+use std::collections::{BTreeSet, HashMap};
+use crate::dataflow::framework::*;
+use crate::ir::cfg::Cfg;
+use crate::ir::instruction::{Instruction, Variable};
+
+// Reaching definitions as a forward analysis
+// 	- A definition reaches a point if some path from it to the point does not redefine its variable
+// 	- The inputs are defined at the entry
+// 	- An instruction kills every other definition of its destination
+pub struct ReachingDefinitions {
+    // Every definition of every variable
+    definitions: HashMap<Variable, BTreeSet<Definition>>,
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Clone)]
+pub enum Definition {
+    Input(String),
+    Instruction(Location),
+}
+
+impl ReachingDefinitions {
+    pub fn new(cfg: &Cfg) -> Self {
+        let mut definitions: HashMap<Variable, BTreeSet<Definition>> = HashMap::new();
+        for input in &cfg.inputs {
+            definitions
+                .entry(Variable::Named(input.clone()))
+                .or_default()
+                .insert(Definition::Input(input.clone()));
+        }
+        for (block, contents) in cfg.blocks.iter().enumerate() {
+            for (index, instruction) in contents.instructions.iter().enumerate() {
+                definitions
+                    .entry(instruction.destination().clone())
+                    .or_default()
+                    .insert(Definition::Instruction(Location { block, index }));
+            }
+        }
+        ReachingDefinitions { definitions }
+    }
+
+    // Whether some definition of a variable reaches a point
+    pub fn is_defined(&self, reaching: &BTreeSet<Definition>, variable: &Variable) -> bool {
+        self.definitions
+            .get(variable)
+            .is_some_and(|definitions| definitions.iter().any(|definition| reaching.contains(definition)))
+    }
+}
+
+impl Analysis for ReachingDefinitions {
+    type Fact = BTreeSet<Definition>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn boundary(&self, cfg: &Cfg) -> BTreeSet<Definition> {
+        cfg.inputs.iter().map(|input| Definition::Input(input.clone())).collect()
+    }
+
+    fn initial(&self, _cfg: &Cfg) -> BTreeSet<Definition> {
+        BTreeSet::new()
+    }
+
+    fn transfer(&self, location: Location, instruction: &Instruction, reaching: &mut BTreeSet<Definition>) {
+        if let Some(killed) = self.definitions.get(instruction.destination()) {
+            reaching.retain(|definition| !killed.contains(definition));
+        }
+        reaching.insert(Definition::Instruction(location));
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::ast::*;
+use crate::arithmetic::*;
+
+// A tree-walking interpreter over the AST
+// 	- Inputs are bound to the given arguments, then statements are executed in order
+// 	- Operands are evaluated left to right, and method receivers before their argument, like in Rust
+// 	- Operators follow the overflow mode at runtime: error and panic both panic like a debug build
+// 	- Execution stops at the first panic, which is reported with the binding it happened in
+pub struct Interpreter {
+    overflow: OverflowMode,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum RuntimeError {
+    // An input without an argument
+    MissingInput(String),
+    // An argument that is not an input
+    UnknownInput(String),
+    // An operation panicked while initializing a binding
+    Panic { panic: Panic, variable: String },
+}
+
+impl RuntimeError {
+    pub fn message(&self) -> String {
+        match self {
+            RuntimeError::MissingInput(name) => format!("Missing a value for the input '{}', pass it with --arg {}=<value>.", name, name),
+            RuntimeError::UnknownInput(name) => format!("There is no input named '{}'.", name),
+            RuntimeError::Panic { panic, variable } => {
+                format!("thread 'main' panicked in the initializer of '{}':\n{}", variable, panic.message())
+            }
+        }
+    }
+}
+
+impl Interpreter {
+    pub fn new(overflow: OverflowMode) -> Self {
+        Interpreter { overflow }
+    }
+
+    // Runs a program on its arguments. Returns the value of every binding, in program order.
+    pub fn run(&self, program: &Program, arguments: &BTreeMap<String, u8>) -> Result<Vec<(String, u8)>, RuntimeError> {
+        if let Some(name) = arguments.keys().find(|name| !program.inputs.iter().any(|input| input.name == **name)) {
+            return Err(RuntimeError::UnknownInput(name.clone()));
+        }
+
+        let mut environment = HashMap::new();
+        for input in &program.inputs {
+            let val = arguments
+                .get(&input.name)
+                .ok_or_else(|| RuntimeError::MissingInput(input.name.clone()))?;
+            environment.insert(input.name.clone(), *val);
+        }
+
+        let mut bindings = Vec::new();
+        for statement in &program.statements {
+            match statement {
+                Statement::Assign { variable, expression } => {
+                    let val = self.expression(&environment, expression).map_err(|panic| RuntimeError::Panic {
+                        panic,
+                        variable: variable.clone(),
+                    })?;
+                    environment.insert(variable.clone(), val);
+                    bindings.push((variable.clone(), val));
+                }
+            }
+        }
+        Ok(bindings)
+    }
+
+    fn expression(&self, environment: &HashMap<String, u8>, expression: &Expression) -> Result<u8, Panic> {
+        match expression {
+            Expression::Binary { left, operator, right } => {
+                let left = self.value(environment, left)?;
+                let right = self.expression(environment, right)?;
+                evaluate_operator(self.overflow, operator, left, right)
+            }
+            Expression::Value(value) => self.value(environment, value),
+        }
+    }
+
+    fn value(&self, environment: &HashMap<String, u8>, value: &Value) -> Result<u8, Panic> {
+        match value {
+            Value::Integer(val) => Ok(*val),
+            Value::Identifier(name) => Ok(environment[name]),
+            Value::Expression(expression) => self.expression(environment, expression),
+            Value::MethodCall { receiver, method, argument } => {
+                let receiver = self.value(environment, receiver)?;
+                let argument = self.expression(environment, argument)?;
+                evaluate_method(method, receiver, argument)
+            }
+        }
+    }
+}
+
+// Parses the argument of an input, e.g a=3 or a=3u8
+pub fn parse_argument(text: &str) -> Result<(String, u8), String> {
+    let invalid = || format!("Invalid argument '{}', expected e.g a=3.", text);
+
+    let (name, val) = text.split_once('=').ok_or_else(invalid)?;
+    let val = val.trim();
+    let val = val.strip_suffix("u8").unwrap_or(val);
+    if name.trim().is_empty() {
+        return Err(invalid());
+    }
+    let val: u8 = match val.parse::<u32>() {
+        Ok(val) => u8::try_from(val).map_err(|_| format!("The argument '{}' is out of the range of u8.", text))?,
+        Err(_) => return Err(invalid()),
+    };
+    Ok((name.trim().to_string(), val))
+}
@@ -0,0 +1,188 @@
+// A control-flow graph of basic blocks over three-address code
+// 	- A basic block is a sequence of phi nodes, then instructions, then one terminator
+// 	- The entry block is always the first one
+// 	- The language has no control flow yet, so a lowered program is a single block that
+// 	  returns, but the graph and the analyses over it handle jumps and branches
+use std::collections::HashSet;
+use crate::ir::instruction::{Function, Instruction, Operand, Variable};
+
+pub type BlockId = usize;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Cfg {
+    pub name: String,
+    pub inputs: Vec<String>,
+    pub blocks: Vec<BasicBlock>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct BasicBlock {
+    pub phis: Vec<Phi>,
+    pub instructions: Vec<Instruction>,
+    pub terminator: Terminator,
+}
+
+// a = phi [b0: x], [b1: 1]
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Phi {
+    pub destination: Variable,
+    // The value coming from each predecessor
+    pub arguments: Vec<(BlockId, Operand)>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub enum Terminator {
+    // The end of the program
+    #[default]
+    Return,
+    // jump b1
+    Jump(BlockId),
+    // branch x, b1, b2: goes to b1 if x is not zero, to b2 otherwise
+    Branch {
+        condition: Operand,
+        then: BlockId,
+        otherwise: BlockId,
+    },
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Return => vec![],
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch { then, otherwise, .. } if then == otherwise => vec![*then],
+            Terminator::Branch { then, otherwise, .. } => vec![*then, *otherwise],
+        }
+    }
+}
+
+impl Cfg {
+    // A straight-line function is a single block
+    pub fn from_function(function: &Function) -> Self {
+        Cfg {
+            name: function.name.clone(),
+            inputs: function.inputs.clone(),
+            blocks: vec![BasicBlock {
+                phis: Vec::new(),
+                instructions: function.instructions.clone(),
+                terminator: Terminator::Return,
+            }],
+        }
+    }
+
+    // Flattens the graph back into a straight-line function.
+    // Only possible if the reachable blocks form a chain of jumps without phi nodes.
+    pub fn to_function(&self) -> Option<Function> {
+        let mut instructions = Vec::new();
+        let mut visited = HashSet::new();
+        let mut block = 0;
+
+        loop {
+            if !visited.insert(block) || !self.blocks[block].phis.is_empty() {
+                return None;
+            }
+            instructions.extend(self.blocks[block].instructions.iter().cloned());
+
+            match self.blocks[block].terminator {
+                Terminator::Return => break,
+                Terminator::Jump(target) => block = target,
+                Terminator::Branch { .. } => return None,
+            }
+        }
+
+        Some(Function {
+            name: self.name.clone(),
+            inputs: self.inputs.clone(),
+            instructions,
+        })
+    }
+
+    pub fn successors(&self, block: BlockId) -> Vec<BlockId> {
+        self.blocks[block].terminator.successors()
+    }
+
+    // The predecessors of every block, in block order
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        for block in 0..self.blocks.len() {
+            for successor in self.successors(block) {
+                predecessors[successor].push(block);
+            }
+        }
+        predecessors
+    }
+
+    // The blocks reachable from the entry, in reverse postorder
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut postorder = Vec::new();
+        let mut visited = vec![false; self.blocks.len()];
+        // Depth-first search with an explicit stack of (block, next successor to visit)
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+
+        while let Some((block, next)) = stack.pop() {
+            let successors = self.successors(block);
+            if next < successors.len() {
+                stack.push((block, next + 1));
+                let successor = successors[next];
+                if !visited[successor] {
+                    visited[successor] = true;
+                    stack.push((successor, 0));
+                }
+            } else {
+                postorder.push(block);
+            }
+        }
+
+        postorder.reverse();
+        postorder
+    }
+}
+
+impl std::fmt::Display for Cfg {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| format!("{}: u8", input))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        writeln!(f, "fn {}({}) {{", self.name, inputs)?;
+        for (id, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "b{}:", id)?;
+            for phi in &block.phis {
+                writeln!(f, "    {}", phi)?;
+            }
+            for instruction in &block.instructions {
+                writeln!(f, "    {}", instruction)?;
+            }
+            writeln!(f, "    {}", block.terminator)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl std::fmt::Display for Phi {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let arguments = self
+            .arguments
+            .iter()
+            .map(|(block, operand)| format!("[b{}: {}]", block, operand))
+            .collect::<Vec<String>>()
+            .join(", ");
+        write!(f, "{} = phi {}", self.destination, arguments)
+    }
+}
+
+impl std::fmt::Display for Terminator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Terminator::Return => write!(f, "return"),
+            Terminator::Jump(target) => write!(f, "jump b{}", target),
+            Terminator::Branch { condition, then, otherwise } => {
+                write!(f, "branch {}, b{}, b{}", condition, then, otherwise)
+            }
+        }
+    }
+}
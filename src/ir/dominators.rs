@@ -0,0 +1,146 @@
+// The dominator tree and dominance frontiers of a control-flow graph
+// 	- A block a dominates a block b if every path from the entry to b goes through a
+// 	- Immediate dominators are computed with the iterative algorithm of Cooper, Harvey and Kennedy:
+// 	  blocks are visited in reverse postorder, intersecting the dominators of their predecessors
+// 	  until nothing changes
+// 	- The dominance frontier of a block a is the set of blocks b such that a dominates
+// 	  a predecessor of b but does not strictly dominate b, i.e where the dominance of a ends
+// 	- Unreachable blocks have no dominators and are in no frontier
+use std::collections::BTreeSet;
+use crate::ir::cfg::{BlockId, Cfg};
+
+pub struct Dominators {
+    // The immediate dominator of every reachable block. The entry is its own immediate dominator.
+    immediate: Vec<Option<BlockId>>,
+    // The position of every reachable block in reverse postorder
+    order: Vec<Option<usize>>,
+    frontiers: Vec<BTreeSet<BlockId>>,
+}
+
+impl Dominators {
+    pub fn new(cfg: &Cfg) -> Self {
+        let reverse_postorder = cfg.reverse_postorder();
+        let predecessors = cfg.predecessors();
+
+        let mut order = vec![None; cfg.blocks.len()];
+        for (position, block) in reverse_postorder.iter().enumerate() {
+            order[*block] = Some(position);
+        }
+
+        let mut immediate = vec![None; cfg.blocks.len()];
+        immediate[0] = Some(0);
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in reverse_postorder.iter().skip(1) {
+                let new_immediate = predecessors[*block]
+                    .iter()
+                    .filter(|predecessor| immediate[**predecessor].is_some())
+                    .copied()
+                    .reduce(|first, second| intersect(&immediate, &order, first, second));
+
+                if new_immediate.is_some() && immediate[*block] != new_immediate {
+                    immediate[*block] = new_immediate;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut frontiers = vec![BTreeSet::new(); cfg.blocks.len()];
+        for block in &reverse_postorder {
+            let reachable: Vec<BlockId> = predecessors[*block]
+                .iter()
+                .filter(|predecessor| order[**predecessor].is_some())
+                .copied()
+                .collect();
+            if reachable.len() < 2 {
+                continue;
+            }
+
+            // Walk up from every predecessor until the immediate dominator of the join
+            for predecessor in reachable {
+                let mut runner = predecessor;
+                while Some(runner) != immediate[*block] {
+                    frontiers[runner].insert(*block);
+                    runner = immediate[runner].unwrap();
+                }
+            }
+        }
+
+        Dominators {
+            immediate,
+            order,
+            frontiers,
+        }
+    }
+
+    // The immediate dominator of a block, None for the entry and for unreachable blocks
+    pub fn immediate_dominator(&self, block: BlockId) -> Option<BlockId> {
+        match self.immediate[block] {
+            Some(dominator) if dominator != block => Some(dominator),
+            _ => None,
+        }
+    }
+
+    pub fn is_reachable(&self, block: BlockId) -> bool {
+        self.order[block].is_some()
+    }
+
+    // Whether `dominator` dominates `block`. Every reachable block dominates itself.
+    pub fn dominates(&self, dominator: BlockId, block: BlockId) -> bool {
+        if !self.is_reachable(dominator) || !self.is_reachable(block) {
+            return false;
+        }
+
+        let mut runner = block;
+        loop {
+            if runner == dominator {
+                return true;
+            }
+            match self.immediate_dominator(runner) {
+                Some(parent) => runner = parent,
+                None => return false,
+            }
+        }
+    }
+
+    // The blocks immediately dominated by a block, i.e its children in the dominator tree
+    pub fn children(&self, block: BlockId) -> Vec<BlockId> {
+        (0..self.immediate.len())
+            .filter(|child| self.immediate_dominator(*child) == Some(block))
+            .collect()
+    }
+
+    pub fn frontier(&self, block: BlockId) -> &BTreeSet<BlockId> {
+        &self.frontiers[block]
+    }
+
+    // The iterated dominance frontier of a set of blocks: where their definitions meet
+    pub fn iterated_frontier(&self, blocks: &BTreeSet<BlockId>) -> BTreeSet<BlockId> {
+        let mut result = BTreeSet::new();
+        let mut worklist: Vec<BlockId> = blocks.iter().copied().collect();
+
+        while let Some(block) = worklist.pop() {
+            for frontier in &self.frontiers[block] {
+                if result.insert(*frontier) {
+                    worklist.push(*frontier);
+                }
+            }
+        }
+        result
+    }
+}
+
+// The closest common dominator of two blocks, walking up the tree by reverse postorder position
+fn intersect(immediate: &[Option<BlockId>], order: &[Option<usize>], mut first: BlockId, mut second: BlockId) -> BlockId {
+    while first != second {
+        while order[first] > order[second] {
+            first = immediate[first].unwrap();
+        }
+        while order[second] > order[first] {
+            second = immediate[second].unwrap();
+        }
+    }
+    first
+}
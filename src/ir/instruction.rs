@@ -0,0 +1,171 @@
+// A three-address intermediate representation of a program
+// 	- Every instruction performs at most one operation and names its result
+// 	- Operands are constants or variables, never nested expressions
+// 	- Temporaries hold the intermediate results of expressions, e.g
+//
+// 	  let a = (x + 1u8) * y;
+//
+// 	  becomes
+//
+// 	  %0 = add x, 1
+// 	  a = mul %0, y
+use crate::ast::{MethodKind, Operator};
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub inputs: Vec<String>,
+    pub instructions: Vec<Instruction>,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, PartialOrd, Ord)]
+pub enum Variable {
+    // A variable of the source program: an input or a let binding
+    Named(String),
+    // %0
+    Temporary(usize),
+    // A definition of a variable in SSA form, e.g a.1
+    Versioned {
+        variable: Box<Variable>,
+        version: usize,
+    },
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, PartialOrd, Ord)]
+pub enum Operand {
+    Constant(u8),
+    Variable(Variable),
+}
+
+// How an operation behaves when its result does not fit in a u8
+#[derive(PartialEq, Eq, Hash, Debug, Clone, PartialOrd, Ord)]
+pub enum Arithmetic {
+    // The operators of the language, which panic
+    Panicking,
+    // An integer method, e.g wrapping_add
+    Method(MethodKind),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Instruction {
+    // a = x
+    Copy {
+        destination: Variable,
+        source: Operand,
+    },
+    // a = add x, 1
+    Binary {
+        destination: Variable,
+        operator: Operator,
+        arithmetic: Arithmetic,
+        left: Operand,
+        right: Operand,
+    },
+}
+
+impl Instruction {
+    pub fn destination(&self) -> &Variable {
+        match self {
+            Instruction::Copy { destination, .. } | Instruction::Binary { destination, .. } => destination,
+        }
+    }
+
+    pub fn destination_mut(&mut self) -> &mut Variable {
+        match self {
+            Instruction::Copy { destination, .. } | Instruction::Binary { destination, .. } => destination,
+        }
+    }
+
+    // The operands an instruction reads, in evaluation order
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Instruction::Copy { source, .. } => vec![source],
+            Instruction::Binary { left, right, .. } => vec![left, right],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Instruction::Copy { source, .. } => vec![source],
+            Instruction::Binary { left, right, .. } => vec![left, right],
+        }
+    }
+}
+
+// The mnemonic of an operator, e.g add
+pub fn mnemonic(operator: &Operator) -> &'static str {
+    match operator {
+        Operator::Add => "add",
+        Operator::Subtract => "sub",
+        Operator::Multiply => "mul",
+        Operator::Divide => "div",
+        Operator::ShiftLeft => "shl",
+        Operator::ShiftRight => "shr",
+        Operator::BitAnd => "and",
+        Operator::BitOr => "or",
+        Operator::BitXor => "xor",
+    }
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| format!("{}: u8", input))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        let instructions = self
+            .instructions
+            .iter()
+            .map(|instruction| format!("    {}\n", instruction))
+            .collect::<Vec<String>>()
+            .join("");
+
+        write!(f, "fn {}({}) {{\n{}}}", self.name, inputs, instructions)
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Instruction::Copy { destination, source } => write!(f, "{} = {}", destination, source),
+            Instruction::Binary { destination, operator, arithmetic, left, right } => {
+                write!(f, "{} = {}{} {}, {}", destination, mnemonic(operator), arithmetic, left, right)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Arithmetic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Arithmetic::Panicking => Ok(()),
+            Arithmetic::Method(MethodKind::Wrapping) => write!(f, ".wrapping"),
+            Arithmetic::Method(MethodKind::Saturating) => write!(f, ".saturating"),
+            Arithmetic::Method(MethodKind::Checked) => write!(f, ".checked"),
+            Arithmetic::Method(MethodKind::Overflowing) => write!(f, ".overflowing"),
+            Arithmetic::Method(MethodKind::OverflowFlag) => write!(f, ".overflowed"),
+        }
+    }
+}
+
+impl std::fmt::Display for Variable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Variable::Named(name) => write!(f, "{}", name),
+            Variable::Temporary(index) => write!(f, "%{}", index),
+            Variable::Versioned { variable, version } => write!(f, "{}.{}", variable, version),
+        }
+    }
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Operand::Constant(val) => write!(f, "{}", val),
+            Operand::Variable(variable) => write!(f, "{}", variable),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::{HashMap, HashSet};
+use crate::ast::*;
+use crate::ir::instruction::{Arithmetic, Function, Instruction, Operand, Variable};
+
+// Lifts three-address code back to a program
+// 	- Instructions that store in a named variable become let bindings
+// 	- Temporaries that are used exactly once are inlined into their use, as long as this
+// 	  keeps the evaluation order, i.e their uses come in the order of their definitions:
+// 		- pending temporaries are kept on a stack, and an instruction may only inline
+// 		  the ones on top of the stack, in the order of its operands
+// 		- otherwise, and whenever a let binding is emitted, the pending temporaries
+// 		  are bound to fresh variables first
+// 	- Any other temporary is bound to a fresh variable
+pub fn lift(function: &Function) -> Program {
+    let mut uses: HashMap<Variable, usize> = HashMap::new();
+    let mut names: HashSet<String> = function.inputs.iter().cloned().collect();
+    for instruction in &function.instructions {
+        if let Variable::Named(name) = instruction.destination() {
+            names.insert(name.clone());
+        }
+        for operand in instruction.operands() {
+            if let Operand::Variable(variable) = operand {
+                *uses.entry(variable.clone()).or_default() += 1;
+            }
+        }
+    }
+
+    let mut lifting = Lifting {
+        statements: Vec::new(),
+        pending: Vec::new(),
+        bound: HashMap::new(),
+        names,
+    };
+
+    for instruction in &function.instructions {
+        // The pending temporaries this instruction reads, in the order it reads them
+        let pending_operands: Vec<Variable> = instruction
+            .operands()
+            .into_iter()
+            .filter_map(|operand| match operand {
+                Operand::Variable(variable) if lifting.is_pending(variable) => Some(variable.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let count = pending_operands.len();
+        let on_top = lifting.pending.len() >= count
+            && lifting.pending[lifting.pending.len() - count..]
+                .iter()
+                .map(|(variable, _)| variable)
+                .eq(pending_operands.iter());
+
+        let mut inlined = if on_top {
+            lifting.pending.split_off(lifting.pending.len() - count)
+        } else {
+            lifting.flush();
+            Vec::new()
+        };
+
+        let expression = match instruction {
+            Instruction::Copy { source, .. } => lifting.operand(source, &mut inlined),
+            Instruction::Binary { operator, arithmetic, left, right, .. } => {
+                let left = lifting.operand(left, &mut inlined);
+                let right = lifting.operand(right, &mut inlined);
+                operation(operator, arithmetic, left, right)
+            }
+        };
+
+        match instruction.destination() {
+            // SSA versions are temporaries as well, the language has no reassignment
+            Variable::Temporary(_) | Variable::Versioned { .. } if uses.get(instruction.destination()) == Some(&1) => {
+                lifting.pending.push((instruction.destination().clone(), expression));
+            }
+            Variable::Temporary(_) | Variable::Versioned { .. } => {
+                lifting.flush();
+                lifting.bind(instruction.destination().clone(), expression);
+            }
+            Variable::Named(name) => {
+                lifting.flush();
+                lifting.statements.push(Statement::Assign {
+                    variable: name.clone(),
+                    expression,
+                });
+            }
+        }
+    }
+    lifting.flush();
+
+    Program {
+        name: function.name.clone(),
+        inputs: function
+            .inputs
+            .iter()
+            .map(|name| Input {
+                name: name.clone(),
+                input_type: Type::U8,
+            })
+            .collect(),
+        statements: lifting.statements,
+    }
+}
+
+struct Lifting {
+    statements: Vec<Statement>,
+    // Temporaries waiting to be inlined, with their expressions, in definition order
+    pending: Vec<(Variable, Expression)>,
+    // The variables that temporaries were bound to
+    bound: HashMap<Variable, String>,
+    // Every name that is in use, to pick fresh variables
+    names: HashSet<String>,
+}
+
+impl Lifting {
+    fn is_pending(&self, variable: &Variable) -> bool {
+        self.pending.iter().any(|(pending, _)| pending == variable)
+    }
+
+    // Binds every pending temporary to a fresh variable, in definition order
+    fn flush(&mut self) {
+        for (variable, expression) in std::mem::take(&mut self.pending) {
+            self.bind(variable, expression);
+        }
+    }
+
+    fn bind(&mut self, variable: Variable, expression: Expression) {
+        let name = fresh_name(&mut self.names);
+        self.bound.insert(variable, name.clone());
+        self.statements.push(Statement::Assign {
+            variable: name,
+            expression,
+        });
+    }
+
+    // The expression of an operand, taking inlined temporaries from the front of `inlined`
+    fn operand(&self, operand: &Operand, inlined: &mut Vec<(Variable, Expression)>) -> Expression {
+        match operand {
+            Operand::Constant(val) => Expression::Value(Box::new(Value::Integer(*val))),
+            Operand::Variable(variable) => {
+                if inlined.first().is_some_and(|(first, _)| first == variable) {
+                    return inlined.remove(0).1;
+                }
+                let name = match variable {
+                    Variable::Named(name) => name.clone(),
+                    _ => self.bound[variable].clone(),
+                };
+                Expression::Value(Box::new(Value::Identifier(name)))
+            }
+        }
+    }
+}
+
+// The expression of an operation on the expressions of its operands
+pub fn operation(operator: &Operator, arithmetic: &Arithmetic, left: Expression, right: Expression) -> Expression {
+    let left = left.parenthesize();
+    match arithmetic {
+        Arithmetic::Panicking => {
+            // A right operand that binds as loosely as the operator needs parenthesis
+            let right = match right {
+                Expression::Binary { operator: ref right_operator, .. } if right_operator.precedence() <= operator.precedence() => {
+                    Expression::Value(Box::new(Value::Expression(Box::new(right))))
+                }
+                right => right,
+            };
+            Expression::Binary {
+                left,
+                operator: operator.clone(),
+                right: Box::new(right),
+            }
+        }
+        Arithmetic::Method(kind) => Expression::Value(Box::new(Value::MethodCall {
+            receiver: Box::new(left),
+            method: Method {
+                kind: kind.clone(),
+                operator: operator.clone(),
+            },
+            argument: Box::new(right),
+        })),
+    }
+}
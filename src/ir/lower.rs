@@ -0,0 +1,110 @@
+use std::ops::Range;
+use crate::ast::*;
+use crate::ir::instruction::{Arithmetic, Function, Instruction, Operand, Variable};
+
+// Lowers a program to three-address code
+// 	- Expressions are flattened in evaluation order: the left operand, then the right one,
+// 	  so the instructions panic in the same order as the expressions did
+// 	- Every operation gets a fresh temporary, except the last one of an initializer,
+// 	  which is stored in the bound variable directly
+// 	- Parenthesis disappear, they only shape the expression tree
+pub fn lower(program: &Program) -> Function {
+    let mut lowering = Lowering {
+        instructions: Vec::new(),
+        temporaries: 0,
+    };
+
+    for statement in &program.statements {
+        lowering.lower_statement(statement);
+    }
+
+    Function {
+        name: program.name.clone(),
+        inputs: program.inputs.iter().map(|input| input.name.clone()).collect(),
+        instructions: lowering.instructions,
+    }
+}
+
+// Lowers a single statement, with temporaries numbered from 0
+pub fn lower_statement(statement: &Statement) -> Vec<Instruction> {
+    let mut lowering = Lowering {
+        instructions: Vec::new(),
+        temporaries: 0,
+    };
+    lowering.lower_statement(statement);
+    lowering.instructions
+}
+
+// The instructions that every statement of a program was lowered to, in order.
+// An initializer ends with an assignment to its binding, and only with it.
+pub fn statement_ranges(function: &Function) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for (index, instruction) in function.instructions.iter().enumerate() {
+        if let Variable::Named(_) = instruction.destination() {
+            ranges.push(start..index + 1);
+            start = index + 1;
+        }
+    }
+    ranges
+}
+
+struct Lowering {
+    instructions: Vec<Instruction>,
+    // The number of temporaries in use
+    temporaries: usize,
+}
+
+impl Lowering {
+    fn lower_statement(&mut self, statement: &Statement) {
+        let Statement::Assign { variable, expression } = statement;
+        let result = self.lower_expression(expression);
+        let destination = Variable::Named(variable.clone());
+
+        match (result, self.instructions.last_mut()) {
+            (Operand::Variable(Variable::Temporary(index)), Some(last)) if *last.destination() == Variable::Temporary(index) => {
+                *last.destination_mut() = destination;
+                self.temporaries -= 1;
+            }
+            (source, _) => self.instructions.push(Instruction::Copy { destination, source }),
+        }
+    }
+
+    fn lower_expression(&mut self, expression: &Expression) -> Operand {
+        match expression {
+            Expression::Binary { left, operator, right } => {
+                let left = self.lower_value(left);
+                let right = self.lower_expression(right);
+                self.emit(operator.clone(), Arithmetic::Panicking, left, right)
+            }
+            Expression::Value(value) => self.lower_value(value),
+        }
+    }
+
+    fn lower_value(&mut self, value: &Value) -> Operand {
+        match value {
+            Value::Integer(val) => Operand::Constant(*val),
+            Value::Identifier(name) => Operand::Variable(Variable::Named(name.clone())),
+            Value::Expression(expression) => self.lower_expression(expression),
+            Value::MethodCall { receiver, method, argument } => {
+                let left = self.lower_value(receiver);
+                let right = self.lower_expression(argument);
+                self.emit(method.operator.clone(), Arithmetic::Method(method.kind.clone()), left, right)
+            }
+        }
+    }
+
+    fn emit(&mut self, operator: Operator, arithmetic: Arithmetic, left: Operand, right: Operand) -> Operand {
+        let destination = Variable::Temporary(self.temporaries);
+        self.temporaries += 1;
+
+        self.instructions.push(Instruction::Binary {
+            destination: destination.clone(),
+            operator,
+            arithmetic,
+            left,
+            right,
+        });
+        Operand::Variable(destination)
+    }
+}
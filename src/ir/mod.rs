@@ -0,0 +1,7 @@
+pub mod instruction;
+pub mod lower;
+pub mod lift;
+pub mod cfg;
+pub mod dominators;
+pub mod ssa;
+pub mod sccp;
@@ -0,0 +1,303 @@
+// Sparse conditional constant propagation (Wegman and Zadeck) over a graph in SSA form
+// 	- Every variable has a value in a lattice: undefined (no value seen yet), a constant,
+// 	  or overdefined (more than one value, or unknown like the inputs)
+// 	- Only blocks that are reached by an executable edge are evaluated, starting at the entry:
+// 		- a branch on a constant only makes one of its edges executable
+// 		- a phi node only merges the values coming from executable edges
+// 	- Values only ever go down the lattice, so evaluation reaches a fixpoint
+// 	- Operations are evaluated with the semantics of the overflow mode. An operation that panics
+// 	  on its constant operands is overdefined, and is reported once the fixpoint is reached.
+// 	- The rewrite replaces constant variables with their value, turns constant operations
+// 	  into copies and branches on constants into jumps, and empties unreachable blocks
+use std::collections::{HashMap, HashSet};
+use crate::ast::Method;
+use crate::arithmetic::*;
+use crate::ir::cfg::{BlockId, Cfg, Terminator};
+use crate::ir::instruction::{Arithmetic, Instruction, Operand, Variable};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Lattice {
+    Undefined,
+    Constant(u8),
+    Overdefined,
+}
+
+impl Lattice {
+    pub fn meet(self, other: Lattice) -> Lattice {
+        match (self, other) {
+            (Lattice::Undefined, value) | (value, Lattice::Undefined) => value,
+            (Lattice::Constant(first), Lattice::Constant(second)) if first == second => self,
+            _ => Lattice::Overdefined,
+        }
+    }
+}
+
+// An operation that panics whenever it is executed
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ConstantPanic {
+    pub block: BlockId,
+    pub instruction: Instruction,
+    pub panic: Panic,
+    // The operation on constants, e.g 200 + 100
+    pub description: String,
+}
+
+// Where a variable is used
+#[derive(Clone, Copy)]
+enum Site {
+    Phi(BlockId, usize),
+    Instruction(BlockId, usize),
+    Terminator(BlockId),
+}
+
+pub struct Sccp {
+    overflow: OverflowMode,
+    values: HashMap<Variable, Lattice>,
+    executable_blocks: Vec<bool>,
+    executable_edges: HashSet<(BlockId, BlockId)>,
+}
+
+impl Sccp {
+    pub fn analyze(cfg: &Cfg, overflow: OverflowMode) -> Self {
+        let mut sccp = Sccp {
+            overflow,
+            values: HashMap::new(),
+            executable_blocks: vec![false; cfg.blocks.len()],
+            executable_edges: HashSet::new(),
+        };
+
+        // Every variable that is defined starts undefined, anything else (the inputs) is unknown
+        for block in &cfg.blocks {
+            for phi in &block.phis {
+                sccp.values.insert(phi.destination.clone(), Lattice::Undefined);
+            }
+            for instruction in &block.instructions {
+                sccp.values.insert(instruction.destination().clone(), Lattice::Undefined);
+            }
+        }
+
+        let mut uses: HashMap<Variable, Vec<Site>> = HashMap::new();
+        for (id, block) in cfg.blocks.iter().enumerate() {
+            for (index, phi) in block.phis.iter().enumerate() {
+                for (_, operand) in &phi.arguments {
+                    if let Operand::Variable(variable) = operand {
+                        uses.entry(variable.clone()).or_default().push(Site::Phi(id, index));
+                    }
+                }
+            }
+            for (index, instruction) in block.instructions.iter().enumerate() {
+                for operand in instruction.operands() {
+                    if let Operand::Variable(variable) = operand {
+                        uses.entry(variable.clone()).or_default().push(Site::Instruction(id, index));
+                    }
+                }
+            }
+            if let Terminator::Branch { condition: Operand::Variable(variable), .. } = &block.terminator {
+                uses.entry(variable.clone()).or_default().push(Site::Terminator(id));
+            }
+        }
+
+        let mut block_worklist: Vec<BlockId> = vec![0];
+        let mut variable_worklist: Vec<Variable> = Vec::new();
+        sccp.executable_blocks[0] = true;
+
+        while !block_worklist.is_empty() || !variable_worklist.is_empty() {
+            if let Some(block) = block_worklist.pop() {
+                let sites = (0..cfg.blocks[block].phis.len())
+                    .map(|index| Site::Phi(block, index))
+                    .chain((0..cfg.blocks[block].instructions.len()).map(|index| Site::Instruction(block, index)))
+                    .chain(std::iter::once(Site::Terminator(block)));
+                for site in sites.collect::<Vec<Site>>() {
+                    sccp.visit(cfg, site, &mut block_worklist, &mut variable_worklist);
+                }
+            } else if let Some(variable) = variable_worklist.pop() {
+                for site in uses.get(&variable).cloned().unwrap_or_default() {
+                    let (Site::Phi(block, _) | Site::Instruction(block, _) | Site::Terminator(block)) = site;
+                    if sccp.executable_blocks[block] {
+                        sccp.visit(cfg, site, &mut block_worklist, &mut variable_worklist);
+                    }
+                }
+            }
+        }
+
+        sccp
+    }
+
+    pub fn value(&self, variable: &Variable) -> Lattice {
+        self.values.get(variable).copied().unwrap_or(Lattice::Overdefined)
+    }
+
+    pub fn operand(&self, operand: &Operand) -> Lattice {
+        match operand {
+            Operand::Constant(val) => Lattice::Constant(*val),
+            Operand::Variable(variable) => self.value(variable),
+        }
+    }
+
+    pub fn is_executable(&self, block: BlockId) -> bool {
+        self.executable_blocks[block]
+    }
+
+    // The executed operations that panic on their constant operands
+    pub fn panics(&self, cfg: &Cfg) -> Vec<ConstantPanic> {
+        let mut panics = Vec::new();
+        for (id, block) in cfg.blocks.iter().enumerate() {
+            if !self.executable_blocks[id] {
+                continue;
+            }
+            for instruction in &block.instructions {
+                let Instruction::Binary { operator, left, right, .. } = instruction else {
+                    continue;
+                };
+                if let (Lattice::Constant(left), Lattice::Constant(right)) = (self.operand(left), self.operand(right)) {
+                    if let Err(panic) = self.evaluate(instruction, left, right) {
+                        panics.push(ConstantPanic {
+                            block: id,
+                            instruction: instruction.clone(),
+                            panic,
+                            description: format!("{} {} {}", left, operator, right),
+                        });
+                    }
+                }
+            }
+        }
+        panics
+    }
+
+    pub fn rewrite(&self, cfg: &mut Cfg) {
+        let constant = |operand: &mut Operand| {
+            if let Lattice::Constant(val) = self.operand(operand) {
+                *operand = Operand::Constant(val);
+            }
+        };
+
+        for (id, block) in cfg.blocks.iter_mut().enumerate() {
+            if !self.executable_blocks[id] {
+                *block = Default::default();
+                continue;
+            }
+
+            let mut copies = Vec::new();
+            block.phis.retain_mut(|phi| {
+                if let Lattice::Constant(val) = self.value(&phi.destination) {
+                    copies.push(Instruction::Copy {
+                        destination: phi.destination.clone(),
+                        source: Operand::Constant(val),
+                    });
+                    return false;
+                }
+                phi.arguments.retain(|(predecessor, _)| self.executable_edges.contains(&(*predecessor, id)));
+                for (_, operand) in &mut phi.arguments {
+                    constant(operand);
+                }
+                true
+            });
+            block.instructions.splice(0..0, copies);
+
+            for instruction in &mut block.instructions {
+                if let Lattice::Constant(val) = self.value(instruction.destination()) {
+                    *instruction = Instruction::Copy {
+                        destination: instruction.destination().clone(),
+                        source: Operand::Constant(val),
+                    };
+                }
+                for operand in instruction.operands_mut() {
+                    constant(operand);
+                }
+            }
+
+            if let Terminator::Branch { condition, then, otherwise } = &block.terminator {
+                block.terminator = match self.operand(condition) {
+                    Lattice::Constant(0) => Terminator::Jump(*otherwise),
+                    Lattice::Constant(_) => Terminator::Jump(*then),
+                    _ => block.terminator.clone(),
+                };
+            }
+        }
+    }
+
+    fn visit(&mut self, cfg: &Cfg, site: Site, blocks: &mut Vec<BlockId>, variables: &mut Vec<Variable>) {
+        match site {
+            Site::Phi(block, index) => {
+                let phi = &cfg.blocks[block].phis[index];
+                let value = phi
+                    .arguments
+                    .iter()
+                    .filter(|(predecessor, _)| self.executable_edges.contains(&(*predecessor, block)))
+                    .fold(Lattice::Undefined, |value, (_, operand)| value.meet(self.operand(operand)));
+                self.update(&phi.destination, value, variables);
+            }
+            Site::Instruction(block, index) => {
+                let instruction = &cfg.blocks[block].instructions[index];
+                let value = match instruction {
+                    Instruction::Copy { source, .. } => self.operand(source),
+                    Instruction::Binary { left, right, .. } => match (self.operand(left), self.operand(right)) {
+                        (Lattice::Constant(left), Lattice::Constant(right)) => match self.evaluate(instruction, left, right) {
+                            Ok(result) => Lattice::Constant(result),
+                            Err(_) => Lattice::Overdefined,
+                        },
+                        (Lattice::Undefined, _) | (_, Lattice::Undefined) => Lattice::Undefined,
+                        _ => Lattice::Overdefined,
+                    },
+                };
+                self.update(instruction.destination(), value, variables);
+            }
+            Site::Terminator(block) => {
+                let successors = match &cfg.blocks[block].terminator {
+                    Terminator::Branch { condition, then, otherwise } => match self.operand(condition) {
+                        Lattice::Undefined => vec![],
+                        Lattice::Constant(0) => vec![*otherwise],
+                        Lattice::Constant(_) => vec![*then],
+                        Lattice::Overdefined => vec![*then, *otherwise],
+                    },
+                    terminator => terminator.successors(),
+                };
+
+                for successor in successors {
+                    if self.executable_edges.insert((block, successor)) {
+                        if self.executable_blocks[successor] {
+                            // A new edge into a visited block only changes its phi nodes
+                            for index in 0..cfg.blocks[successor].phis.len() {
+                                self.visit(cfg, Site::Phi(successor, index), blocks, variables);
+                            }
+                        } else {
+                            self.executable_blocks[successor] = true;
+                            blocks.push(successor);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn update(&mut self, variable: &Variable, value: Lattice, variables: &mut Vec<Variable>) {
+        let current = self.value(variable);
+        // Values only go down: the new value is met with the old one
+        let value = current.meet(value);
+        if value != current {
+            self.values.insert(variable.clone(), value);
+            variables.push(variable.clone());
+        }
+    }
+
+    fn evaluate(&self, instruction: &Instruction, left: u8, right: u8) -> Result<u8, Panic> {
+        evaluate_instruction(self.overflow, instruction, left, right)
+    }
+}
+
+// Evaluates an instruction on constant operands. The right operand of a copy is ignored.
+pub fn evaluate_instruction(overflow: OverflowMode, instruction: &Instruction, left: u8, right: u8) -> Result<u8, Panic> {
+    match instruction {
+        Instruction::Binary { operator, arithmetic: Arithmetic::Panicking, .. } => {
+            evaluate_operator(overflow, operator, left, right)
+        }
+        Instruction::Binary { operator, arithmetic: Arithmetic::Method(kind), .. } => {
+            let method = Method {
+                kind: kind.clone(),
+                operator: operator.clone(),
+            };
+            evaluate_method(&method, left, right)
+        }
+        Instruction::Copy { .. } => Ok(left),
+    }
+}
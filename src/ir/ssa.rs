@@ -0,0 +1,164 @@
+// Conversion of a control-flow graph to and from static single assignment (SSA) form
+// 	- Into SSA:
+// 		- variables with more than one definition (an input counts as a definition in the entry)
+// 		  get a phi node in the iterated dominance frontier of their defining blocks
+// 		- walking the dominator tree, every definition of such a variable gets a fresh version
+// 		  (a.1, a.2, ...) and every use is renamed to the version that reaches it
+// 		- variables with a single definition keep their name, so a program without
+// 		  reassignments is unchanged
+// 	- Out of SSA:
+// 		- every phi node becomes a copy at the end of each predecessor
+// 		- the copies are sequential, which is correct as long as no phi node reads the
+// 		  destination of another phi node of the same block. Renaming never creates such
+// 		  phi nodes, and constant propagation only replaces operands with constants.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::ir::cfg::{BlockId, Cfg, Phi, Terminator};
+use crate::ir::dominators::Dominators;
+use crate::ir::instruction::{Instruction, Operand, Variable};
+
+pub fn into_ssa(cfg: &mut Cfg) {
+    let dominators = Dominators::new(cfg);
+    let predecessors = cfg.predecessors();
+
+    // The blocks defining each variable, and how many definitions it has
+    let mut definitions: BTreeMap<Variable, (BTreeSet<BlockId>, usize)> = BTreeMap::new();
+    for input in &cfg.inputs {
+        let entry = definitions.entry(Variable::Named(input.clone())).or_default();
+        entry.0.insert(0);
+        entry.1 += 1;
+    }
+    for (id, block) in cfg.blocks.iter().enumerate() {
+        if !dominators.is_reachable(id) {
+            continue;
+        }
+        for instruction in &block.instructions {
+            let entry = definitions.entry(instruction.destination().clone()).or_default();
+            entry.0.insert(id);
+            entry.1 += 1;
+        }
+    }
+
+    let renamed: BTreeSet<Variable> = definitions
+        .iter()
+        .filter(|(_, (_, count))| *count > 1)
+        .map(|(variable, _)| variable.clone())
+        .collect();
+
+    // The variable each phi node merges, by block
+    let mut phi_variables: Vec<Vec<Variable>> = vec![Vec::new(); cfg.blocks.len()];
+    for variable in &renamed {
+        let (blocks, _) = &definitions[variable];
+        for join in dominators.iterated_frontier(blocks) {
+            cfg.blocks[join].phis.push(Phi {
+                destination: variable.clone(),
+                arguments: predecessors[join]
+                    .iter()
+                    .filter(|predecessor| dominators.is_reachable(**predecessor))
+                    .map(|predecessor| (*predecessor, Operand::Variable(variable.clone())))
+                    .collect(),
+            });
+            phi_variables[join].push(variable.clone());
+        }
+    }
+
+    let mut renaming = Renaming {
+        renamed,
+        phi_variables,
+        stacks: HashMap::new(),
+        versions: HashMap::new(),
+    };
+    for input in &cfg.inputs {
+        let input = Variable::Named(input.clone());
+        renaming.stacks.entry(input.clone()).or_default().push(input);
+    }
+    renaming.rename_block(cfg, &dominators, 0);
+}
+
+pub fn out_of_ssa(cfg: &mut Cfg) {
+    for block in 0..cfg.blocks.len() {
+        for phi in std::mem::take(&mut cfg.blocks[block].phis) {
+            for (predecessor, source) in phi.arguments {
+                cfg.blocks[predecessor].instructions.push(Instruction::Copy {
+                    destination: phi.destination.clone(),
+                    source,
+                });
+            }
+        }
+    }
+}
+
+struct Renaming {
+    // The variables that get versions
+    renamed: BTreeSet<Variable>,
+    phi_variables: Vec<Vec<Variable>>,
+    // The version of each variable that reaches the current block, innermost last
+    stacks: HashMap<Variable, Vec<Variable>>,
+    // The last version given to each variable
+    versions: HashMap<Variable, usize>,
+}
+
+impl Renaming {
+    fn define(&mut self, variable: &Variable) -> Variable {
+        let version = self.versions.entry(variable.clone()).or_default();
+        *version += 1;
+        let versioned = Variable::Versioned {
+            variable: Box::new(variable.clone()),
+            version: *version,
+        };
+        self.stacks.entry(variable.clone()).or_default().push(versioned.clone());
+        versioned
+    }
+
+    fn current(&self, operand: &mut Operand) {
+        if let Operand::Variable(variable) = operand {
+            if let Some(version) = self.stacks.get(variable).and_then(|stack| stack.last()) {
+                *variable = version.clone();
+            }
+        }
+    }
+
+    fn rename_block(&mut self, cfg: &mut Cfg, dominators: &Dominators, block: BlockId) {
+        let mut defined = Vec::new();
+
+        for (index, variable) in self.phi_variables[block].clone().into_iter().enumerate() {
+            cfg.blocks[block].phis[index].destination = self.define(&variable);
+            defined.push(variable);
+        }
+
+        for instruction in &mut cfg.blocks[block].instructions {
+            for operand in instruction.operands_mut() {
+                self.current(operand);
+            }
+            let destination = instruction.destination().clone();
+            if self.renamed.contains(&destination) {
+                *instruction.destination_mut() = self.define(&destination);
+                defined.push(destination);
+            }
+        }
+
+        if let Terminator::Branch { condition, .. } = &mut cfg.blocks[block].terminator {
+            self.current(condition);
+        }
+
+        // The arguments of the phi nodes of the successors that come from this block
+        for successor in cfg.successors(block) {
+            for (index, variable) in self.phi_variables[successor].clone().into_iter().enumerate() {
+                let phi = &mut cfg.blocks[successor].phis[index];
+                for (predecessor, operand) in &mut phi.arguments {
+                    if *predecessor == block {
+                        *operand = Operand::Variable(variable.clone());
+                        self.current(operand);
+                    }
+                }
+            }
+        }
+
+        for child in dominators.children(block) {
+            self.rename_block(cfg, dominators, child);
+        }
+
+        for variable in defined {
+            self.stacks.get_mut(&variable).unwrap().pop();
+        }
+    }
+}
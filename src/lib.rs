@@ -6,3 +6,11 @@ pub mod ast;
 pub mod parser;
 pub mod visitors;
 pub mod messages;
+pub mod arithmetic;
+pub mod passes;
+pub mod ir;
+pub mod dataflow;
+pub mod interpreter;
+pub mod bytecode;
+pub mod codegen;
+pub mod circuit;
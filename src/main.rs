@@ -1,10 +1,27 @@
 use rustic::parser::*;
+use rustic::arithmetic::{EvaluationMode, OverflowMode};
 use rustic::visitors::*;
 use rustic::visitors::visitor::NodeAccept;
+use rustic::passes::*;
+use rustic::interpreter::*;
+use rustic::ir::lower::lower;
+use rustic::bytecode::compile::compile;
+use rustic::bytecode::format::{decode, encode, is_bytecode};
+use rustic::bytecode::vm::Vm;
+use rustic::codegen::x86_64;
+use rustic::codegen::c;
+use rustic::codegen::riscv::{emit as riscv, simulator::Harness};
+use rustic::codegen::wasm::{binary as wasm, emit as wat};
+use rustic::codegen::llvm;
+use rustic::codegen::verilog;
+use rustic::circuit::{compile as circuit, netlist, witness};
+use rustic::messages::*;
 use crate::variable_checker::*;
-use crate::constprop::*;
+use crate::strength::CostModel;
+use crate::ranges::{parse_input_range, RangeAnalysis};
 
-use clap::{Arg, Command};
+use clap::{Arg, ArgMatches, Command};
+use std::collections::BTreeMap;
 use std::fs;
 
 fn main() {
@@ -13,6 +30,65 @@ fn main() {
         .version(env!("CARGO_PKG_VERSION"))
         .author("Konstantinos Fragkiadakis, fconstantinos@gmail.com")
         .about("A parser for a small subset of the Rust programming language")
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            Command::new("run")
+                .about("Execute a program and print the value of its bindings")
+                .arg(
+                    Arg::new("file")
+                        .help("The input file to run, either a program or serialized bytecode")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("vm")
+                        .long("vm")
+                        .required(false)
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Compile the program to bytecode and run it on the virtual machine")
+                )
+                .arg(
+                    Arg::new("riscv")
+                        .long("riscv")
+                        .required(false)
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("vm")
+                        .help("Compile the program to RV32IM and run it on the simulator")
+                )
+                .arg(
+                    Arg::new("r1cs")
+                        .long("r1cs")
+                        .required(false)
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["vm", "riscv"])
+                        .help("Compile the program to a constraint system and compute its witness")
+                )
+                .arg(
+                    Arg::new("arg")
+                        .long("arg")
+                        .required(false)
+                        .action(clap::ArgAction::Append)
+                        .value_name("INPUT=VALUE")
+                        .help("The value of an input, e.g a=3")
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .required(false)
+                        .action(clap::ArgAction::Append)
+                        .value_name("VARIABLE")
+                        .help("A binding to print, instead of every binding")
+                )
+                .arg(
+                    Arg::new("overflow")
+                        .long("overflow")
+                        .required(false)
+                        .value_parser(["panic", "wrap", "saturate"])
+                        .default_value("panic")
+                        .help("What u8 arithmetic does when it overflows at runtime")
+                )
+        )
         .arg(
             Arg::new("file")
                 .help("The input file to use")
@@ -26,8 +102,187 @@ fn main() {
                 .action(clap::ArgAction::SetTrue)
                 .help("Enable constant propagation optimization")
         )
+        .arg(
+            Arg::new("reassociate")
+                .long("reassociate")
+                .required(false)
+                .action(clap::ArgAction::SetTrue)
+                .help("Reassociate chains of operations to fold their constant terms")
+        )
+        .arg(
+            Arg::new("simplify")
+                .long("simplify")
+                .required(false)
+                .action(clap::ArgAction::SetTrue)
+                .help("Simplify algebraic identities, e.g x * 1u8")
+        )
+        .arg(
+            Arg::new("strength-reduce")
+                .long("strength-reduce")
+                .required(false)
+                .action(clap::ArgAction::SetTrue)
+                .help("Replace multiplications and divisions by constants with cheaper operations")
+        )
+        .arg(
+            Arg::new("cost-model")
+                .long("cost-model")
+                .required(false)
+                .value_parser(CostModel::NAMES)
+                .default_value("default")
+                .help("The relative cost of operations that strength reduction assumes")
+        )
+        .arg(
+            Arg::new("cse")
+                .long("cse")
+                .required(false)
+                .action(clap::ArgAction::SetTrue)
+                .help("Eliminate common subexpressions")
+        )
+        .arg(
+            Arg::new("copyprop")
+                .long("copyprop")
+                .required(false)
+                .action(clap::ArgAction::SetTrue)
+                .help("Replace uses of copied variables with their originals")
+        )
+        .arg(
+            Arg::new("dce")
+                .long("dce")
+                .required(false)
+                .action(clap::ArgAction::SetTrue)
+                .help("Remove let bindings whose value is never used")
+        )
+        .arg(
+            Arg::new("passes")
+                .long("passes")
+                .required(false)
+                .value_name("PIPELINE")
+                .conflicts_with_all(["opt-level", "constprop", "reassociate", "simplify", "strength-reduce", "cse", "copyprop", "dce"])
+                .help("A comma separated pipeline of passes to run, e.g constprop,dce,cse")
+        )
+        .arg(
+            Arg::new("opt-level")
+                .short('O')
+                .required(false)
+                .value_name("LEVEL")
+                .value_parser(clap::value_parser!(u8).range(0..=2))
+                .conflicts_with_all(["constprop", "reassociate", "simplify", "strength-reduce", "cse", "copyprop", "dce"])
+                .help("Run the pipeline of an optimization level: 0 runs nothing, 1 runs cheap cleanups, 2 runs every pass to a fixpoint")
+        )
+        .arg(
+            Arg::new("fixpoint")
+                .long("fixpoint")
+                .required(false)
+                .action(clap::ArgAction::SetTrue)
+                .help("Repeat the pipeline until the program stops changing")
+        )
+        .arg(
+            Arg::new("print-after")
+                .long("print-after")
+                .required(false)
+                .action(clap::ArgAction::Append)
+                .value_name("PASS")
+                .value_parser(PASSES)
+                .help("Print the program after every run of a pass")
+        )
+        .arg(
+            Arg::new("emit")
+                .long("emit")
+                .required(false)
+                .value_parser(["ast", "ir", "bytecode", "asm", "riscv", "c", "wat", "wasm", "llvm-ir", "r1cs", "verilog"])
+                .default_value("ast")
+                .help("What to print: the resulting program, its three-address code, a disassembly of its bytecode, x86-64 or RV32IM assembly, C code, a WebAssembly module as text or as a hex dump of its binary, LLVM IR, the R1CS constraints of its circuit as JSON, or a Verilog module")
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .required(false)
+                .action(clap::ArgAction::Append)
+                .value_name("VARIABLE")
+                .help("With --emit=verilog, a binding to output, instead of every binding")
+        )
+        .arg(
+            Arg::new("print-intervals")
+                .long("print-intervals")
+                .required(false)
+                .action(clap::ArgAction::SetTrue)
+                .help("With --emit=asm or --emit=riscv, print the live interval and the register of every variable")
+        )
+        .arg(
+            Arg::new("c-main")
+                .long("c-main")
+                .required(false)
+                .action(clap::ArgAction::SetTrue)
+                .help("With --emit=c, add a main function that reads the inputs from the command line and prints the bindings")
+        )
+        .arg(
+            Arg::new("write-bytecode")
+                .long("write-bytecode")
+                .required(false)
+                .value_name("FILE")
+                .help("Write the bytecode of the resulting program to a file, which the run subcommand executes")
+        )
+        .arg(
+            Arg::new("write-wasm")
+                .long("write-wasm")
+                .required(false)
+                .value_name("FILE")
+                .help("Write the resulting program to a file as a WebAssembly module")
+        )
+        .arg(
+            Arg::new("keep")
+                .long("keep")
+                .required(false)
+                .action(clap::ArgAction::Append)
+                .value_name("VARIABLE")
+                .help("A program output that dead code elimination has to keep")
+        )
+        .arg(
+            Arg::new("overflow")
+                .long("overflow")
+                .required(false)
+                .value_parser(OverflowMode::NAMES)
+                .default_value("error")
+                .help("What constant folding does when u8 arithmetic overflows")
+        )
+        .arg(
+            Arg::new("evaluation")
+                .long("evaluation")
+                .required(false)
+                .value_parser(EvaluationMode::NAMES)
+                .default_value("exact")
+                .help("Whether every operation or only whole constant expressions have to fit in u8")
+        )
+        .arg(
+            Arg::new("ranges")
+                .long("ranges")
+                .required(false)
+                .action(clap::ArgAction::SetTrue)
+                .help("Check for overflows with the ranges of values that every binding may hold")
+        )
+        .arg(
+            Arg::new("input-range")
+                .long("input-range")
+                .required(false)
+                .action(clap::ArgAction::Append)
+                .value_name("INPUT=LO..HI")
+                .requires("ranges")
+                .help("The values an input may take, e.g a=0..10 or a=0..=9")
+        )
+        .arg(
+            Arg::new("warn-inexact-division")
+                .long("warn-inexact-division")
+                .required(false)
+                .action(clap::ArgAction::SetTrue)
+                .help("Warn when constant folding truncates a division")
+        )
         .get_matches();
 
+    if let Some(("run", run_matches)) = matches.subcommand() {
+        run(run_matches);
+        return;
+    }
+
     // Get the file name from the command-line arguments
     let file_name = matches.get_one::<String>("file").expect("required argument");
 
@@ -42,12 +297,180 @@ fn main() {
     let mut variable_checker = VariableChecker::new();
     file.accept(&mut variable_checker);
 
-    // Perform constant folding only if the --constprop flag is set.
-    if matches.contains_id("constprop") && matches.get_flag("constprop") {
-        let mut constant_propagation = ConstantPropagation::new();
-        file.accept(&mut constant_propagation);
+    let overflow = matches.get_one::<String>("overflow").expect("has a default value");
+    let overflow = OverflowMode::from_name(overflow).expect("validated by clap");
+
+    // Check for overflows on the inputs, before the passes change the program.
+    if matches.get_flag("ranges") {
+        let mut range_analysis = RangeAnalysis::new(overflow);
+        for text in matches.get_many::<String>("input-range").unwrap_or_default() {
+            let (name, range) = parse_input_range(text).unwrap_or_else(|message| error(&message));
+            range_analysis = range_analysis.with_input_range(&name, range);
+        }
+        file.accept(&mut range_analysis);
+    }
+
+    let evaluation = matches.get_one::<String>("evaluation").expect("has a default value");
+    let costs = matches.get_one::<String>("cost-model").expect("has a default value");
+
+    let mut options = PassOptions::new(overflow);
+    options.evaluation = EvaluationMode::from_name(evaluation).expect("validated by clap");
+    options.inexact_division_lint = matches.get_flag("warn-inexact-division");
+    options.costs = CostModel::from_name(costs).expect("validated by clap");
+    options.outputs = matches
+        .get_many::<String>("keep")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+
+    // An explicit pipeline, an optimization level, or the individual pass flags.
+    let pass_manager = if let Some(pipeline) = matches.get_one::<String>("passes") {
+        let mut pass_manager = PassManager::new(options);
+        pass_manager.add_pipeline(pipeline).map(|_| pass_manager)
+    } else if let Some(level) = matches.get_one::<u8>("opt-level") {
+        PassManager::optimization_level(options, *level)
+    } else {
+        Ok(PassManager::new(options))
+    };
+    let mut pass_manager = pass_manager.unwrap_or_else(|message| error(&message));
+
+    let constprop = matches.get_flag("constprop");
+    let legacy_passes = [
+        (constprop, "constprop"),
+        (matches.get_flag("simplify"), "simplify"),
+        // Simplification can turn variables into constants, e.g x * 0u8
+        (matches.get_flag("simplify") && constprop, "constprop"),
+        (matches.get_flag("reassociate"), "reassociate"),
+        (matches.get_flag("strength-reduce"), "strength"),
+        (matches.get_flag("cse"), "cse"),
+        (matches.get_flag("copyprop"), "copyprop"),
+        (matches.get_flag("dce"), "dce"),
+    ];
+    for (enabled, name) in legacy_passes {
+        if enabled {
+            pass_manager.add_pass(name).expect("a registered pass");
+        }
+    }
+
+    for name in matches.get_many::<String>("print-after").unwrap_or_default() {
+        pass_manager.print_after(name).unwrap_or_else(|message| error(&message));
+    }
+
+    let pass_manager = pass_manager.iterate_to_fixpoint(matches.get_flag("fixpoint"));
+    pass_manager.run(&mut file);
+
+    if let Some(path) = matches.get_one::<String>("write-bytecode") {
+        fs::write(path, encode(&compile(&file)))
+            .unwrap_or_else(|io_error| error(&format!("Cannot write the bytecode to '{}': {}.", path, io_error)));
+    }
+    if let Some(path) = matches.get_one::<String>("write-wasm") {
+        fs::write(path, wasm::encode(&wat::emit(&lower(&file), overflow)))
+            .unwrap_or_else(|io_error| error(&format!("Cannot write the WebAssembly module to '{}': {}.", path, io_error)));
+    }
+
+    let emit = matches.get_one::<String>("emit").expect("has a default value").as_str();
+    if matches.get_flag("print-intervals") {
+        let intervals = match emit {
+            "asm" => x86_64::allocation(&lower(&file)).to_string(),
+            "riscv" => riscv::allocation(&lower(&file)).to_string(),
+            _ => error(&"--print-intervals needs --emit=asm or --emit=riscv.".to_string()),
+        };
+        println!("Live intervals:\n\n{}\n", intervals);
     }
 
     // Write program to output.
-    println!("Resulting program:\n\n{}", file);
+    match emit {
+        "ir" => println!("Three-address code:\n\n{}", lower(&file)),
+        "bytecode" => println!("Bytecode:\n\n{}", compile(&file)),
+        "asm" => println!("Assembly:\n\n{}", x86_64::emit(&lower(&file), overflow)),
+        "riscv" => println!("Assembly:\n\n{}", riscv::emit(&lower(&file), overflow)),
+        "c" => {
+            let function = lower(&file);
+            let mut code = c::emit(&function, overflow);
+            if matches.get_flag("c-main") {
+                code += &c::emit_main(&function);
+            }
+            println!("C code:\n\n{}", code);
+        }
+        "wat" => println!("WebAssembly text:\n\n{}", wat::emit(&lower(&file), overflow)),
+        "wasm" => println!("WebAssembly binary:\n\n{}", wasm::hex_dump(&wasm::encode(&wat::emit(&lower(&file), overflow)))),
+        "llvm-ir" => println!("LLVM IR:\n\n{}", llvm::emit(&lower(&file), overflow)),
+        "r1cs" => println!("R1CS:\n\n{}", circuit::compile(&file, overflow).to_json()),
+        "verilog" => {
+            let mut netlist = netlist::synthesize(&file, overflow);
+            let bindings: Vec<String> = netlist.outputs.iter().map(|(binding, _)| binding.clone()).collect();
+            let outputs: Vec<String> = matches.get_many::<String>("output").unwrap_or_default().cloned().collect();
+            for output in &outputs {
+                if !bindings.contains(output) {
+                    error(&format!("There is no binding named '{}'.", output));
+                }
+            }
+            netlist.select(if outputs.is_empty() { &bindings } else { &outputs });
+            println!("Verilog:\n\n{}", verilog::emit(&netlist));
+        }
+        _ => println!("Resulting program:\n\n{}", file),
+    }
+}
+
+// Executes a program and prints the value of its bindings, or of the chosen outputs.
+// Serialized bytecode, and programs with --vm, run on the virtual machine. Programs with --riscv run on the RV32IM simulator,
+// and programs with --r1cs are compiled to a constraint system whose witness gives the bindings.
+// Exits with the code of a Rust panic (101) if the program panics.
+fn run(matches: &ArgMatches) {
+    let file_name = matches.get_one::<String>("file").expect("required argument");
+    let bytes = fs::read(file_name).expect("cannot read file");
+
+    let mut arguments = BTreeMap::new();
+    for text in matches.get_many::<String>("arg").unwrap_or_default() {
+        let (name, val) = parse_argument(text).unwrap_or_else(|message| error(&message));
+        arguments.insert(name, val);
+    }
+
+    let overflow = matches.get_one::<String>("overflow").expect("has a default value");
+    let overflow = OverflowMode::from_name(overflow).expect("validated by clap");
+
+    let (names, result) = if is_bytecode(&bytes) {
+        let bytecode = decode(&bytes).unwrap_or_else(|message| error(&message));
+        (bytecode.bindings().to_vec(), Vm::new(overflow).run_named(&bytecode, &arguments))
+    } else {
+        let unparsed_file = String::from_utf8(bytes).expect("cannot read file");
+        let mut file = parse(&unparsed_file).expect("unsuccessful parse");
+
+        let mut variable_checker = VariableChecker::new();
+        file.accept(&mut variable_checker);
+
+        let bytecode = compile(&file);
+        let result = if matches.get_flag("vm") {
+            Vm::new(overflow).run_named(&bytecode, &arguments)
+        } else if matches.get_flag("riscv") {
+            Harness::new(&riscv::emit(&lower(&file), overflow)).run_named(&arguments)
+        } else if matches.get_flag("r1cs") {
+            witness::run_named(&circuit::compile(&file, overflow), &arguments)
+        } else {
+            Interpreter::new(overflow).run(&file, &arguments)
+        };
+        (bytecode.bindings().to_vec(), result)
+    };
+
+    let outputs: Vec<&String> = matches.get_many::<String>("output").unwrap_or_default().collect();
+    for output in &outputs {
+        if !names.contains(output) {
+            error(&format!("There is no binding named '{}'.", output));
+        }
+    }
+
+    match result {
+        Ok(bindings) => {
+            for (variable, val) in bindings {
+                if outputs.is_empty() || outputs.contains(&&variable) {
+                    println!("{} = {}", variable, val);
+                }
+            }
+        }
+        Err(panic @ RuntimeError::Panic { .. }) => {
+            eprintln!("{}", panic.message());
+            std::process::exit(101);
+        }
+        Err(runtime_error) => error(&runtime_error.message()),
+    }
 }
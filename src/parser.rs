@@ -10,6 +10,8 @@ pub struct RustParser;
 
 // Functions to parse a Rust code string into a Rust AST
 
+// pest errors are large, but callers get them as they are
+#[allow(clippy::result_large_err)]
 pub fn parse(source: &str) -> Result<Program, Error<Rule>> {
     let mut name = String::new();
     let mut inputs = Vec::new();
@@ -26,11 +28,8 @@ pub fn parse(source: &str) -> Result<Program, Error<Rule>> {
 
                 // Parse fn inputs if any
                 for inner_pair in inner_pairs {
-                    match inner_pair.as_rule() {
-                        Rule::input => {
-                            inputs.push(parse_single_input(inner_pair));
-                        }
-                        _ => {}
+                    if inner_pair.as_rule() == Rule::input {
+                        inputs.push(parse_single_input(inner_pair));
                     }
                 }
             }
@@ -82,19 +81,41 @@ fn parse_statement(pair: pest::iterators::Pair<Rule>) -> Statement {
 }
 
 fn parse_expression(pair: pest::iterators::Pair<Rule>) -> Expression {
-    parse_addition(pair.into_inner().next().unwrap())
+    parse_bit_or(pair.into_inner().next().unwrap())
+}
+
+fn parse_bit_or(pair: pest::iterators::Pair<Rule>) -> Expression {
+    parse_binary(pair, parse_bit_xor)
+}
+
+fn parse_bit_xor(pair: pest::iterators::Pair<Rule>) -> Expression {
+    parse_binary(pair, parse_bit_and)
+}
+
+fn parse_bit_and(pair: pest::iterators::Pair<Rule>) -> Expression {
+    parse_binary(pair, parse_shift)
+}
+
+fn parse_shift(pair: pest::iterators::Pair<Rule>) -> Expression {
+    parse_binary(pair, parse_addition)
 }
 
 fn parse_addition(pair: pest::iterators::Pair<Rule>) -> Expression {
+    parse_binary(pair, parse_multiplication)
+}
+
+// Parses a left-associative chain of operators of the same precedence,
+// whose operands are parsed by the rule of the next precedence level
+fn parse_binary(pair: pest::iterators::Pair<Rule>, parse_operand: fn(pest::iterators::Pair<Rule>) -> Expression) -> Expression {
     let mut inner_pairs = pair.into_inner();
 
-    // The initial left-hand side is a multiplication
-    let mut expr = parse_multiplication(inner_pairs.next().unwrap());
+    // The initial left-hand side is an operand
+    let mut expr = parse_operand(inner_pairs.next().unwrap());
 
-    // Loop over any additional (operator multiplication) pairs
+    // Loop over any additional (operator operand) pairs
     while inner_pairs.peek().is_some() {
         let operator = parse_operator(inner_pairs.next().unwrap());
-        let right_expr = parse_multiplication(inner_pairs.next().unwrap());
+        let right_expr = parse_operand(inner_pairs.next().unwrap());
         expr = Expression::Binary {
             left: Value::Expression(Box::new(expr)),
             operator,
@@ -109,12 +130,14 @@ fn parse_multiplication(pair: pest::iterators::Pair<Rule>) -> Expression {
     let mut inner_pairs = pair.into_inner();
 
     // The initial left-hand side is a value
-    let mut expr = Expression::Value(Box::new(parse_value(inner_pairs.next().unwrap())));
+    let first = inner_pairs.next().unwrap();
+    let mut expr = Expression::Value(Box::new(parse_method_calls(first, &mut inner_pairs)));
 
     // Loop over any additional (operator value) pairs
     while let Some(op_pair) = inner_pairs.next() {
         let operator = parse_operator(op_pair);
-        let right_expr = parse_value(inner_pairs.next().unwrap());
+        let operand = inner_pairs.next().unwrap();
+        let right_expr = parse_method_calls(operand, &mut inner_pairs);
         expr = Expression::Binary {
             left: Value::Expression(Box::new(expr)),
             operator,
@@ -125,6 +148,37 @@ fn parse_multiplication(pair: pest::iterators::Pair<Rule>) -> Expression {
     expr
 }
 
+// Parses a value followed by any method calls on it, e.g a.wrapping_add(1u8)
+fn parse_method_calls(pair: pest::iterators::Pair<Rule>, pairs: &mut pest::iterators::Pairs<Rule>) -> Value {
+    let mut value = parse_value(pair);
+
+    // Every call receives the value of the previous one
+    while let Some(method_pair) = pairs.peek() {
+        let kind = match method_pair.as_rule() {
+            Rule::wrapping => MethodKind::Wrapping,
+            Rule::saturating => MethodKind::Saturating,
+            Rule::checked => MethodKind::Checked,
+            Rule::overflowing => MethodKind::Overflowing,
+            Rule::overflow_flag => MethodKind::OverflowFlag,
+            _ => break,
+        };
+
+        let mut method_pairs = pairs.next().unwrap().into_inner();
+        // The method name, e.g wrapping_add, holds the operator
+        let name = method_pairs.next().unwrap();
+        let operator = parse_method_operator(name.into_inner().next().unwrap());
+        let argument = parse_expression(method_pairs.next().unwrap());
+
+        value = Value::MethodCall {
+            receiver: Box::new(value),
+            method: Method { kind, operator },
+            argument: Box::new(argument),
+        };
+    }
+
+    value
+}
+
 fn parse_value(pair: pest::iterators::Pair<Rule>) -> Value {
     match pair.as_rule() {
         Rule::integer => {
@@ -156,6 +210,21 @@ fn parse_operator(pair: pest::iterators::Pair<Rule>) -> Operator {
         "-" => Operator::Subtract,
         "*" => Operator::Multiply,
         "/" => Operator::Divide,
+        "<<" => Operator::ShiftLeft,
+        ">>" => Operator::ShiftRight,
+        "&" => Operator::BitAnd,
+        "|" => Operator::BitOr,
+        "^" => Operator::BitXor,
         _ => panic!("failed to parse operator"),
     }
 }
+
+fn parse_method_operator(pair: pest::iterators::Pair<Rule>) -> Operator {
+    match pair.as_str() {
+        "add" => Operator::Add,
+        "sub" => Operator::Subtract,
+        "mul" => Operator::Multiply,
+        "div" => Operator::Divide,
+        _ => panic!("failed to parse method operator"),
+    }
+}
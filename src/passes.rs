@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use crate::ast::*;
+use crate::arithmetic::*;
+use crate::messages::*;
+use crate::visitors::visitor::{NodeAccept, Visitor};
+use crate::visitors::constprop::ConstantPropagation;
+use crate::visitors::sccp::SparseConditionalConstantPropagation;
+use crate::visitors::simplify::Simplifier;
+use crate::visitors::reassociate::Reassociation;
+use crate::visitors::strength::{CostModel, StrengthReduction};
+use crate::visitors::cse::CommonSubexpressionElimination;
+use crate::visitors::copyprop::CopyPropagation;
+use crate::visitors::dead_code::DeadCodeElimination;
+
+// The names of the passes that can be part of a pipeline
+pub const PASSES: [&str; 8] = ["constprop", "sccp", "simplify", "reassociate", "strength", "cse", "copyprop", "dce"];
+
+// The most times a pipeline is repeated while looking for a fixpoint
+const MAX_ITERATIONS: usize = 16;
+
+// The options every pass is created with
+#[derive(Debug, Clone)]
+pub struct PassOptions {
+    pub overflow: OverflowMode,
+    pub evaluation: EvaluationMode,
+    pub inexact_division_lint: bool,
+    pub costs: CostModel,
+    // Variables that dead code elimination has to keep
+    pub outputs: HashSet<String>,
+}
+
+impl PassOptions {
+    pub fn new(overflow: OverflowMode) -> Self {
+        PassOptions {
+            overflow,
+            evaluation: EvaluationMode::Exact,
+            inexact_division_lint: false,
+            costs: CostModel::from_name("default").unwrap(),
+            outputs: HashSet::new(),
+        }
+    }
+}
+
+// Creates the pass registered under `name`
+pub fn create_pass(name: &str, options: &PassOptions) -> Option<Box<dyn Visitor>> {
+    let pass: Box<dyn Visitor> = match name {
+        "constprop" => Box::new(
+            ConstantPropagation::with_overflow(options.overflow)
+                .with_evaluation(options.evaluation)
+                .lint_inexact_division(options.inexact_division_lint),
+        ),
+        "sccp" => Box::new(SparseConditionalConstantPropagation::new(options.overflow)),
+        "simplify" => Box::new(Simplifier::new(options.overflow)),
+        "reassociate" => Box::new(Reassociation::new(options.overflow)),
+        "strength" => Box::new(StrengthReduction::new(options.overflow, options.costs)),
+        "cse" => Box::new(CommonSubexpressionElimination::new(options.overflow)),
+        "copyprop" => Box::new(CopyPropagation::new()),
+        "dce" => Box::new(DeadCodeElimination::new(options.overflow, options.outputs.clone())),
+        _ => return None,
+    };
+    Some(pass)
+}
+
+// Runs a pipeline of passes over a program
+// 	- Passes are created fresh for every run, so no state leaks between runs
+// 	- With fixpoint iteration, the whole pipeline is repeated until the program stops changing
+// 	- The program can be printed after any pass, for debugging
+pub struct PassManager {
+    options: PassOptions,
+    pipeline: Vec<String>,
+    fixpoint: bool,
+    print_after: HashSet<String>,
+}
+
+impl PassManager {
+    pub fn new(options: PassOptions) -> Self {
+        PassManager {
+            options,
+            pipeline: Vec::new(),
+            fixpoint: false,
+            print_after: HashSet::new(),
+        }
+    }
+
+    // The pipeline of an optimization level.
+    // Dead code elimination only runs if there are outputs to keep, otherwise it would remove everything.
+    pub fn optimization_level(options: PassOptions, level: u8) -> Result<Self, String> {
+        let with_dce = !options.outputs.is_empty();
+        let mut manager = PassManager::new(options);
+
+        let pipeline = match level {
+            0 => "",
+            1 => "sccp,simplify,copyprop",
+            2 => "sccp,simplify,reassociate,sccp,strength,cse,copyprop",
+            _ => return Err(format!("Unknown optimization level '{}', expected 0, 1 or 2.", level)),
+        };
+        manager.add_pipeline(pipeline)?;
+
+        if level > 0 && with_dce {
+            manager.add_pass("dce")?;
+        }
+        manager.fixpoint = level >= 2;
+        Ok(manager)
+    }
+
+    pub fn add_pass(&mut self, name: &str) -> Result<(), String> {
+        if !PASSES.contains(&name) {
+            return Err(format!("Unknown pass '{}', expected one of: {}.", name, PASSES.join(", ")));
+        }
+        self.pipeline.push(name.to_string());
+        Ok(())
+    }
+
+    // Appends a comma separated list of passes, e.g "constprop,dce,cse"
+    pub fn add_pipeline(&mut self, pipeline: &str) -> Result<(), String> {
+        for name in pipeline.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            self.add_pass(name)?;
+        }
+        Ok(())
+    }
+
+    pub fn iterate_to_fixpoint(mut self, fixpoint: bool) -> Self {
+        self.fixpoint = self.fixpoint || fixpoint;
+        self
+    }
+
+    pub fn print_after(&mut self, name: &str) -> Result<(), String> {
+        if !PASSES.contains(&name) {
+            return Err(format!("Unknown pass '{}', expected one of: {}.", name, PASSES.join(", ")));
+        }
+        self.print_after.insert(name.to_string());
+        Ok(())
+    }
+
+    pub fn pipeline(&self) -> &[String] {
+        &self.pipeline
+    }
+
+    pub fn run(&self, program: &mut Program) {
+        let iterations = if self.fixpoint { MAX_ITERATIONS } else { 1 };
+
+        for iteration in 1..=iterations {
+            let before = program.clone();
+            self.run_once(program, iteration);
+
+            if *program == before {
+                return;
+            }
+        }
+
+        if self.fixpoint {
+            warn(&format!("The pipeline did not reach a fixpoint after {} iterations.", MAX_ITERATIONS));
+        }
+    }
+
+    fn run_once(&self, program: &mut Program, iteration: usize) {
+        for name in &self.pipeline {
+            let mut pass = create_pass(name, &self.options).expect("validated when added");
+            program.accept(pass.as_mut());
+
+            if self.print_after.contains(name) {
+                if self.fixpoint {
+                    println!("Program after {} (iteration {}):\n\n{}\n", name, iteration, program);
+                } else {
+                    println!("Program after {}:\n\n{}\n", name, program);
+                }
+            }
+        }
+    }
+}
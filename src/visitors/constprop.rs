@@ -1,8 +1,14 @@
-use std::collections::HashMap;
 use crate::ast::*;
 use crate::visitors::visitor::Visitor;
 use crate::visitors::visitor::NodeAccept;
 use crate::messages::*;
+use crate::arithmetic::*;
+use crate::dataflow::constants::{ConstantFacts, Constants};
+use crate::dataflow::framework::{Analysis, Location};
+use crate::ir::cfg::Cfg;
+use crate::ir::instruction::Variable;
+use crate::ir::lower::{lower, lower_statement};
+use crate::ir::sccp;
 
 // Visitor that performs constant propagation
 // 	- If an expression is encountered: 
@@ -15,27 +21,318 @@ use crate::messages::*;
 // 			- recursively traverse the LHS and RHS
 // 			- if both LHS and RHS reduce to Value::Integer, apply the binary operation to the integers
 // 				- With the appropriate checks for a correct result
+// 				- What happens on overflow is decided by the OverflowMode
+// 			- in mathematical evaluation mode, a constant expression is first evaluated as a whole
+// 			  over the integers, and only its result has to fit in a u8
+// 		- if it's a Value::MethodCall (e.g a.wrapping_add(1u8)):
+// 			- recursively traverse the receiver and the argument
+// 			- if both reduce to Value::Integer, apply the method with its documented semantics
 // 			
 //     - if a Statment node is encountered (i.e an assignment):
 // 		- recursively traverse the initializing expression.
-// 		- If that expression is evaluated to a constant after traversal, cache the name of the constant and its value
+// 		- The values of the variables are then computed by the constant analysis of dataflow::constants.
+// 		  The program is a single block, so the analysis transforms its fact statement by statement,
+// 		  once each statement is folded (e.g in mathematical evaluation mode, a variable is only
+// 		  constant if its initializer was folded)
 pub struct ConstantPropagation {
-    // A map to store the current known constants for variables
-    constants: HashMap<String, u8>,
+    constants: Constants,
+    // The values of the variables defined so far
+    facts: ConstantFacts,
+    // How to fold operations whose result does not fit in a u8
+    overflow: OverflowMode,
+    // Whether every operation or only whole constant expressions have to fit in a u8
+    evaluation: EvaluationMode,
+    // Whether to warn when a folded division discards a remainder
+    inexact_division_lint: bool,
+    // The variable whose initializer is being visited, to give context to messages
+    variable: String,
 }
 
 impl ConstantPropagation {
     pub fn new() -> Self {
+        ConstantPropagation::with_overflow(OverflowMode::Error)
+    }
+
+    pub fn with_overflow(overflow: OverflowMode) -> Self {
         ConstantPropagation {
-            constants: HashMap::new(),
+            constants: Constants::new(overflow),
+            facts: ConstantFacts(None),
+            overflow,
+            evaluation: EvaluationMode::Exact,
+            inexact_division_lint: false,
+            variable: String::new(),
+        }
+    }
+
+    pub fn with_evaluation(mut self, evaluation: EvaluationMode) -> Self {
+        self.evaluation = evaluation;
+        self
+    }
+
+    // Warn when a folded division truncates its result, e.g 10u8 / 3u8
+    pub fn lint_inexact_division(mut self, enabled: bool) -> Self {
+        self.inexact_division_lint = enabled;
+        self
+    }
+
+    // Called when folding an operation would panic.
+    // Returns the folded value if there is one, otherwise the operation is left as is.
+    fn on_panic(&self, panic: Panic, description: String) -> Option<u8> {
+        let description = format!("{} (in the initializer of '{}')", description, self.variable);
+
+        if self.overflow == OverflowMode::Error {
+            let message = match panic {
+                Panic::Overflow(Operator::Subtract) => {
+                    format!("Constant evaluation resulted in negative value: {}", description)
+                }
+                Panic::Overflow(Operator::Add | Operator::Multiply) => {
+                    format!("Constant evaluation resulted in value greater than 255: {}", description)
+                }
+                Panic::DivisionByZero => {
+                    format!("Constant evaluation resulted in division by zero: {}", description)
+                }
+                Panic::Overflow(_) | Panic::Unwrap => {
+                    format!("Constant evaluation panicked with '{}': {}", panic.message(), description)
+                }
+            };
+            error(&message);
+        }
+
+        // The operation panics at runtime, which is only preserved by not folding it
+        warn(&format!("This operation will panic at runtime with '{}': {}", panic.message(), description));
+        None
+    }
+
+    // The warning for a folded division that truncates its result, if the lint is enabled.
+    // Integer division truncates exactly like it does in Rust, whether it is written / or e.g wrapping_div.
+    fn inexact_division(&self, operator: &Operator, left: i128, right: i128, description: &str) -> Option<String> {
+        if self.inexact_division_lint && *operator == Operator::Divide && right != 0 && left % right != 0 {
+            Some(format!("Constant division discards a remainder of {}: {}", left % right, description))
+        } else {
+            None
+        }
+    }
+
+    fn fold_operator(&self, operator: &Operator, left: u8, right: u8) -> Option<u8> {
+        if let Some(message) = self.inexact_division(operator, left.into(), right.into(), &format!("{} {} {}", left, operator, right)) {
+            warn(&message);
+        }
+
+        match evaluate_operator(self.overflow, operator, left, right) {
+            Ok(result) => Some(result),
+            Err(panic) => self.on_panic(panic, format!("{} {} {}", left, operator, right)),
+        }
+    }
+
+    fn fold_method(&self, method: &Method, left: u8, right: u8) -> Option<u8> {
+        if let Some(message) = self.inexact_method_division(method, left, right) {
+            warn(&message);
+        }
+
+        match evaluate_method(method, left, right) {
+            Ok(result) => Some(result),
+            Err(panic) => self.on_panic(panic, method_call(method, left, right)),
+        }
+    }
+
+    // The overflow flag of a division is not a quotient, so it discards nothing
+    fn inexact_method_division(&self, method: &Method, left: u8, right: u8) -> Option<String> {
+        if method.kind == MethodKind::OverflowFlag {
+            return None;
+        }
+        self.inexact_division(&method.operator, left.into(), right.into(), &method_call(method, left, right))
+    }
+
+    // The value of a variable, if it is a known constant
+    fn constant(&self, variable: &str) -> Option<u8> {
+        match self.facts.value(&Variable::Named(variable.to_string())) {
+            sccp::Lattice::Constant(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    // Folds an initializer in mathematical evaluation mode. It is evaluated once over the integers,
+    // then its largest subexpressions that fit in a u8 are folded, with the warnings of what they contain.
+    // The whole initializer has to fit in a u8, subexpressions that don't may still be folded as part of their parent.
+    fn fold_wide(&self, expression: &mut Expression) {
+        let tree = self.evaluate_wide_expression(expression);
+
+        match tree.value {
+            Some(result) if u8::try_from(result).is_err() => {
+                let description = format!("{} (in the initializer of '{}')", expression, self.variable);
+                let folded = match self.overflow {
+                    OverflowMode::Error => {
+                        error(&format!("Constant evaluation resulted in {}, which does not fit in u8: {}", result, description));
+                    }
+                    OverflowMode::Panic => {
+                        warn(&format!("Constant evaluation resulted in {}, which does not fit in u8: {}", result, description));
+                        None
+                    }
+                    OverflowMode::Wrap => Some(result.rem_euclid(256) as u8),
+                    OverflowMode::Saturate => Some(result.clamp(0, u8::MAX as i128) as u8),
+                };
+
+                match folded {
+                    Some(val) => {
+                        let mut notes = WideNotes::default();
+                        tree.collect(&mut notes);
+                        for division in &notes.divisions {
+                            warn(division);
+                        }
+                        *expression = Expression::Value(Box::new(Value::Integer(val)));
+                    }
+                    None => self.fold_wide_children(expression, &tree),
+                }
+            }
+            _ => self.fold_wide_expression(expression, &tree),
+        }
+    }
+
+    // Folds an expression if it fits in a u8, and its subexpressions otherwise
+    fn fold_wide_expression(&self, expression: &mut Expression, tree: &WideTree) {
+        match self.fits(tree) {
+            Some(val) => *expression = Expression::Value(Box::new(Value::Integer(val))),
+            None => self.fold_wide_children(expression, tree),
+        }
+    }
+
+    fn fold_wide_children(&self, expression: &mut Expression, tree: &WideTree) {
+        match expression {
+            Expression::Binary { left, right, .. } => {
+                self.fold_wide_value(left, &tree.children[0]);
+                self.fold_wide_expression(right, &tree.children[1]);
+            }
+            Expression::Value(value) => self.fold_wide_value(value, &tree.children[0]),
+        }
+    }
+
+    fn fold_wide_value(&self, value: &mut Value, tree: &WideTree) {
+        if let Some(val) = self.fits(tree) {
+            *value = Value::Integer(val);
+            return;
+        }
+        match value {
+            Value::Expression(expr) => self.fold_wide_expression(expr, &tree.children[0]),
+            Value::MethodCall { receiver, argument, .. } => {
+                self.fold_wide_value(receiver, &tree.children[0]);
+                self.fold_wide_expression(argument, &tree.children[1]);
+            }
+            _ => {}
+        }
+    }
+
+    // The value of a node if it is constant and fits in a u8, after warning about what it contains
+    fn fits(&self, tree: &WideTree) -> Option<u8> {
+        let val = u8::try_from(tree.value?).ok()?;
+
+        let mut notes = WideNotes::default();
+        tree.collect(&mut notes);
+        for overflow in &notes.overflows {
+            warn(&format!("Subexpression {}, which does not fit in u8 (in the initializer of '{}')", overflow, self.variable));
+        }
+        for division in &notes.divisions {
+            warn(division);
+        }
+        Some(val)
+    }
+
+    // Evaluates every node of an expression over the integers, for the nodes whose leaves are all known constants
+    fn evaluate_wide_expression(&self, expression: &Expression) -> WideTree {
+        match expression {
+            Expression::Binary { left, operator, right } => {
+                let children = vec![self.evaluate_wide_value(left), self.evaluate_wide_expression(right)];
+                let mut notes = WideNotes::default();
+                let value = match (children[0].value, children[1].value) {
+                    (Some(left_val), Some(right_val)) => evaluate_wide(operator, left_val, right_val),
+                    _ => None,
+                };
+
+                if let (Some(result), Some(left_val), Some(right_val)) = (value, children[0].value, children[1].value) {
+                    if u8::try_from(result).is_err() {
+                        notes.overflows.push(format!("{} evaluates to {}", expression, result));
+                    }
+                    let description = format!("{} {} {}", left_val, operator, right_val);
+                    notes.divisions.extend(self.inexact_division(operator, left_val, right_val, &description));
+                }
+                WideTree { value, notes, children }
+            }
+            Expression::Value(value) => WideTree::parent(self.evaluate_wide_value(value)),
+        }
+    }
+
+    fn evaluate_wide_value(&self, value: &Value) -> WideTree {
+        match value {
+            Value::Integer(val) => WideTree::leaf(Some(*val as i128)),
+            Value::Identifier(var) => WideTree::leaf(self.constant(var).map(i128::from)),
+            Value::Expression(expr) => WideTree::parent(self.evaluate_wide_expression(expr)),
+            Value::MethodCall { receiver, method, argument } => {
+                let children = vec![self.evaluate_wide_value(receiver), self.evaluate_wide_expression(argument)];
+                let mut notes = WideNotes::default();
+
+                // Methods are called on u8 values, so their operands have to fit
+                let operands = children[0]
+                    .value
+                    .and_then(|left_val| u8::try_from(left_val).ok())
+                    .zip(children[1].value.and_then(|right_val| u8::try_from(right_val).ok()));
+                let value = operands.and_then(|(left_val, right_val)| {
+                    let result = evaluate_method(method, left_val, right_val).ok()?;
+                    notes.divisions.extend(self.inexact_method_division(method, left_val, right_val));
+                    Some(i128::from(result))
+                });
+                WideTree { value, notes, children }
+            }
+        }
+    }
+}
+
+// What evaluating an operation over the integers found, to warn about once it is folded
+#[derive(Default)]
+struct WideNotes {
+    // Intermediate results that do not fit in a u8
+    overflows: Vec<String>,
+    // Inexact divisions, when the lint is enabled
+    divisions: Vec<String>,
+}
+
+// The value over the integers of every node of an expression, with the children of every node in order
+struct WideTree {
+    // None if a leaf is not a known constant, or if an operation panics whatever the width
+    value: Option<i128>,
+    // What evaluating the operation of the node found
+    notes: WideNotes,
+    children: Vec<WideTree>,
+}
+
+impl WideTree {
+    fn leaf(value: Option<i128>) -> Self {
+        WideTree { value, notes: WideNotes::default(), children: Vec::new() }
+    }
+
+    // A node with the value of its only child, e.g parenthesis
+    fn parent(child: WideTree) -> Self {
+        WideTree { value: child.value, notes: WideNotes::default(), children: vec![child] }
+    }
+
+    // Gathers the notes of the whole subtree, in evaluation order
+    fn collect(&self, notes: &mut WideNotes) {
+        for child in &self.children {
+            child.collect(notes);
         }
+        notes.overflows.extend(self.notes.overflows.iter().cloned());
+        notes.divisions.extend(self.notes.divisions.iter().cloned());
+    }
+}
+
+impl Default for ConstantPropagation {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Visitor for ConstantPropagation {
     fn visit_program(&mut self, program: &mut Program) {
-        // Clear the constants map for a new run
-        self.constants.clear();
+        // The inputs are unknown
+        self.facts = self.constants.boundary(&Cfg::from_function(&lower(program)));
 
         // Visit each statement in the program.
         // There is no need to visit inputs as they
@@ -48,17 +345,19 @@ impl Visitor for ConstantPropagation {
     fn visit_statement(&mut self, statement: &mut Statement) {
         match statement {
             Statement::Assign { variable, expression } => {
+                self.variable = variable.clone();
+
+                // Evaluate the constant subexpressions as a whole before their operations are folded one by one
+                if self.evaluation == EvaluationMode::Mathematical {
+                    self.fold_wide(expression);
+                }
+
                 // Visit the initializing expression to propagate constants
                 expression.accept(self);
 
-                // If the expression is a constant value, store it in the map
-                match expression {
-                    Expression::Value(boxed_value) => {
-                        if let Value::Integer(val) = **boxed_value {
-                            self.constants.insert(variable.clone(), val);
-                        }
-                    }
-                    _ => {}
+                // Computes the value of the variable from the folded statement
+                for (index, instruction) in lower_statement(statement).iter().enumerate() {
+                    self.constants.transfer(Location { block: 0, index }, instruction, &mut self.facts);
                 }
             }
         }
@@ -72,50 +371,10 @@ impl Visitor for ConstantPropagation {
                 right.accept(self);
 
                 // Simplify if possible
-                match (left, &**right) {
-                    (Value::Integer(left_val), Expression::Value(boxed_value)) => {
-                        match &**boxed_value {
-                            Value::Integer(right_val) => {
-                                let result = match operator {
-                                    Operator::Add => {
-                                        let res = (*left_val as u16) + (*right_val as u16);
-                                        if res > u8::MAX as u16{
-                                            error(&format!("Constant evaluation resulted in value greater than 255: {} + {}", left_val, right_val));
-                                        } else {
-                                            res as u8
-                                        }
-                                    }
-                                    Operator::Subtract => {
-                                        if *left_val < *right_val {
-                                            error(&format!("Constant evaluation resulted in negative value: {} - {}", left_val, right_val));
-                                        } else {
-                                            *left_val - *right_val
-                                        }
-                                    }
-                                    Operator::Multiply => {
-                                        let res: u16 = (*left_val as u16) * (*right_val as u16);
-                                        if res > u8::MAX as u16{
-                                            error(&format!("Constant evaluation resulted in value greater than 255: {} * {}", left_val, right_val));
-                                        } else {
-                                            res as u8
-                                        }    
-                                    }
-                                    Operator::Divide => {
-                                        if *right_val == 0 {
-                                            error(&format!("Constant evaluation resulted in division by zero: {} / {}", left_val, right_val));
-                                        } else if (*left_val % *right_val) != 0 {
-                                            error(&format!("Constant evaluation resulted in non-integer division: {} / {}", left_val, right_val));
-                                        } else {
-                                            *left_val / *right_val
-                                        }
-                                    }
-                                };
-                                *expression = Expression::Value(Box::new(Value::Integer(result)));
-                            }
-                            _ => {}
-                        }
+                if let (Value::Integer(left_val), Some(right_val)) = (&*left, right.as_integer()) {
+                    if let Some(result) = self.fold_operator(operator, *left_val, right_val) {
+                        *expression = Expression::Value(Box::new(Value::Integer(result)));
                     }
-                    _ => {}
                 }
             }
             Expression::Value(value) => {
@@ -129,26 +388,43 @@ impl Visitor for ConstantPropagation {
 
             // If the value is an identifier, check if it is a constant
             Value::Identifier(ref var) => {
-                if (self.constants).contains_key(var) {
+                if let Some(val) = self.constant(var) {
 
                     // Replace the identifier with its constant value
-                    *value = Value::Integer(self.constants[var]);
+                    *value = Value::Integer(val);
                 }
             }
 
             // If the expression is a constant, replace it with the constant value
             Value::Expression(expr) => {
                 expr.accept(self);
-                match &**expr {
-                    Expression::Value(boxed_value) => {
-                        if let Value::Integer(val) = **boxed_value {
-                            *value = Value::Integer(val);
-                        }
+                if let Some(val) = expr.as_integer() {
+                    *value = Value::Integer(val);
+                }
+            }
+
+            // If both the receiver and the argument are constants, apply the method
+            Value::MethodCall { receiver, method, argument } => {
+                receiver.accept(self);
+                argument.accept(self);
+
+                if let (Value::Integer(left_val), Some(right_val)) = (&**receiver, argument.as_integer()) {
+                    if let Some(result) = self.fold_method(method, *left_val, right_val) {
+                        *value = Value::Integer(result);
                     }
-                    _ => {}
                 }
             }
             _ => {}
         }
     }
 }
+
+// A method call on integers, e.g 10u8.wrapping_div(3u8)
+fn method_call(method: &Method, left: u8, right: u8) -> String {
+    let call = Value::MethodCall {
+        receiver: Box::new(Value::Integer(left)),
+        method: method.clone(),
+        argument: Box::new(Expression::Value(Box::new(Value::Integer(right)))),
+    };
+    call.to_string()
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use crate::ast::*;
+use crate::visitors::visitor::Visitor;
+use crate::visitors::visitor::NodeAccept;
+
+// Visitor that performs copy propagation
+// 	- If a Statement binds a variable to another plain variable (e.g let a = x;),
+// 	  the binding is recorded as a copy of x
+// 	- Every use of a copy is replaced with its original, e.g let b = a + 1u8; becomes let b = x + 1u8;
+// 		- copies of copies resolve to the first original
+// 	- The copy bindings themselves are kept, dead code elimination removes them if they are unused
+pub struct CopyPropagation {
+    // The original variable of each copy
+    copies: HashMap<String, String>,
+}
+
+impl CopyPropagation {
+    pub fn new() -> Self {
+        CopyPropagation {
+            copies: HashMap::new(),
+        }
+    }
+}
+
+impl Default for CopyPropagation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Visitor for CopyPropagation {
+    fn visit_program(&mut self, program: &mut Program) {
+        self.copies.clear();
+
+        for statement in &mut program.statements {
+            statement.accept(self);
+        }
+    }
+
+    fn visit_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Assign { variable, expression } => {
+                expression.accept(self);
+
+                // The uses of the original have already been replaced, so it is not a copy itself
+                if let Some(original) = identifier_of(expression) {
+                    self.copies.insert(variable.clone(), original);
+                }
+            }
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &mut Expression) {
+        match expression {
+            Expression::Binary { left, right, .. } => {
+                left.accept(self);
+                right.accept(self);
+            }
+            Expression::Value(value) => {
+                value.accept(self);
+            }
+        }
+    }
+
+    fn visit_value(&mut self, value: &mut Value) {
+        match value {
+            Value::Identifier(name) => {
+                if let Some(original) = self.copies.get(name) {
+                    *name = original.clone();
+                }
+            }
+            Value::Expression(expr) => {
+                expr.accept(self);
+            }
+            Value::MethodCall { receiver, argument, .. } => {
+                receiver.accept(self);
+                argument.accept(self);
+            }
+            Value::Integer(_) => {}
+        }
+    }
+}
+
+// Returns the variable an expression consists of, looking through parenthesis
+fn identifier_of(expression: &Expression) -> Option<String> {
+    match expression {
+        Expression::Value(value) => match &**value {
+            Value::Identifier(name) => Some(name.clone()),
+            Value::Expression(inner) => identifier_of(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
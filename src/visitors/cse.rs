@@ -0,0 +1,265 @@
+use std::collections::{HashMap, HashSet};
+use crate::ast::*;
+use crate::visitors::visitor::Visitor;
+use crate::arithmetic::*;
+
+// Visitor that performs common subexpression elimination by value numbering
+// 	- Every operation (binary operation or method call) is numbered by its structure,
+// 	  modulo the commutativity of +, *, &, | and ^, e.g a + b and b + a share a number
+// 		- bindings are never redefined, so equal structures always have equal values
+// 	- Statements are visited in order:
+// 		- occurrences of an available value are replaced with the variable that holds it
+// 		- an operation that occurs again (in the same or in a later statement) and is not
+// 		  available yet is bound to a fresh temporary, inserted before the statement.
+// 		  The largest such operation is chosen first.
+// 		  Only occurrences with nothing that may panic evaluated before them in the statement
+// 		  are bound, otherwise the statement could panic differently, e.g in (a * 2u8) + (b - 2u8)
+// 		- the initializer of the statement becomes available through its variable
+pub struct CommonSubexpressionElimination {
+    overflow: OverflowMode,
+    // The variable holding each available value, by value number
+    available: HashMap<String, String>,
+    // Every name that is in use, to pick fresh temporaries
+    names: HashSet<String>,
+}
+
+impl CommonSubexpressionElimination {
+    pub fn new(overflow: OverflowMode) -> Self {
+        CommonSubexpressionElimination {
+            overflow,
+            available: HashMap::new(),
+            names: HashSet::new(),
+        }
+    }
+
+    // Replaces available values in a statement. The initializer of a temporary is not replaced by itself.
+    fn rewrite_statement(statement: &mut Statement, available: &HashMap<String, String>) {
+        match statement {
+            Statement::Assign { variable, expression } => {
+                rewrite_children(expression, available);
+                if let Some(holder) = available.get(&value_number(expression)) {
+                    if holder != variable && is_operation(expression) {
+                        *expression = Expression::Value(Box::new(Value::Identifier(holder.clone())));
+                    }
+                }
+            }
+        }
+    }
+
+    // Finds the largest operation of the statement at `index` that occurs at least twice
+    // in that statement and the ones after it, once the available values are replaced.
+    fn repeated_operation(&self, statements: &[Statement], index: usize) -> Option<Expression> {
+        let Statement::Assign { variable, expression } = &statements[index];
+        let root = value_number(expression);
+
+        // Later occurrences of the whole initializer will reuse its variable
+        let mut available = self.available.clone();
+        if is_operation(expression) {
+            available.entry(root.clone()).or_insert(variable.clone());
+        }
+
+        let mut occurrences: HashMap<String, usize> = HashMap::new();
+        for statement in &statements[index..] {
+            let mut statement = statement.clone();
+            Self::rewrite_statement(&mut statement, &available);
+            let Statement::Assign { expression, .. } = &statement;
+
+            let mut operations = Vec::new();
+            operations_of(expression, &mut operations);
+            for operation in operations {
+                *occurrences.entry(value_number(&operation)).or_default() += 1;
+            }
+        }
+
+        let mut operations = Vec::new();
+        hoistable_operations(self.overflow, expression, &mut true, &mut operations);
+
+        operations
+            .into_iter()
+            .map(|operation| (value_number(&operation), operation))
+            .filter(|(number, _)| *number != root && occurrences.get(number).copied().unwrap_or(0) > 1)
+            .fold(None, |largest: Option<(String, Expression)>, (number, operation)| match largest {
+                Some((ref largest_number, _)) if largest_number.len() >= number.len() => largest,
+                _ => Some((number, operation)),
+            })
+            .map(|(_, operation)| operation)
+    }
+}
+
+impl Visitor for CommonSubexpressionElimination {
+    fn visit_program(&mut self, program: &mut Program) {
+        self.available.clear();
+        self.names = program.inputs.iter().map(|input| input.name.clone()).collect();
+        for statement in &program.statements {
+            let Statement::Assign { variable, .. } = statement;
+            self.names.insert(variable.clone());
+        }
+
+        let statements = &mut program.statements;
+        let mut index = 0;
+        while index < statements.len() {
+            Self::rewrite_statement(&mut statements[index], &self.available);
+
+            // Bind repeated operations to temporaries, which are then visited themselves
+            if let Some(operation) = self.repeated_operation(statements, index) {
+                let temporary = fresh_name(&mut self.names);
+                self.available.insert(value_number(&operation), temporary.clone());
+                statements.insert(index, Statement::Assign {
+                    variable: temporary,
+                    expression: operation,
+                });
+                continue;
+            }
+
+            let Statement::Assign { variable, expression } = &statements[index];
+            let number = value_number(expression);
+            if is_operation(expression) && !self.available.contains_key(&number) {
+                self.available.insert(number, variable.clone());
+            }
+            index += 1;
+        }
+    }
+}
+
+// The value number of an expression: its structure, with the operands
+// of commutative operations sorted and without parenthesis
+fn value_number(expression: &Expression) -> String {
+    match expression {
+        Expression::Binary { left, operator, right } => {
+            let mut operands = [value_number_of_value(left), value_number(right)];
+            if operator.is_commutative() {
+                operands.sort();
+            }
+            format!("({} {} {})", operands[0], operator, operands[1])
+        }
+        Expression::Value(value) => value_number_of_value(value),
+    }
+}
+
+fn value_number_of_value(value: &Value) -> String {
+    match value {
+        Value::Integer(val) => format!("{}u8", val),
+        Value::Identifier(name) => name.clone(),
+        Value::Expression(expression) => value_number(expression),
+        Value::MethodCall { receiver, method, argument } => {
+            let mut operands = [value_number_of_value(receiver), value_number(argument)];
+            if method.operator.is_commutative() {
+                operands.sort();
+            }
+            format!("({:?} {} {} {})", method.kind, operands[0], method.operator, operands[1])
+        }
+    }
+}
+
+// Whether an expression is an operation, as opposed to a (parenthesized) integer or identifier
+fn is_operation(expression: &Expression) -> bool {
+    match expression {
+        Expression::Binary { .. } => true,
+        Expression::Value(value) => match &**value {
+            Value::Expression(inner) => is_operation(inner),
+            Value::MethodCall { .. } => true,
+            _ => false,
+        },
+    }
+}
+
+// Collects every operation of an expression, outermost first
+fn operations_of(expression: &Expression, operations: &mut Vec<Expression>) {
+    match expression {
+        Expression::Binary { left, right, .. } => {
+            operations.push(expression.clone());
+            operations_of_value(left, operations);
+            operations_of(right, operations);
+        }
+        Expression::Value(value) => operations_of_value(value, operations),
+    }
+}
+
+// Collects the operations of an expression that can be evaluated before the rest of it,
+// i.e that nothing evaluated before them may panic. `safe` is whether that holds so far.
+fn hoistable_operations(mode: OverflowMode, expression: &Expression, safe: &mut bool, operations: &mut Vec<Expression>) {
+    match expression {
+        Expression::Binary { left, right, .. } => {
+            if *safe {
+                operations.push(expression.clone());
+            }
+            hoistable_operations_of_value(mode, left, safe, operations);
+            hoistable_operations(mode, right, safe, operations);
+        }
+        Expression::Value(value) => hoistable_operations_of_value(mode, value, safe, operations),
+    }
+    *safe = *safe && !may_panic(mode, expression);
+}
+
+fn hoistable_operations_of_value(mode: OverflowMode, value: &Value, safe: &mut bool, operations: &mut Vec<Expression>) {
+    match value {
+        Value::Expression(expression) => hoistable_operations(mode, expression, safe, operations),
+        Value::MethodCall { receiver, argument, .. } => {
+            if *safe {
+                operations.push(Expression::Value(Box::new(value.clone())));
+            }
+            hoistable_operations_of_value(mode, receiver, safe, operations);
+            hoistable_operations(mode, argument, safe, operations);
+            *safe = *safe && !value_may_panic(mode, value);
+        }
+        _ => {}
+    }
+}
+
+fn operations_of_value(value: &Value, operations: &mut Vec<Expression>) {
+    match value {
+        Value::Expression(expression) => operations_of(expression, operations),
+        Value::MethodCall { receiver, argument, .. } => {
+            operations.push(Expression::Value(Box::new(value.clone())));
+            operations_of_value(receiver, operations);
+            operations_of(argument, operations);
+        }
+        _ => {}
+    }
+}
+
+// Replaces available values bottom-up, so that values are matched
+// in terms of the variables that replaced their operands
+fn rewrite_expression(expression: &mut Expression, available: &HashMap<String, String>) {
+    rewrite_children(expression, available);
+    if is_operation(expression) {
+        if let Some(variable) = available.get(&value_number(expression)) {
+            *expression = Expression::Value(Box::new(Value::Identifier(variable.clone())));
+        }
+    }
+}
+
+fn rewrite_children(expression: &mut Expression, available: &HashMap<String, String>) {
+    match expression {
+        Expression::Binary { left, right, .. } => {
+            rewrite_value(left, available);
+            rewrite_expression(right, available);
+        }
+        Expression::Value(value) => match &mut **value {
+            Value::Expression(inner) => rewrite_children(inner, available),
+            Value::MethodCall { receiver, argument, .. } => {
+                rewrite_value(receiver, available);
+                rewrite_expression(argument, available);
+            }
+            _ => {}
+        },
+    }
+}
+
+fn rewrite_value(value: &mut Value, available: &HashMap<String, String>) {
+    match value {
+        Value::Expression(inner) => rewrite_children(inner, available),
+        Value::MethodCall { receiver, argument, .. } => {
+            rewrite_value(receiver, available);
+            rewrite_expression(argument, available);
+        }
+        _ => {}
+    }
+
+    let expression = Expression::Value(Box::new(value.clone()));
+    if is_operation(&expression) {
+        if let Some(variable) = available.get(&value_number(&expression)) {
+            *value = Value::Identifier(variable.clone());
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::{BTreeSet, HashSet};
+use crate::ast::*;
+use crate::visitors::visitor::Visitor;
+use crate::arithmetic::*;
+use crate::dataflow::framework::{instruction_facts, solve, Analysis, Location};
+use crate::dataflow::liveness::Liveness;
+use crate::ir::cfg::Cfg;
+use crate::ir::instruction::Variable;
+use crate::ir::lower::{lower, statement_ranges};
+use crate::messages::*;
+
+// Visitor that removes dead let bindings, based on liveness
+// 	- The program is lowered to three-address code, whose live variables are computed
+// 	  with the program outputs live at the end
+// 	- A binding is kept if its variable is live after its initializer, or if its initializer
+// 	  may panic (removing it would remove the panic). Any other binding is removed.
+// 	- The variables read by a removed binding were live because of it. Bindings that are only read
+// 	  by removed ones are faint, and removed too: a single backward pass from the live variables
+// 	  at the end only makes the operands of kept bindings live
+pub struct DeadCodeElimination {
+    overflow: OverflowMode,
+    // Variables that are live at the end of the program
+    outputs: HashSet<String>,
+}
+
+impl DeadCodeElimination {
+    pub fn new(overflow: OverflowMode, outputs: HashSet<String>) -> Self {
+        DeadCodeElimination { overflow, outputs }
+    }
+}
+
+impl Visitor for DeadCodeElimination {
+    fn visit_program(&mut self, program: &mut Program) {
+        for output in &self.outputs {
+            let defined = program.inputs.iter().any(|input| input.name == *output)
+                || program.statements.iter().any(|statement| match statement {
+                    Statement::Assign { variable, .. } => variable == output,
+                });
+            if !defined {
+                error(&format!("Output variable '{}' is not defined.", output));
+            }
+        }
+
+        let outputs: BTreeSet<Variable> = self.outputs.iter().map(|output| Variable::Named(output.clone())).collect();
+        let function = lower(program);
+        let cfg = Cfg::from_function(&function);
+        let analysis = Liveness::new(outputs);
+        let solution = solve(&analysis, &cfg);
+        let facts = instruction_facts(&analysis, &cfg, &solution, 0);
+
+        // The instructions of statement i are ranges[i]
+        let ranges = statement_ranges(&function);
+        assert_eq!(ranges.len(), program.statements.len(), "one range per statement");
+
+        // The variables that kept bindings after the current point read
+        let mut live = facts[function.instructions.len()].clone();
+        let mut kept = vec![false; program.statements.len()];
+        for (index, range) in ranges.into_iter().enumerate().rev() {
+            let Statement::Assign { variable, expression } = &program.statements[index];
+            kept[index] = live.contains(&Variable::Named(variable.clone())) || may_panic(self.overflow, expression);
+            if kept[index] {
+                for position in range.rev() {
+                    analysis.transfer(Location { block: 0, index: position }, &function.instructions[position], &mut live);
+                }
+            }
+        }
+
+        let statements = std::mem::take(&mut program.statements);
+        program.statements = statements
+            .into_iter()
+            .zip(kept)
+            .filter_map(|(statement, kept)| kept.then_some(statement))
+            .collect();
+    }
+}
+
@@ -1,3 +1,11 @@
 pub mod visitor;
 pub mod variable_checker;
-pub mod constprop;
\ No newline at end of file
+pub mod constprop;
+pub mod reassociate;
+pub mod simplify;
+pub mod dead_code;
+pub mod cse;
+pub mod copyprop;
+pub mod strength;
+pub mod sccp;
+pub mod ranges;
\ No newline at end of file
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use crate::ast::*;
+use crate::visitors::visitor::Visitor;
+use crate::messages::*;
+use crate::arithmetic::*;
+use crate::dataflow::framework::{instruction_facts, solve};
+use crate::dataflow::intervals::{Interval, Intervals, Verdict};
+use crate::ir::cfg::Cfg;
+use crate::ir::instruction::{Instruction, Operand, Variable};
+use crate::ir::lift::operation;
+use crate::ir::lower::lower;
+
+// Pass that checks for overflows with the interval analysis of dataflow::intervals
+// 	- Unlike ConstantPropagation, operations on inputs are checked too, e.g a * 2u8
+// 	  is in [0, 510] and may overflow when a is any u8
+// 	- Operations that may panic are reported with a warning
+// 	- Operations that always panic are reported like ConstantPropagation reports them:
+// 	  an error with --overflow=error, a warning otherwise
+// 	- Operations that are proven safe are not reported. The program is left unchanged.
+pub struct RangeAnalysis {
+    overflow: OverflowMode,
+    inputs: Vec<(String, Interval)>,
+}
+
+impl RangeAnalysis {
+    pub fn new(overflow: OverflowMode) -> Self {
+        RangeAnalysis {
+            overflow,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn with_input_range(mut self, name: &str, range: Interval) -> Self {
+        self.inputs.push((name.to_string(), range));
+        self
+    }
+}
+
+// Parses the range of an input, e.g a=0..10 or a=0..=9
+pub fn parse_input_range(text: &str) -> Result<(String, Interval), String> {
+    let invalid = || format!("Invalid input range '{}', expected e.g a=0..10 or a=0..=9.", text);
+
+    let (name, range) = text.split_once('=').ok_or_else(invalid)?;
+    let (lo, hi, inclusive) = match range.split_once("..=") {
+        Some((lo, hi)) => (lo, hi, true),
+        None => {
+            let (lo, hi) = range.split_once("..").ok_or_else(invalid)?;
+            (lo, hi, false)
+        }
+    };
+    let lo: i32 = lo.trim().parse().map_err(|_| invalid())?;
+    let hi: i32 = hi.trim().parse().map_err(|_| invalid())?;
+    let hi = if inclusive { hi } else { hi - 1 };
+
+    let range = Interval::new(lo, hi);
+    if name.trim().is_empty() || lo > hi {
+        return Err(invalid());
+    }
+    if !range.fits() {
+        return Err(format!("The input range '{}' is out of the range of u8.", text));
+    }
+    Ok((name.trim().to_string(), range))
+}
+
+impl Visitor for RangeAnalysis {
+    fn visit_program(&mut self, program: &mut Program) {
+        let mut intervals = Intervals::new(self.overflow);
+        for (name, range) in &self.inputs {
+            if !program.inputs.iter().any(|input| input.name == *name) {
+                error(&format!("Unknown input '{}' in an input range.", name));
+            }
+            intervals = intervals.with_input(name, *range);
+        }
+
+        let function = lower(program);
+        let cfg = Cfg::from_function(&function);
+        let solution = solve(&intervals, &cfg);
+        // Programs have no control flow, so their instructions are in the entry block
+        let facts = instruction_facts(&intervals, &cfg, &solution, 0);
+
+        // The source expression of every temporary, to describe the operations that use it
+        let mut expressions: HashMap<Variable, Expression> = HashMap::new();
+        let expression = |expressions: &HashMap<Variable, Expression>, operand: &Operand| match operand {
+            Operand::Constant(val) => Expression::Value(Box::new(Value::Integer(*val))),
+            Operand::Variable(Variable::Named(name)) => Expression::Value(Box::new(Value::Identifier(name.clone()))),
+            Operand::Variable(variable) => expressions[variable].clone(),
+        };
+
+        for (index, instruction) in function.instructions.iter().enumerate() {
+            let Instruction::Binary { destination, operator, arithmetic, left, right } = instruction else {
+                continue;
+            };
+            let description = operation(
+                operator,
+                arithmetic,
+                expression(&expressions, left),
+                expression(&expressions, right),
+            );
+            expressions.insert(destination.clone(), description.clone());
+
+            let Some(outcome) = intervals.outcome(instruction, &facts[index]) else {
+                continue;
+            };
+            let (panic, always) = match outcome.verdict {
+                Verdict::Safe => continue,
+                Verdict::MayPanic(panic) => (panic, false),
+                Verdict::AlwaysPanics(panic) => (panic, true),
+            };
+
+            // Lowering stores every operation of an initializer in a temporary, except the last one
+            let variable = function.instructions[index..]
+                .iter()
+                .find_map(|instruction| match instruction.destination() {
+                    Variable::Named(name) => Some(name.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default();
+
+            let right = facts[index].operand(right).expect("has an outcome");
+            let (may, does, detail) = match operator {
+                Operator::Divide => ("may divide by zero", "always divides by zero", format!("divides by {}", right)),
+                Operator::ShiftLeft | Operator::ShiftRight => ("may overflow", "always overflows", format!("shifts by {}", right)),
+                _ => {
+                    let exact = outcome.exact.expect("only divisions and shifts have no exact range");
+                    ("may overflow", "always overflows", format!("is in {}", exact))
+                }
+            };
+            let message = format!("{} {} (in the initializer of '{}')", description, detail, variable);
+
+            if !always {
+                warn(&format!("This operation {}: {}", may, message));
+            } else if self.overflow == OverflowMode::Error {
+                error(&format!("This operation {}: {}", does, message));
+            } else {
+                warn(&format!("This operation {} and will panic at runtime with '{}': {}", does, panic.message(), message));
+            }
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use crate::ast::*;
+use crate::visitors::visitor::Visitor;
+use crate::visitors::visitor::NodeAccept;
+use crate::arithmetic::*;
+
+// Visitor that reassociates chains of binary operations to fold their constant terms
+// 	- A chain is a left associative sequence of operations of the same kind, e.g x + 5u8 - 2u8
+// 		- additive chains use + and -, multiplicative chains use *, division chains use /
+// 	- The terms of a chain are visited first, so that nested chains are reassociated as well
+// 	- Constant terms are gathered into a single term, as long as the result stays equivalent:
+// 		- With wrapping overflow every reordering is equivalent, so all constants of
+// 		  additive and multiplicative chains are gathered, e.g x + 5u8 - 2u8 becomes x + 3u8
+// 		- Otherwise overflow has to happen for exactly the same inputs as before:
+// 			- additions only: every partial sum is at most the total, so all constants are gathered
+// 			- subtractions only: every partial difference is at least the result, so all subtracted constants are gathered
+// 			- multiplications: only consecutive non-zero constants are gathered,
+// 			  since a later zero factor could hide an overflow of a partial product
+// 		- Divisions truncate, so x / a / b is always x / (a * b)
+// 		- Shifts compose, so x << a << b is x << (a + b) as long as a + b is less than 8
+// 		- Bitwise operations never overflow, so all constants of &, | and ^ chains are gathered
+// 		  into a last term, e.g x & 12u8 & y & 10u8 becomes x & y & 8u8
+// 	- Chains whose constants cannot be gathered are left untouched
+pub struct Reassociation {
+    overflow: OverflowMode,
+}
+
+impl Reassociation {
+    pub fn new(overflow: OverflowMode) -> Self {
+        Reassociation { overflow }
+    }
+
+    fn reassociate_additive(&self, terms: &[(Operator, Expression)]) -> Option<Expression> {
+        let constants = terms.iter().filter(|(_, term)| term.as_integer().is_some()).count();
+        if constants < 2 {
+            return None;
+        }
+
+        let additions_only = terms.iter().all(|(operator, _)| *operator == Operator::Add);
+        let subtractions_only = terms[1..].iter().all(|(operator, _)| *operator == Operator::Subtract);
+
+        if self.overflow == OverflowMode::Wrap {
+            // The sum of all constant terms, modulo 256
+            let mut constant: u8 = 0;
+            let mut variables = Vec::new();
+            for (operator, term) in terms {
+                match (term.as_integer(), operator) {
+                    (Some(val), Operator::Add) => constant = constant.wrapping_add(val),
+                    (Some(val), _) => constant = constant.wrapping_sub(val),
+                    (None, _) => variables.push((operator.clone(), term.clone())),
+                }
+            }
+
+            // Subtracting the constant reads better when it is "negative", e.g x - 3u8 instead of x + 253u8
+            return match variables.first() {
+                Some((Operator::Add, _)) if constant > 128 => {
+                    variables.push((Operator::Subtract, Expression::integer(constant.wrapping_neg())));
+                    Some(build_chain(variables))
+                }
+                Some((Operator::Add, _)) => {
+                    variables.push((Operator::Add, Expression::integer(constant)));
+                    Some(build_chain(variables))
+                }
+                // Only subtracted variables are left, so the constant leads the chain
+                _ => {
+                    variables.insert(0, (Operator::Add, Expression::integer(constant)));
+                    Some(build_chain(variables))
+                }
+            };
+        }
+
+        if additions_only {
+            let mut constant: u8 = 0;
+            let mut variables = Vec::new();
+            for (operator, term) in terms {
+                match term.as_integer() {
+                    // The sum always overflows, reassociating would change where
+                    Some(val) => constant = constant.checked_add(val)?,
+                    None => variables.push((operator.clone(), term.clone())),
+                }
+            }
+            variables.push((Operator::Add, Expression::integer(constant)));
+            return Some(build_chain(variables));
+        }
+
+        if subtractions_only {
+            let mut subtracted: u8 = 0;
+            let mut variables = Vec::new();
+            for (operator, term) in terms[1..].iter() {
+                match term.as_integer() {
+                    Some(val) => subtracted = subtracted.checked_add(val)?,
+                    None => variables.push((operator.clone(), term.clone())),
+                }
+            }
+
+            return match terms[0].1.as_integer() {
+                // The result is negative for every input, leave it as is
+                Some(head) => {
+                    variables.insert(0, (Operator::Add, Expression::integer(head.checked_sub(subtracted)?)));
+                    Some(build_chain(variables))
+                }
+                None => {
+                    variables.insert(0, terms[0].clone());
+                    variables.push((Operator::Subtract, Expression::integer(subtracted)));
+                    Some(build_chain(variables))
+                }
+            };
+        }
+
+        None
+    }
+
+    fn reassociate_multiplicative(&self, terms: &[(Operator, Expression)]) -> Option<Expression> {
+        if self.overflow == OverflowMode::Wrap {
+            let constants = terms.iter().filter(|(_, term)| term.as_integer().is_some()).count();
+            if constants < 2 {
+                return None;
+            }
+
+            let mut constant: u8 = 1;
+            let mut variables = Vec::new();
+            for (operator, term) in terms {
+                match term.as_integer() {
+                    Some(val) => constant = constant.wrapping_mul(val),
+                    None => variables.push((operator.clone(), term.clone())),
+                }
+            }
+            variables.push((Operator::Multiply, Expression::integer(constant)));
+            return Some(build_chain(variables));
+        }
+
+        merge_consecutive(terms, |product, val| {
+            if product == 0 || val == 0 {
+                None
+            } else {
+                product.checked_mul(val)
+            }
+        })
+    }
+}
+
+impl Visitor for Reassociation {
+    fn visit_program(&mut self, program: &mut Program) {
+        for statement in &mut program.statements {
+            statement.accept(self);
+        }
+    }
+
+    fn visit_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Assign { expression, .. } => {
+                expression.accept(self);
+            }
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &mut Expression) {
+        match expression {
+            Expression::Binary { operator, .. } => {
+                let operator = operator.clone();
+                let mut terms = Vec::new();
+                flatten(expression, &operator, &mut terms);
+
+                // Nested chains are reassociated first
+                for (_, term) in &mut terms {
+                    term.accept(self);
+                }
+
+                let reassociated = match operator {
+                    Operator::Add | Operator::Subtract => self.reassociate_additive(&terms),
+                    Operator::Multiply => self.reassociate_multiplicative(&terms),
+                    // Truncating divisions compose: (x / a) / b == x / (a * b)
+                    Operator::Divide => merge_consecutive(&terms, |product, val| {
+                        if product == 0 || val == 0 {
+                            None
+                        } else {
+                            product.checked_mul(val)
+                        }
+                    }),
+                    Operator::ShiftLeft | Operator::ShiftRight => merge_consecutive(&terms, |bits, val| {
+                        bits.checked_add(val).filter(|sum| *sum < 8)
+                    }),
+                    Operator::BitAnd | Operator::BitOr | Operator::BitXor => reassociate_bitwise(&operator, &terms),
+                };
+
+                *expression = reassociated.unwrap_or_else(|| build_chain(terms));
+            }
+            Expression::Value(value) => {
+                value.accept(self);
+            }
+        }
+    }
+
+    fn visit_value(&mut self, value: &mut Value) {
+        match value {
+            Value::Expression(expr) => {
+                expr.accept(self);
+            }
+            Value::MethodCall { receiver, argument, .. } => {
+                receiver.accept(self);
+                argument.accept(self);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Whether two operators can be part of the same chain
+fn same_chain(first: &Operator, second: &Operator) -> bool {
+    match first {
+        Operator::Add | Operator::Subtract => matches!(second, Operator::Add | Operator::Subtract),
+        Operator::Multiply => *second == Operator::Multiply,
+        other => second == other,
+    }
+}
+
+// Collects the terms of the chain rooted at `expression` along its left spine.
+// The first term is always paired with Operator::Add (or the chain's own operator for non-additive chains).
+fn flatten(expression: &Expression, chain: &Operator, terms: &mut Vec<(Operator, Expression)>) {
+    match expression {
+        Expression::Binary { left, operator, right } if same_chain(chain, operator) => {
+            match left {
+                Value::Expression(inner) => flatten(inner, chain, terms),
+                _ => terms.push((first_operator(chain), Expression::Value(Box::new(left.clone())))),
+            }
+            terms.push((operator.clone(), (**right).clone()));
+        }
+        _ => terms.push((first_operator(chain), expression.clone())),
+    }
+}
+
+fn first_operator(chain: &Operator) -> Operator {
+    match chain {
+        Operator::Add | Operator::Subtract => Operator::Add,
+        other => other.clone(),
+    }
+}
+
+// Rebuilds a left associative chain from its terms. The operator of the first term is ignored.
+fn build_chain(terms: Vec<(Operator, Expression)>) -> Expression {
+    let mut terms = terms.into_iter();
+    let (_, mut chain) = terms.next().expect("a chain has at least one term");
+
+    for (operator, term) in terms {
+        let left = match chain {
+            Expression::Value(value) => *value,
+            binary => Value::Expression(Box::new(binary)),
+        };
+        chain = Expression::Binary {
+            left,
+            operator,
+            right: Box::new(term),
+        };
+    }
+
+    chain
+}
+
+// Gathers every constant of a bitwise chain into a last term.
+// Returns None if there are less than two constants.
+fn reassociate_bitwise(operator: &Operator, terms: &[(Operator, Expression)]) -> Option<Expression> {
+    let constants: Vec<u8> = terms.iter().filter_map(|(_, term)| term.as_integer()).collect();
+    if constants.len() < 2 {
+        return None;
+    }
+
+    let constant = constants[1..].iter().fold(constants[0], |constant, val| match operator {
+        Operator::BitAnd => constant & val,
+        Operator::BitOr => constant | val,
+        _ => constant ^ val,
+    });
+    let mut variables: Vec<(Operator, Expression)> = terms
+        .iter()
+        .filter(|(_, term)| term.as_integer().is_none())
+        .map(|(_, term)| (operator.clone(), term.clone()))
+        .collect();
+    if variables.is_empty() {
+        return Some(Expression::integer(constant));
+    }
+    variables.push((operator.clone(), Expression::integer(constant)));
+    Some(build_chain(variables))
+}
+
+// Merges runs of consecutive constants after the first term with `merge`.
+// Returns None if nothing was merged.
+fn merge_consecutive(terms: &[(Operator, Expression)], merge: impl Fn(u8, u8) -> Option<u8>) -> Option<Expression> {
+    let mut merged: Vec<(Operator, Expression)> = vec![terms[0].clone()];
+    let mut changed = false;
+
+    for (operator, term) in terms[1..].iter() {
+        let previous = merged.last().and_then(|(_, last)| last.as_integer());
+        match (previous, term.as_integer()) {
+            (Some(product), Some(val)) if merged.len() > 1 => {
+                if let Some(result) = merge(product, val) {
+                    merged.last_mut().unwrap().1 = Expression::integer(result);
+                    changed = true;
+                    continue;
+                }
+                merged.push((operator.clone(), term.clone()));
+            }
+            _ => merged.push((operator.clone(), term.clone())),
+        }
+    }
+
+    if changed {
+        Some(build_chain(merged))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,68 @@
+use crate::ast::*;
+use crate::visitors::visitor::Visitor;
+use crate::messages::*;
+use crate::arithmetic::*;
+use crate::ir::cfg::Cfg;
+use crate::ir::instruction::Variable;
+use crate::ir::lift::lift;
+use crate::ir::lower::lower;
+use crate::ir::sccp::Sccp;
+use crate::ir::ssa::{into_ssa, out_of_ssa};
+
+// Pass that performs sparse conditional constant propagation on the IR of the program
+// 	- The program is lowered to three-address code, then to a control-flow graph in SSA form
+// 	- Constants are propagated by ir::sccp, with the semantics of the overflow mode
+// 	- Operations that always panic are reported like ConstantPropagation reports them:
+// 	  an error with --overflow=error, a warning otherwise
+// 	- The graph is then taken out of SSA form and lifted back to a program
+pub struct SparseConditionalConstantPropagation {
+    overflow: OverflowMode,
+}
+
+impl SparseConditionalConstantPropagation {
+    pub fn new(overflow: OverflowMode) -> Self {
+        SparseConditionalConstantPropagation { overflow }
+    }
+}
+
+impl Visitor for SparseConditionalConstantPropagation {
+    fn visit_program(&mut self, program: &mut Program) {
+        let function = lower(program);
+        let mut cfg = Cfg::from_function(&function);
+        into_ssa(&mut cfg);
+
+        let sccp = Sccp::analyze(&cfg, self.overflow);
+        for panic in sccp.panics(&cfg) {
+            // Lowering stores every operation of an initializer in a temporary, except the last one
+            let index = function
+                .instructions
+                .iter()
+                .position(|instruction| *instruction == panic.instruction)
+                .unwrap_or(0);
+            let variable = function.instructions[index..]
+                .iter()
+                .find_map(|instruction| match instruction.destination() {
+                    Variable::Named(name) => Some(name.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default();
+
+            let message = format!(
+                "'{}': {} (in the initializer of '{}')",
+                panic.panic.message(),
+                panic.description,
+                variable
+            );
+            if self.overflow == OverflowMode::Error {
+                error(&format!("Constant evaluation panicked with {}", message));
+            }
+            warn(&format!("This operation will panic at runtime with {}", message));
+        }
+
+        sccp.rewrite(&mut cfg);
+        out_of_ssa(&mut cfg);
+
+        let function = cfg.to_function().expect("programs have no control flow");
+        *program = lift(&function);
+    }
+}
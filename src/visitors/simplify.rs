@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use crate::ast::*;
+use crate::visitors::visitor::Visitor;
+use crate::visitors::visitor::NodeAccept;
+use crate::arithmetic::*;
+use crate::dataflow::known_bits::{operation_bits, Bits};
+use crate::ir::instruction::Arithmetic;
+
+// Visitor that performs peephole simplification of algebraic identities
+// 	- Operands are simplified first, so identities are applied bottom-up
+// 	- For a binary operation or an integer method call (e.g x.wrapping_add(0u8)):
+// 		- x + 0, 0 + x, x - 0, x * 1, 1 * x, x / 1, x << 0 and x >> 0 become x
+// 		- x * 0, 0 * x and x - x become 0, as long as evaluating x cannot panic
+// 		- x & 0 becomes 0, x & x, x | x, x | 0 and x ^ 0 become x, and x ^ x becomes 0
+// 		- 0 / x becomes 0 and x / x becomes 1, only if x is known to be non-zero
+// 		- the overflow flag of an operation that cannot overflow becomes 0
+// 	- The known bits of every expression and binding (see dataflow::known_bits) prove more:
+// 		- an operation on variables whose bits are all known becomes a constant, e.g (x << 1u8) & 1u8 becomes 0
+// 		- x & mask becomes x if the mask keeps every bit of x that may be one,
+// 		  and x | mask becomes x if every bit of the mask is known to be one in x
+// 		- a divisor with a bit known to be one is non-zero
+// 	- Operands that are dropped must not panic, otherwise the panic would be lost
+pub struct Simplifier {
+    overflow: OverflowMode,
+    // The known bits of the bindings simplified so far
+    bindings: HashMap<String, Bits>,
+}
+
+// The result of simplifying an operation
+enum Simplified {
+    Left,
+    Right,
+    Integer(u8),
+}
+
+impl Simplifier {
+    pub fn new(overflow: OverflowMode) -> Self {
+        Simplifier {
+            overflow,
+            bindings: HashMap::new(),
+        }
+    }
+
+    fn pure(&self, expression: &Expression) -> bool {
+        !may_panic(self.overflow, expression)
+    }
+
+    // Whether an expression is known to never evaluate to zero
+    fn non_zero(&self, expression: &Expression) -> bool {
+        self.bits(expression).ones != 0
+    }
+
+    // The bits of the value of an expression that are known
+    fn bits(&self, expression: &Expression) -> Bits {
+        match expression {
+            Expression::Binary { left, operator, right } => {
+                operation_bits(self.overflow, operator, &Arithmetic::Panicking, self.value_bits(left), self.bits(right))
+            }
+            Expression::Value(value) => self.value_bits(value),
+        }
+    }
+
+    fn value_bits(&self, value: &Value) -> Bits {
+        match value {
+            Value::Integer(val) => Bits::constant(*val),
+            Value::Identifier(name) => self.bindings.get(name).copied().unwrap_or(Bits::unknown()),
+            Value::Expression(expression) => self.bits(expression),
+            Value::MethodCall { receiver, method, argument } => operation_bits(
+                self.overflow,
+                &method.operator,
+                &Arithmetic::Method(method.kind.clone()),
+                self.value_bits(receiver),
+                self.bits(argument),
+            ),
+        }
+    }
+
+    // The value of an operation on variables whose bits are all known, if it cannot panic.
+    // Operations on constants only are left to constant propagation.
+    fn known_value(&self, expression: &Expression) -> Option<u8> {
+        if !reads_variable(expression) || !self.pure(expression) {
+            return None;
+        }
+        self.bits(expression).value()
+    }
+
+    fn simplify_operation(&self, operator: &Operator, left: &Expression, right: &Expression) -> Option<Simplified> {
+        let left_val = left.as_integer();
+        let right_val = right.as_integer();
+        let left_bits = self.bits(left);
+        let right_bits = self.bits(right);
+
+        match operator {
+            Operator::Add if right_val == Some(0) => Some(Simplified::Left),
+            Operator::Add if left_val == Some(0) => Some(Simplified::Right),
+            Operator::Subtract if right_val == Some(0) => Some(Simplified::Left),
+            Operator::Subtract if same(left, right) && self.pure(left) => Some(Simplified::Integer(0)),
+            Operator::Multiply if right_val == Some(1) => Some(Simplified::Left),
+            Operator::Multiply if left_val == Some(1) => Some(Simplified::Right),
+            Operator::Multiply if right_val == Some(0) && self.pure(left) => Some(Simplified::Integer(0)),
+            Operator::Multiply if left_val == Some(0) && self.pure(right) => Some(Simplified::Integer(0)),
+            Operator::Divide if right_val == Some(1) => Some(Simplified::Left),
+            Operator::ShiftLeft | Operator::ShiftRight if right_val == Some(0) => Some(Simplified::Left),
+            Operator::Divide if left_val == Some(0) && self.non_zero(right) && self.pure(right) => Some(Simplified::Integer(0)),
+            Operator::Divide if same(left, right) && self.non_zero(right) && self.pure(right) => Some(Simplified::Integer(1)),
+            Operator::BitAnd if right_val == Some(0) && self.pure(left) => Some(Simplified::Integer(0)),
+            Operator::BitAnd if left_val == Some(0) && self.pure(right) => Some(Simplified::Integer(0)),
+            Operator::BitXor if same(left, right) && self.pure(left) => Some(Simplified::Integer(0)),
+            Operator::BitAnd | Operator::BitOr if same(left, right) && self.pure(right) => Some(Simplified::Left),
+            Operator::BitOr | Operator::BitXor if right_val == Some(0) => Some(Simplified::Left),
+            Operator::BitOr | Operator::BitXor if left_val == Some(0) => Some(Simplified::Right),
+            // The mask keeps every bit that may be one
+            Operator::BitAnd if right_bits.ones | left_bits.zeros == u8::MAX && self.pure(right) => Some(Simplified::Left),
+            Operator::BitAnd if left_bits.ones | right_bits.zeros == u8::MAX && self.pure(left) => Some(Simplified::Right),
+            // Every bit of the mask is already one
+            Operator::BitOr if right_bits.max() & !left_bits.ones == 0 && self.pure(right) => Some(Simplified::Left),
+            Operator::BitOr if left_bits.max() & !right_bits.ones == 0 && self.pure(left) => Some(Simplified::Right),
+            _ => None,
+        }
+    }
+
+    fn simplify_method(&self, method: &Method, left: &Expression, right: &Expression) -> Option<Simplified> {
+        let simplified = self.simplify_operation(&method.operator, left, right)?;
+
+        match method.kind {
+            // Operations that simplify cannot overflow
+            MethodKind::OverflowFlag if self.pure(left) && self.pure(right) => Some(Simplified::Integer(0)),
+            MethodKind::OverflowFlag => None,
+            _ => Some(simplified),
+        }
+    }
+}
+
+impl Visitor for Simplifier {
+    fn visit_program(&mut self, program: &mut Program) {
+        self.bindings.clear();
+        for statement in &mut program.statements {
+            statement.accept(self);
+        }
+    }
+
+    fn visit_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Assign { variable, expression } => {
+                expression.accept(self);
+                self.bindings.insert(variable.clone(), self.bits(expression));
+            }
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &mut Expression) {
+        match expression {
+            Expression::Binary { left, operator, right } => {
+                left.accept(self);
+                right.accept(self);
+
+                let left_expression = Expression::Value(Box::new(left.clone()));
+                match self.simplify_operation(operator, &left_expression, right) {
+                    Some(Simplified::Left) => *expression = left_expression.unwrap_parenthesis(),
+                    Some(Simplified::Right) => *expression = (**right).clone().unwrap_parenthesis(),
+                    Some(Simplified::Integer(val)) => *expression = Expression::Value(Box::new(Value::Integer(val))),
+                    None => {
+                        if let Some(val) = self.known_value(expression) {
+                            *expression = Expression::Value(Box::new(Value::Integer(val)));
+                        }
+                    }
+                }
+            }
+            Expression::Value(value) => {
+                value.accept(self);
+            }
+        }
+    }
+
+    fn visit_value(&mut self, value: &mut Value) {
+        match value {
+            Value::Expression(expr) => {
+                expr.accept(self);
+
+                // A parenthesized single value does not need its parenthesis
+                if let Expression::Value(inner) = &**expr {
+                    if !matches!(**inner, Value::MethodCall { .. }) {
+                        *value = (**inner).clone();
+                    }
+                }
+            }
+            Value::MethodCall { receiver, method, argument } => {
+                receiver.accept(self);
+                argument.accept(self);
+
+                let receiver_expression = Expression::Value(receiver.clone());
+                match self.simplify_method(method, &receiver_expression, argument) {
+                    Some(Simplified::Left) => *value = (**receiver).clone(),
+                    Some(Simplified::Right) => {
+                        *value = match (**argument).clone().unwrap_parenthesis() {
+                            Expression::Value(inner) => *inner,
+                            binary => Value::Expression(Box::new(binary)),
+                        };
+                    }
+                    Some(Simplified::Integer(val)) => *value = Value::Integer(val),
+                    None => {
+                        if let Some(val) = self.known_value(&Expression::Value(Box::new(value.clone()))) {
+                            *value = Value::Integer(val);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Two expressions are the same if they are equal once their outer parenthesis are removed
+fn same(first: &Expression, second: &Expression) -> bool {
+    first.clone().unwrap_parenthesis() == second.clone().unwrap_parenthesis()
+}
+
+fn reads_variable(expression: &Expression) -> bool {
+    match expression {
+        Expression::Binary { left, right, .. } => value_reads_variable(left) || reads_variable(right),
+        Expression::Value(value) => value_reads_variable(value),
+    }
+}
+
+fn value_reads_variable(value: &Value) -> bool {
+    match value {
+        Value::Integer(_) => false,
+        Value::Identifier(_) => true,
+        Value::Expression(expression) => reads_variable(expression),
+        Value::MethodCall { receiver, argument, .. } => value_reads_variable(receiver) || reads_variable(argument),
+    }
+}
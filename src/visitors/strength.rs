@@ -0,0 +1,377 @@
+use std::collections::HashSet;
+use crate::ast::*;
+use crate::visitors::visitor::Visitor;
+use crate::visitors::visitor::NodeAccept;
+use crate::arithmetic::*;
+
+// The cost of each kind of operation on a target, used to decide whether a rewrite pays off
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct CostModel {
+    pub add: u32,
+    pub multiply: u32,
+    pub divide: u32,
+    pub shift: u32,
+}
+
+impl CostModel {
+    pub const NAMES: [&'static str; 3] = ["default", "no-div", "uniform"];
+
+    pub fn from_name(name: &str) -> Option<CostModel> {
+        match name {
+            // A typical CPU: multiplication is a few cycles, division a few tens
+            "default" => Some(CostModel { add: 1, multiply: 3, divide: 20, shift: 1 }),
+            // A target without a divider, where division is a software routine
+            "no-div" => Some(CostModel { add: 1, multiply: 3, divide: 1000, shift: 1 }),
+            // Every operation costs the same, so nothing is worth rewriting
+            "uniform" => Some(CostModel { add: 1, multiply: 1, divide: 1, shift: 1 }),
+            _ => None,
+        }
+    }
+
+    pub fn operation(&self, operator: &Operator) -> u32 {
+        match operator {
+            // Bitwise operations take a cycle like additions
+            Operator::Add | Operator::Subtract | Operator::BitAnd | Operator::BitOr | Operator::BitXor => self.add,
+            Operator::Multiply => self.multiply,
+            Operator::Divide => self.divide,
+            Operator::ShiftLeft | Operator::ShiftRight => self.shift,
+        }
+    }
+
+    // The cost of evaluating every operation of an expression
+    pub fn expression(&self, expression: &Expression) -> u32 {
+        match expression {
+            Expression::Binary { left, operator, right } => {
+                self.value(left) + self.operation(operator) + self.expression(right)
+            }
+            Expression::Value(value) => self.value(value),
+        }
+    }
+
+    fn value(&self, value: &Value) -> u32 {
+        match value {
+            Value::Expression(expression) => self.expression(expression),
+            Value::MethodCall { receiver, method, argument } => {
+                self.value(receiver) + self.operation(&method.operator) + self.expression(argument)
+            }
+            _ => 0,
+        }
+    }
+}
+
+// Visitor that performs strength reduction, replacing operations with cheaper equivalent ones
+// 	- Operands are reduced first
+// 	- x * 2^k (or 2^k * x) becomes x << k with wrapping overflow. Otherwise it is kept, since a shift
+// 	  would not panic when the multiplication overflows.
+// 		- x * 2 becomes x + x instead if additions are the cheapest, and evaluating x twice cannot panic
+// 	- x / 2^k becomes x >> k
+// 	- x / c, for any other constant c > 1, becomes a multiply-high sequence:
+// 		- q = (x * m) >> (8 + s), for a magic number m found by exhaustively checking every u8
+// 		- the high byte of x * m is computed with u8 operations only, by splitting x and m in nibbles
+// 		- magic numbers that need 9 bits use the "add indicator" form: ((x - t) >> 1 + t) >> (s - 1)
+// 		- none of these operations can overflow
+// 	- A rewrite only happens if the cost model says it is cheaper
+// 	- The operands of a multiply-high sequence are used more than once, so they are bound to
+// 	  temporaries first. The other rewrites keep their operand inside the expression.
+// 		- the temporaries are evaluated before the statement, so the sequence is only used if
+// 		  neither the dividend nor anything evaluated before it in the statement may panic
+pub struct StrengthReduction {
+    overflow: OverflowMode,
+    costs: CostModel,
+    // Every name that is in use, to pick fresh temporaries
+    names: HashSet<String>,
+    // Temporaries to insert before the statement being visited
+    prelude: Vec<Statement>,
+    // Whether nothing evaluated so far in the statement being visited may panic
+    safe: bool,
+}
+
+// A magic number for dividing every u8 by a constant
+#[derive(Clone, Copy)]
+enum Magic {
+    // q = mulhi(x, m) >> s
+    Short { m: u8, s: u8 },
+    // t = mulhi(x, m - 256); q = (((x - t) >> 1) + t) >> (s - 1)
+    Long { m: u8, s: u8 },
+}
+
+impl StrengthReduction {
+    pub fn new(overflow: OverflowMode, costs: CostModel) -> Self {
+        StrengthReduction {
+            overflow,
+            costs,
+            names: HashSet::new(),
+            prelude: Vec::new(),
+            safe: true,
+        }
+    }
+
+    // Binds an expression to a temporary, unless it is an integer or a variable already
+    fn atom(&mut self, expression: Expression) -> Value {
+        match expression.unwrap_parenthesis() {
+            Expression::Value(value) if matches!(*value, Value::Integer(_) | Value::Identifier(_)) => *value,
+            expression => Value::Identifier(self.bind(expression)),
+        }
+    }
+
+    fn bind(&mut self, expression: Expression) -> String {
+        let variable = fresh_name(&mut self.names);
+        self.prelude.push(Statement::Assign {
+            variable: variable.clone(),
+            expression,
+        });
+        variable
+    }
+
+    fn reduce(&mut self, expression: &mut Expression) {
+        let Expression::Binary { left, operator, right } = expression else {
+            return;
+        };
+        let left_expression = Expression::Value(Box::new(left.clone()));
+        let left_val = left_expression.as_integer();
+        let right_val = right.as_integer();
+
+        match operator {
+            Operator::Multiply => {
+                let (operand, factor) = match (left_val, right_val) {
+                    (_, Some(factor)) if is_power_of_two(factor) => (left_expression, factor),
+                    (Some(factor), _) if is_power_of_two(factor) => ((**right).clone(), factor),
+                    _ => return,
+                };
+                // Without wrapping, x << k would not panic when the multiplication overflows,
+                // and additions would panic with another message
+                if self.overflow != OverflowMode::Wrap {
+                    return;
+                }
+
+                // x + x evaluates x twice, which costs it twice
+                let doubling = self.costs.expression(&operand) + self.costs.add;
+                if factor == 2 && doubling < self.costs.shift && doubling < self.costs.multiply && !may_panic(self.overflow, &operand) {
+                    let operand = operand.parenthesize();
+                    *expression = Expression::binary(operand.clone(), Operator::Add, Expression::Value(Box::new(operand)));
+                } else if self.costs.shift < self.costs.multiply {
+                    let bits = factor.trailing_zeros() as u8;
+                    *expression = Expression::binary(operand.parenthesize(), Operator::ShiftLeft, Expression::integer(bits));
+                }
+            }
+            Operator::Divide => {
+                let Some(divisor) = right_val.filter(|divisor| *divisor > 1) else {
+                    return;
+                };
+
+                if is_power_of_two(divisor) {
+                    if self.costs.shift < self.costs.divide {
+                        let bits = divisor.trailing_zeros() as u8;
+                        *expression = Expression::binary(left_expression.parenthesize(), Operator::ShiftRight, Expression::integer(bits));
+                    }
+                    return;
+                }
+
+                let Some(magic) = find_magic(divisor) else {
+                    return;
+                };
+
+                // Price the sequence on a placeholder before committing to it
+                let placeholder = Value::Identifier(String::from("x"));
+                let mut dry_run = StrengthReduction::new(self.overflow, self.costs);
+                let quotient = dry_run.divide_by_magic(placeholder, magic);
+                let cost = dry_run
+                    .prelude
+                    .iter()
+                    .map(|Statement::Assign { expression, .. }| self.costs.expression(expression))
+                    .sum::<u32>()
+                    + self.costs.expression(&quotient);
+
+                if cost < self.costs.divide && self.safe {
+                    let dividend = self.atom(left_expression);
+                    *expression = self.divide_by_magic(dividend, magic);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Emits the statements of a multiply-high division and returns the quotient
+    fn divide_by_magic(&mut self, x: Value, magic: Magic) -> Expression {
+        match magic {
+            Magic::Short { m, s } => {
+                let high = self.multiply_high(&x, m);
+                if s == 0 {
+                    Expression::Value(Box::new(high))
+                } else {
+                    Expression::binary(high, Operator::ShiftRight, Expression::integer(s))
+                }
+            }
+            Magic::Long { m, s } => {
+                let high = self.multiply_high(&x, m);
+                let difference = self.bind(Expression::binary(x, Operator::Subtract, Expression::Value(Box::new(high.clone()))));
+                let half = Expression::binary(Value::Identifier(difference), Operator::ShiftRight, Expression::integer(1));
+                let sum = self.bind(Expression::binary(Value::Expression(Box::new(half)), Operator::Add, Expression::Value(Box::new(high))));
+                if s == 1 {
+                    Expression::Value(Box::new(Value::Identifier(sum)))
+                } else {
+                    Expression::binary(Value::Identifier(sum), Operator::ShiftRight, Expression::integer(s - 1))
+                }
+            }
+        }
+    }
+
+    // Emits the statements computing the high byte of x * m, and returns it.
+    // With x = 16 * xh + xl and m = 16 * mh + ml, every partial product fits in a u8.
+    fn multiply_high(&mut self, x: &Value, m: u8) -> Value {
+        let (mh, ml) = (m >> 4, m & 15);
+
+        let xh = self.bind(Expression::binary(x.clone(), Operator::ShiftRight, Expression::integer(4)));
+        let shifted = Expression::binary(Value::Identifier(xh.clone()), Operator::ShiftLeft, Expression::integer(4));
+        let xl = self.bind(Expression::binary(x.clone(), Operator::Subtract, Expression::Value(Box::new(Value::Expression(Box::new(shifted))))));
+
+        let mut product = |nibble: &String, factor: u8| -> Option<String> {
+            match factor {
+                0 => None,
+                1 => Some(nibble.clone()),
+                _ => Some(self.bind(Expression::binary(Value::Identifier(nibble.clone()), Operator::Multiply, Expression::integer(factor)))),
+            }
+        };
+        let low_low = product(&xl, ml);
+        let low_high = product(&xl, mh);
+        let high_low = product(&xh, ml);
+        let high_high = product(&xh, mh);
+
+        // The carry out of the low byte: the high nibble of the low product plus
+        // the low nibbles of the middle products, divided by 16
+        let mut carry_terms = Vec::new();
+        if let Some(low_low) = &low_low {
+            carry_terms.push(Expression::binary(Value::Identifier(low_low.clone()), Operator::ShiftRight, Expression::integer(4)));
+        }
+        for middle in [&low_high, &high_low].into_iter().flatten() {
+            let high_nibble = Expression::binary(Value::Identifier(middle.clone()), Operator::ShiftRight, Expression::integer(4));
+            let shifted = Expression::binary(Value::Expression(Box::new(high_nibble)), Operator::ShiftLeft, Expression::integer(4));
+            carry_terms.push(Expression::binary(Value::Identifier(middle.clone()), Operator::Subtract, Expression::Value(Box::new(Value::Expression(Box::new(shifted))))));
+        }
+        let carry_sum = sum(carry_terms);
+        let carry = Expression::binary(Value::Expression(Box::new(carry_sum)), Operator::ShiftRight, Expression::integer(4));
+        let carry = self.bind(carry);
+
+        let mut high_terms = Vec::new();
+        if let Some(high_high) = &high_high {
+            high_terms.push(Expression::Value(Box::new(Value::Identifier(high_high.clone()))));
+        }
+        for middle in [&low_high, &high_low].into_iter().flatten() {
+            high_terms.push(Expression::binary(Value::Identifier(middle.clone()), Operator::ShiftRight, Expression::integer(4)));
+        }
+        high_terms.push(Expression::Value(Box::new(Value::Identifier(carry))));
+
+        Value::Identifier(self.bind(sum(high_terms)))
+    }
+}
+
+impl Visitor for StrengthReduction {
+    fn visit_program(&mut self, program: &mut Program) {
+        self.names = program.inputs.iter().map(|input| input.name.clone()).collect();
+        for statement in &program.statements {
+            let Statement::Assign { variable, .. } = statement;
+            self.names.insert(variable.clone());
+        }
+
+        let mut statements = Vec::new();
+        for mut statement in program.statements.drain(..) {
+            statement.accept(self);
+            statements.append(&mut self.prelude);
+            statements.push(statement);
+        }
+        program.statements = statements;
+    }
+
+    fn visit_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Assign { expression, .. } => {
+                self.safe = true;
+                expression.accept(self);
+            }
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &mut Expression) {
+        match expression {
+            Expression::Binary { left, right, .. } => {
+                left.accept(self);
+                right.accept(self);
+                self.reduce(expression);
+                self.safe = self.safe && !may_panic(self.overflow, expression);
+            }
+            Expression::Value(value) => {
+                value.accept(self);
+            }
+        }
+    }
+
+    fn visit_value(&mut self, value: &mut Value) {
+        match value {
+            Value::Expression(expr) => {
+                expr.accept(self);
+            }
+            Value::MethodCall { receiver, argument, .. } => {
+                receiver.accept(self);
+                argument.accept(self);
+                self.safe = self.safe && !value_may_panic(self.overflow, value);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Finds a magic number m and a shift s such that (x * m) >> (8 + s) == x / divisor for every u8 x
+fn find_magic(divisor: u8) -> Option<Magic> {
+    let divisor = divisor as u32;
+    for s in 0..=8u32 {
+        let m = (1u32 << (8 + s)).div_ceil(divisor);
+
+        if m < 256 {
+            let exact = (0..=255u32).all(|x| (x * m) >> (8 + s) == x / divisor);
+            if exact {
+                return Some(Magic::Short { m: m as u8, s: s as u8 });
+            }
+        } else if m < 512 && s > 0 {
+            let exact = (0..=255u32).all(|x| {
+                let t = (x * (m - 256)) >> 8;
+                (((x - t) >> 1) + t) >> (s - 1) == x / divisor
+            });
+            if exact {
+                return Some(Magic::Long { m: (m - 256) as u8, s: s as u8 });
+            }
+        }
+    }
+    None
+}
+
+fn is_power_of_two(val: u8) -> bool {
+    val > 1 && val.is_power_of_two()
+}
+
+// Adds up terms, left to right
+fn sum(terms: Vec<Expression>) -> Expression {
+    let mut terms = terms.into_iter();
+    let mut total = terms.next().expect("at least one term");
+    for term in terms {
+        let left = match total {
+            Expression::Value(value) => *value,
+            binary => Value::Expression(Box::new(binary)),
+        };
+        total = Expression::Binary {
+            left,
+            operator: Operator::Add,
+            right: Box::new(wrap_lower_precedence(term)),
+        };
+    }
+    total
+}
+
+// Shifts bind looser than additions, so they need parenthesis on the right of one
+fn wrap_lower_precedence(expression: Expression) -> Expression {
+    match expression {
+        Expression::Binary { ref operator, .. } if operator.precedence() < Operator::Add.precedence() => {
+            Expression::Value(Box::new(Value::Expression(Box::new(expression))))
+        }
+        expression => expression,
+    }
+}
@@ -1,83 +1,66 @@
 use std::collections::HashSet;
 use crate::ast::*;
 use crate::visitors::visitor::Visitor;
-use crate::visitors::visitor::NodeAccept;
+use crate::dataflow::framework::{instruction_facts, solve};
+use crate::dataflow::reaching::ReachingDefinitions;
+use crate::ir::cfg::Cfg;
+use crate::ir::instruction::{Operand, Variable};
+use crate::ir::lower::{lower, statement_ranges};
 
 use crate::messages::*;
 
-// Visitor that checks for undefined variables and redefinitions
-pub struct VariableChecker {
-    defined_variables: HashSet<String>,
-}
+// Visitor that checks for undefined variables and redefinitions, based on reaching definitions
+// 	- The program is lowered to three-address code, and the definitions that reach every instruction are computed
+// 	- Statements are checked in order:
+// 		- a binding is a redefinition if a definition of its variable reaches the statement
+// 		- a variable is undefined if none of its definitions reaches an instruction that reads it,
+// 		  e.g in its own initializer
+// 	- Inputs are all defined at the entry, so their redefinitions are found by name
+pub struct VariableChecker;
 
 impl VariableChecker {
     pub fn new() -> Self {
-        VariableChecker {
-            defined_variables: HashSet::new(),
-        }
+        VariableChecker
     }
+}
 
+impl Default for VariableChecker {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Visitor for VariableChecker {
     fn visit_program(&mut self, program: &mut Program) {
-        self.defined_variables.clear();
-
-        // Arguments are considered variables
-        for input in &mut program.inputs {
-            input.accept(self);
-        }
-
-        for statement in &mut program.statements {
-            statement.accept(self);
-        }
-    }
-
-    fn visit_input(&mut self, input: &mut Input) {
-        if !self.defined_variables.insert(input.name.clone()) {
-            error(&format!("Redefinition of input variable '{}'.", input.name));
-        }
-    }
-
-    fn visit_statement(&mut self, statement: &mut Statement) {
-        match statement {
-            Statement::Assign { variable, expression } => {
-                if !self.defined_variables.insert(variable.clone()) {
-                    error(&format!("Redefinition of variable '{}'.", variable));
-                }
-
-                expression.accept(self);
+        let mut inputs = HashSet::new();
+        for input in &program.inputs {
+            if !inputs.insert(&input.name) {
+                error(&format!("Redefinition of input variable '{}'.", input.name));
             }
         }
-    }
 
-    fn visit_expression(&mut self, expression: &mut Expression) {
-        match expression {
-            Expression::Binary { left, right, .. } => {
-                left.accept(self);
-                right.accept(self);
-            }
-            Expression::Value(value) => {
-                value.accept(self);
+        let function = lower(program);
+        let cfg = Cfg::from_function(&function);
+        let analysis = ReachingDefinitions::new(&cfg);
+        let solution = solve(&analysis, &cfg);
+        // The definitions that reach every instruction
+        let reaching = instruction_facts(&analysis, &cfg, &solution, 0);
+
+        for (statement, range) in program.statements.iter().zip(statement_ranges(&function)) {
+            let Statement::Assign { variable, .. } = statement;
+            if analysis.is_defined(&reaching[range.start], &Variable::Named(variable.clone())) {
+                error(&format!("Redefinition of variable '{}'.", variable));
             }
-        }
-    }
 
-    fn visit_value(&mut self, value: &mut Value) {
-        match value {
-            Value::Identifier(name) => {
-                // Check if the variable is defined
-                if !self.defined_variables.contains(name) {
-                    error(&format!("Use of undefined variable '{}'.", name));
+            for index in range {
+                for operand in function.instructions[index].operands() {
+                    if let Operand::Variable(variable @ Variable::Named(name)) = operand {
+                        if !analysis.is_defined(&reaching[index], variable) {
+                            error(&format!("Use of undefined variable '{}'.", name));
+                        }
+                    }
                 }
             }
-            Value::Expression(expr) => {
-                // Visit nested expressions
-                expr.accept(self);
-            }
-            Value::Integer(_) => {
-                // Do nothing for integer literals
-            }
         }
     }
-}
\ No newline at end of file
+}
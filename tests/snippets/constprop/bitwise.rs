@@ -0,0 +1,5 @@
+fn main(x: u8) {
+    let a = 6u8 & 3u8 ^ 1u8 | 8u8;
+    let b = 255u8 ^ a;
+    let c = x & 12u8 & 10u8;
+}
@@ -0,0 +1,3 @@
+fn main() {
+    let a = 250u8.checked_add(10u8).unwrap();
+}
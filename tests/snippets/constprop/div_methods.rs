@@ -0,0 +1,8 @@
+fn main() {
+    let a = 10u8.wrapping_div(3u8);
+    let b = 11u8.saturating_div(4u8);
+    let c = 12u8.checked_div(5u8).unwrap();
+    let d = 13u8.overflowing_div(6u8).0;
+    let e = 14u8.overflowing_div(3u8).1 as u8;
+    let f = 15u8.wrapping_div(5u8);
+}
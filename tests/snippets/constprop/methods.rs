@@ -0,0 +1,9 @@
+fn main(x: u8) {
+    let a = 250u8.wrapping_add(10u8);
+    let b = 5u8.saturating_sub(10u8);
+    let c = 20u8.checked_mul(3u8).unwrap();
+    let d = 40u8.overflowing_mul(7u8).0;
+    let e = 40u8.overflowing_mul(7u8).1 as u8;
+    let f = x.wrapping_add(1u8);
+    let g = a.wrapping_mul(2u8).saturating_add(b);
+}
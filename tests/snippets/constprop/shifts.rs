@@ -0,0 +1,6 @@
+fn main(x: u8) {
+    let a = 1u8 << 3u8 + 2u8;
+    let b = 200u8 >> 2u8 >> 1u8;
+    let c = a << 8u8;
+    let d = x << b;
+}
@@ -0,0 +1,3 @@
+fn main() {
+    let m = 252u8 + 5u8 - 2u8;
+}
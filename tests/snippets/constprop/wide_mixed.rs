@@ -0,0 +1,4 @@
+fn main(x: u8) {
+    let m = x + (250u8 + 10u8 - 60u8);
+    let n = (2u8 - 5u8) * (1u8 - 4u8);
+}
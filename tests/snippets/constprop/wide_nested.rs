@@ -0,0 +1,4 @@
+fn main(x: u8) {
+    let m = x * ((((250u8 + 10u8) - 20u8) + 30u8 - 40u8) / 3u8);
+    let n = (10u8 / 3u8 * 200u8) + 1u8;
+}
@@ -0,0 +1,3 @@
+fn main() {
+    let m = 200u8 + 100u8 - 20u8;
+}
@@ -0,0 +1,6 @@
+fn main(x: u8) {
+    let a = x;
+    let b = a + 1u8;
+    let c = (a);
+    let d = c.wrapping_mul(b);
+}
@@ -0,0 +1,5 @@
+fn main(a: u8, b: u8) {
+    let x = a * b + 1u8;
+    let y = b * a + 1u8;
+    let z = (a * b + 1u8) / 2u8;
+}
@@ -0,0 +1,8 @@
+fn main(a: u8, b: u8, c: u8) {
+    let x = (a & b) + (b & a);
+    let y = (a | c) - (c | a);
+    let z = (b ^ c) * (c ^ b);
+    let w = a & b | c;
+    let v = c | b & a;
+    let u = a ^ b | b ^ a;
+}
@@ -0,0 +1,6 @@
+fn main(a: u8, b: u8) {
+    let v = (a * 2u8) + (b - 2u8);
+    let w = b - 2u8;
+    let x = (a & b) + (b >> 1u8);
+    let y = b >> 1u8;
+}
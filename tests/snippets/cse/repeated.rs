@@ -0,0 +1,7 @@
+fn main(a: u8, b: u8, c: u8) {
+    let x = (a + b) * (a + b);
+    let y = (b + a) * c;
+    let z = c * (a + b);
+    let w = (a * c + 1u8) - (c * a + 1u8);
+    let v = a.wrapping_mul(b) + b.wrapping_mul(a);
+}
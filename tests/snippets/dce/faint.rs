@@ -0,0 +1,6 @@
+fn main(x: u8) {
+    let a = x.wrapping_add(1u8);
+    let b = a + 1u8;
+    let c = b.wrapping_mul(3u8);
+    let d = c.wrapping_sub(a);
+}
@@ -0,0 +1,7 @@
+fn main(x: u8, y: u8) {
+    let a = 10u8;
+    let b = a + x;
+    let c = x + y;
+    let d = x.wrapping_add(y);
+    let e = b.wrapping_mul(2u8);
+}
@@ -0,0 +1,3 @@
+fn main(x: u8) {
+    let a = x + a;
+}
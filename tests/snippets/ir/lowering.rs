@@ -0,0 +1,8 @@
+fn main(x: u8, y: u8) {
+    let a = (x + 1u8) * (y - 2u8);
+    let b = a;
+    let c = x.wrapping_mul(y + 3u8) << 1u8;
+    let d = 7u8;
+    let e = (a + b) - (c / 2u8 - d);
+    let f = x.overflowing_add(y).1 as u8;
+}
@@ -0,0 +1,6 @@
+fn main(x: u8, y: u8) {
+    let a = x & 12u8 | y ^ 3u8;
+    let b = x | y & 1u8 << 2u8;
+    let c = (x | y) & 15u8;
+    let d = x ^ y + 1u8;
+}
@@ -0,0 +1,8 @@
+fn main(x: u8, y: u8) {
+    let a = x.wrapping_add(y);
+    let b = (x + 1u8).saturating_mul(2u8) - y;
+    let c = x.checked_div(y).unwrap();
+    let d = x.overflowing_sub(y).0 * 2u8;
+    let e = x.overflowing_sub(y).1 as u8 + 1u8;
+    let f = x.wrapping_sub(1u8).wrapping_mul(y + 1u8);
+}
@@ -0,0 +1,6 @@
+fn main(x: u8, y: u8) {
+    let a = x << 2u8 + y;
+    let b = x >> 1u8 >> y;
+    let c = x + (y << 1u8);
+    let d = x.wrapping_add(y) << 3u8;
+}
@@ -0,0 +1,7 @@
+fn main(x: u8) {
+    let a = 2u8 * 3u8;
+    let b = x * 1u8;
+    let c = b + a;
+    let d = (x + a) - (x + a);
+    let e = c / 4u8;
+}
@@ -0,0 +1,4 @@
+fn main(a: u8) {
+    let b = a / 4u8 + 200u8;
+    let c = b + 200u8;
+}
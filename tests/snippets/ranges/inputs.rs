@@ -0,0 +1,6 @@
+fn main(a: u8, b: u8) {
+    let half = a / 2u8;
+    let safe = half + 100u8;
+    let double = a * 2u8;
+    let quotient = b / (a >> 4u8);
+}
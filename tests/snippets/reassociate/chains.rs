@@ -0,0 +1,10 @@
+fn main(x: u8, y: u8) {
+    let m = x + 5u8 - 2u8;
+    let n = 3u8 + x + y + 4u8;
+    let o = x - 1u8 - y - 2u8;
+    let p = x * 2u8 * 3u8;
+    let q = x / 2u8 / 3u8;
+    let r = 2u8 * x * 3u8;
+    let s = 10u8 - x - 4u8;
+    let t = x * (y + 1u8 + 2u8);
+}
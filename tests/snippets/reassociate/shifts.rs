@@ -0,0 +1,6 @@
+fn main(x: u8) {
+    let a = x << 200u8 << 100u8;
+    let b = x >> 255u8 >> 1u8;
+    let c = x << 3u8 << 4u8;
+    let d = x >> 2u8 >> 3u8;
+}
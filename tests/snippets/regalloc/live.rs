@@ -0,0 +1,11 @@
+fn main(a: u8, b: u8) {
+    let c = a.wrapping_add(1u8);
+    let d = b.wrapping_mul(3u8);
+    let e = a ^ b;
+    let f = c & d;
+    let g = e | 3u8;
+    let h = a >> 1u8;
+    let i = b << 1u8;
+    let j = c - h;
+    let total = c ^ d ^ e ^ f ^ g ^ h ^ i ^ j;
+}
@@ -0,0 +1,7 @@
+fn main(a: u8, b: u8) {
+    let sum = a + b;
+    let product = sum * 2u8;
+    let quotient = product / (b - a);
+    let flag = a.overflowing_sub(b).1 as u8;
+    let masked = product & 15u8 | 1u8 << 4u8;
+}
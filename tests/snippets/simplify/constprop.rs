@@ -0,0 +1,4 @@
+fn main(x: u8) {
+    let a = x * 0u8;
+    let b = a + 3u8;
+}
@@ -0,0 +1,11 @@
+fn main(x: u8, y: u8) {
+    let a = x + 0u8;
+    let b = 1u8 * (y - 0u8);
+    let c = x * 0u8;
+    let d = (x + y) - (x + y);
+    let e = y / 1u8;
+    let f = 0u8 / x;
+    let g = 0u8 / 5u8;
+    let h = x.wrapping_mul(1u8) + c;
+    let i = x.overflowing_add(0u8).1 as u8;
+}
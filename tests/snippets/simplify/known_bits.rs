@@ -0,0 +1,11 @@
+fn main(x: u8, y: u8) {
+    let even = x << 1u8;
+    let low = even & 1u8;
+    let high = x >> 4u8;
+    let masked = high & 15u8;
+    let odd = y | 1u8;
+    let set = odd | 1u8;
+    let quotient = x / odd;
+    let parity = (x * 2u8) & 1u8;
+    let flipped = (x ^ y) ^ (x ^ y);
+}
@@ -0,0 +1,4 @@
+fn main(x: u8) {
+    let a = x / 3u8;
+    let b = x / 7u8;
+}
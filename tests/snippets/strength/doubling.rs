@@ -0,0 +1,6 @@
+fn main(x: u8, y: u8) {
+    let a = x * 2u8;
+    let b = 2u8 * (x + y);
+    let c = (x / y) * 2u8;
+    let d = x * 4u8;
+}
@@ -0,0 +1,5 @@
+fn main(a: u8, b: u8) {
+    let v = (a * 2u8) + ((b - 2u8) / 3u8);
+    let w = (b / 3u8) + (a * 3u8);
+    let x = ((a + b) / 7u8) - 1u8;
+}
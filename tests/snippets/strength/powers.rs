@@ -0,0 +1,7 @@
+fn main(x: u8, y: u8) {
+    let a = x * 8u8;
+    let b = 2u8 * (x + y);
+    let c = x / 4u8;
+    let d = y * 3u8;
+    let e = x / 0u8;
+}
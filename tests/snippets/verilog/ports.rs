@@ -0,0 +1,5 @@
+fn main(input: u8, b: u8) {
+    let overflow = input.overflowing_add(b).1 as u8;
+    let wire = input ^ b;
+    let shifted = wire << 3u8;
+}
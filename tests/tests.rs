@@ -1,20 +1,71 @@
 use std::process::Command;
 use std::process::Output;
 
+// The original tests pass their expected lines as &vec![...]
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use super::*;
+    use rustic::ast::Operator;
+    use rustic::parser::parse;
+    use rustic::ir::instruction::*;
+    use rustic::ir::lower::lower;
+    use rustic::ir::lift::lift;
+    use rustic::ir::cfg::*;
+    use rustic::ir::dominators::Dominators;
+    use rustic::ir::ssa::{into_ssa, out_of_ssa};
+    use rustic::ir::sccp::{Lattice, Sccp};
+    use rustic::arithmetic::OverflowMode;
+    use rustic::dataflow::framework::*;
+    use rustic::dataflow::constants::Constants;
+    use rustic::dataflow::liveness::Liveness;
+    use rustic::dataflow::reaching::{Definition, ReachingDefinitions};
+    use rustic::dataflow::available::{AvailableExpressions, Operation};
+    use rustic::dataflow::intervals::{Interval, Intervals, Verdict};
+    use rustic::dataflow::known_bits::{variable_bits, Bits};
+    use rustic::arithmetic::Panic;
+    use rustic::interpreter::{Interpreter, RuntimeError};
+    use rustic::passes::{PassManager, PassOptions};
+    use rustic::visitors::strength::CostModel;
+    use std::collections::{BTreeMap, BTreeSet};
+    use rustic::bytecode::instruction::{self as bytecode, Bytecode};
+    use rustic::bytecode::compile::compile;
+    use rustic::bytecode::format::{decode, encode};
+    use rustic::bytecode::vm::Vm;
+    use rustic::codegen::abi;
+    use rustic::codegen::x86_64;
+    use rustic::codegen::c;
+    use rustic::codegen::riscv::emit as riscv;
+    use rustic::codegen::riscv::simulator::Harness;
+    use rustic::codegen::wasm::module::{BinaryOp, Instruction as WasmInstruction, Module};
+    use rustic::codegen::wasm::{binary as wasm, emit as wat};
+    use rustic::codegen::wasm::validate::validate;
+    use rustic::codegen::wasm::interpreter::{invoke, Trap};
+    use rustic::codegen::llvm;
+    use rustic::codegen::regalloc;
+    use rustic::circuit::{compile as circuit, witness};
+    use rustic::circuit::field::Field;
+    use rustic::circuit::netlist::{self, Netlist};
+    use rustic::codegen::verilog;
+    use rustic::ast::Program;
 
     const SNIPPET_PATH: &str = "tests/snippets/";
 
     // runs an instance of rustic with the given file name and constprop flag.
     // returns the output of the process.
     fn run_program(file_name: &str, constprop: bool) -> Output {
-        let mut args = vec![format!("{}{}", SNIPPET_PATH, file_name)];
-
         if constprop {
-            args.push("--constprop".to_string());
+            run_program_with(file_name, &["--constprop"])
+        } else {
+            run_program_with(file_name, &[])
         }
+    }
+
+    // runs an instance of rustic with the given file name and extra command-line arguments.
+    // returns the output of the process.
+    fn run_program_with(file_name: &str, extra_args: &[&str]) -> Output {
+        let mut args = vec![format!("{}{}", SNIPPET_PATH, file_name)];
+        args.extend(extra_args.iter().map(|arg| arg.to_string()));
 
         Command::new(env!("CARGO_BIN_EXE_rustic"))
             .args(&args) // Pass the arguments to the program
@@ -40,6 +91,33 @@ mod tests {
         }
     }
 
+    // Same as runs_ok, with extra command-line arguments.
+    fn runs_ok_with(file_name: &str, args: &[&str], expected: &[&str]) {
+        let output = run_program_with(file_name, args);
+        assert!(output.status.success(), "Failed: {:?}", output);
+        for line in expected {
+            assert!(String::from_utf8(output.stdout.clone()).unwrap().contains(line), "Missing {:?} in {:?}", line, output);
+        }
+    }
+
+    // Same as runs_err, with extra command-line arguments.
+    fn runs_err_with(file_name: &str, args: &[&str], expected: &[&str]) {
+        let output = run_program_with(file_name, args);
+        assert!(!output.status.success(), "Failed: {:?}", output);
+        for line in expected {
+            assert!(String::from_utf8(output.stderr.clone()).unwrap().contains(line), "Missing {:?} in {:?}", line, output);
+        }
+    }
+
+    // Check that a rustic execution runs successfully and that it warns with the expected strings.
+    fn warns_with(file_name: &str, args: &[&str], expected: &[&str]) {
+        let output = run_program_with(file_name, args);
+        assert!(output.status.success(), "Failed: {:?}", output);
+        for line in expected {
+            assert!(String::from_utf8(output.stderr.clone()).unwrap().contains(line), "Missing {:?} in {:?}", line, output);
+        }
+    }
+
     #[test]
     fn test_all_grammar_feats() {
         runs_ok("parser/all_grammar_feats.rs", false, &vec![]);
@@ -82,7 +160,7 @@ mod tests {
 
     #[test]
     fn single_def() {
-        runs_ok("parser/single_def.rs", false, &vec![]);
+        runs_ok("parser/single_def.rs", false, &[]);
     }
 
     #[test]
@@ -120,6 +198,13 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn test_self_reference() {
+        runs_err("errors/self_reference.rs", false, &[
+            "Error: Use of undefined variable 'a'."
+        ]);
+    }
+
     #[test]
     fn test_constprop_unfoldable() {
         runs_ok("constprop/unfoldable.rs", true, &vec![
@@ -184,11 +269,36 @@ mod tests {
 
     #[test]
     fn test_constprop_div_non_exact() {
-        runs_err("constprop/div_non_exact.rs", true, &vec![
-            "Error: Constant evaluation resulted in non-integer division: 10 / 3"
+        runs_ok("constprop/div_non_exact.rs", true, &[
+            "let a = 3u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_div_non_exact_lint() {
+        warns_with("constprop/div_non_exact.rs", &["--constprop", "--warn-inexact-division"], &[
+            "Warning: Constant division discards a remainder of 1: 10 / 3"
         ]);
     }
 
+    #[test]
+    fn test_constprop_div_methods_lint() {
+        for evaluation in ["--evaluation=exact", "--evaluation=mathematical"] {
+            let output = run_program_with("constprop/div_methods.rs", &["--constprop", "--warn-inexact-division", evaluation]);
+            assert!(output.status.success(), "Failed: {:?}", output);
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert_eq!(stderr.matches("Warning: Constant division discards").count(), 4, "{}", stderr);
+            for line in [
+                "Warning: Constant division discards a remainder of 1: 10u8.wrapping_div(3u8)",
+                "Warning: Constant division discards a remainder of 3: 11u8.saturating_div(4u8)",
+                "Warning: Constant division discards a remainder of 2: 12u8.checked_div(5u8).unwrap()",
+                "Warning: Constant division discards a remainder of 1: 13u8.overflowing_div(6u8).0"
+            ] {
+                assert!(stderr.contains(line), "Missing {:?} in {}", line, stderr);
+            }
+        }
+    }
+
     #[test]
     fn test_constprop_div0() {
         runs_err("constprop/div0.rs", true, &vec![
@@ -247,4 +357,1899 @@ mod tests {
             "let d = 10u8;"
         ]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_methods() {
+        runs_ok("parser/methods.rs", false, &[
+            "let a = x.wrapping_add(y);",
+            "let b = (((x) + 1u8).saturating_mul(2u8)) - y;",
+            "let c = x.checked_div(y).unwrap();",
+            "let d = (x.overflowing_sub(y).0) * 2u8;",
+            "let e = (x.overflowing_sub(y).1 as u8) + 1u8;",
+            "let f = x.wrapping_sub(1u8).wrapping_mul((y) + 1u8);"
+        ]);
+    }
+
+    // Method names and the cast of the overflow flag are tokens, which rustc does not split
+    #[test]
+    fn test_methods_tokens() {
+        for statement in [
+            "let a = x.wrapping_ add(y);",
+            "let a = x.saturating _mul(y);",
+            "let a = x.checked_ div(y).unwrap();",
+            "let a = x.overflowing_ sub(y).0;",
+            "let a = x.overflowing_sub(y).1asu8;",
+            "let a = x.overflowing_sub(y).1 asu8;",
+            "let a = x.overflowing_sub(y).1as u8;",
+            "let a = x.overflowing_sub(y). 1 as u8;",
+        ] {
+            let source = format!("fn main(x: u8, y: u8) {{\n    {}\n}}\n", statement);
+            assert!(parse(&source).is_err(), "{} should not parse", statement);
+        }
+
+        let source = "fn main(x: u8, y: u8) {\n    let a = x . wrapping_add ( y ) + x.overflowing_sub(y).1  as\tu8;\n}\n";
+        assert!(parse(source).is_ok());
+    }
+
+    #[test]
+    fn test_constprop_methods() {
+        runs_ok("constprop/methods.rs", true, &[
+            "let a = 4u8;",
+            "let b = 0u8;",
+            "let c = 60u8;",
+            "let d = 24u8;",
+            "let e = 1u8;",
+            "let f = x.wrapping_add(1u8);",
+            "let g = 8u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_checked_overflow() {
+        runs_err("constprop/checked_overflow.rs", true, &[
+            "Error: Constant evaluation panicked with 'called `Option::unwrap()` on a `None` value': 250u8.checked_add(10u8).unwrap()"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_overflow_wrap() {
+        runs_ok_with("constprop/overflow.rs", &["--constprop", "--overflow=wrap"], &[
+            "let a = 4u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_underflow_wrap() {
+        runs_ok_with("constprop/underflow.rs", &["--constprop", "--overflow=wrap"], &[
+            "let e = 251u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_overflow_error() {
+        runs_err_with("constprop/overflow.rs", &["--constprop", "--overflow=error"], &[
+            "Error: Constant evaluation resulted in value greater than 255: 250 + 10"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_overflow_saturate() {
+        runs_ok_with("constprop/overflow_2.rs", &["--constprop", "--overflow=saturate"], &[
+            "let b = 255u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_overflow_panic() {
+        runs_ok_with("constprop/overflow.rs", &["--constprop", "--overflow=panic"], &[
+            "let a = 250u8 + 10u8;"
+        ]);
+        warns_with("constprop/overflow.rs", &["--constprop", "--overflow=panic"], &[
+            "Warning: This operation will panic at runtime with 'attempt to add with overflow': 250 + 10"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_checked_overflow_panic() {
+        runs_ok_with("constprop/checked_overflow.rs", &["--constprop", "--overflow=panic"], &[
+            "let a = 250u8.checked_add(10u8).unwrap();"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_div0_wrap() {
+        warns_with("constprop/div0.rs", &["--constprop", "--overflow=wrap"], &[
+            "Warning: This operation will panic at runtime with 'attempt to divide by zero': 10 / 0"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_wide_exact() {
+        runs_err_with("constprop/wide.rs", &["--constprop", "--evaluation=exact"], &[
+            "Error: Constant evaluation resulted in value greater than 255: 252 + 5 (in the initializer of 'm')"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_wide_mathematical() {
+        runs_ok_with("constprop/wide.rs", &["--constprop", "--evaluation=mathematical"], &[
+            "let m = 255u8;"
+        ]);
+        warns_with("constprop/wide.rs", &["--constprop", "--evaluation=mathematical"], &[
+            "Warning: Subexpression (252u8) + 5u8 evaluates to 257, which does not fit in u8 (in the initializer of 'm')"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_wide_overflow() {
+        runs_err_with("constprop/wide_overflow.rs", &["--constprop", "--evaluation=mathematical"], &[
+            "Error: Constant evaluation resulted in 280, which does not fit in u8: ((200u8) + 100u8) - 20u8 (in the initializer of 'm')"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_wide_overflow_wrap() {
+        runs_ok_with("constprop/wide_overflow.rs", &["--constprop", "--evaluation=mathematical", "--overflow=wrap"], &[
+            "let m = 24u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_wide_mixed() {
+        runs_ok_with("constprop/wide_mixed.rs", &["--constprop", "--evaluation=mathematical"], &[
+            "let m = (x) + 200u8;",
+            "let n = 9u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_wide_nested() {
+        let output = run_program_with("constprop/wide_nested.rs", &["--constprop", "--evaluation=mathematical", "--warn-inexact-division", "--overflow=panic"]);
+        assert!(output.status.success(), "{:?}", output);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        // Every warning is reported once, however deep the constant subexpression is
+        for line in [
+            "Warning: Subexpression (250u8) + 10u8 evaluates to 260, which does not fit in u8 (in the initializer of 'm')",
+            "Warning: Subexpression (((((250u8) + 10u8)) - 20u8)) + 30u8 evaluates to 270, which does not fit in u8 (in the initializer of 'm')",
+            "Warning: Constant division discards a remainder of 2: 230 / 3",
+            "Warning: Constant evaluation resulted in 601, which does not fit in u8: ((((10u8) / 3u8) * 200u8)) + 1u8 (in the initializer of 'n')",
+            "Warning: Constant division discards a remainder of 1: 10 / 3"
+        ] {
+            assert_eq!(stderr.matches(line).count(), 1, "{:?} in {}", line, stderr);
+        }
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("let m = (x) * 76u8;"), "{}", stdout);
+    }
+
+    #[test]
+    fn test_reassociate() {
+        runs_ok_with("reassociate/chains.rs", &["--reassociate"], &[
+            "let m = (x + 5u8) - 2u8;",
+            "let n = (x + y) + 7u8;",
+            "let o = (x - y) - 3u8;",
+            "let p = x * 6u8;",
+            "let q = x / 6u8;",
+            "let r = (2u8 * x) * 3u8;",
+            "let s = 6u8 - x;",
+            "let t = x * (y + 3u8);"
+        ]);
+    }
+
+    #[test]
+    fn test_reassociate_wrap() {
+        runs_ok_with("reassociate/chains.rs", &["--reassociate", "--overflow=wrap"], &[
+            "let m = x + 3u8;",
+            "let r = x * 6u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_reassociate_constprop() {
+        runs_ok_with("constprop/mixed.rs", &["--constprop", "--reassociate", "--overflow=wrap"], &[
+            "let m = x + 3u8;",
+            "let n = 3u8 + x;"
+        ]);
+    }
+
+    #[test]
+    fn test_reassociate_shifts() {
+        runs_ok_with("reassociate/shifts.rs", &["--reassociate"], &[
+            "let a = (x << 200u8) << 100u8;",
+            "let b = (x >> 255u8) >> 1u8;",
+            "let c = x << 7u8;",
+            "let d = x >> 5u8;"
+        ]);
+        runs_ok_with("reassociate/shifts.rs", &["-O2"], &[
+            "let a = (x << 200u8) << 100u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_simplify() {
+        runs_ok_with("simplify/identities.rs", &["--simplify"], &[
+            "let a = x;",
+            "let b = y;",
+            "let c = 0u8;",
+            "let d = (x + y) - (x + y);",
+            "let e = y;",
+            "let f = 0u8 / x;",
+            "let g = 0u8;",
+            "let h = x + c;",
+            "let i = 0u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_simplify_wrap() {
+        runs_ok_with("simplify/identities.rs", &["--simplify", "--overflow=wrap"], &[
+            "let d = 0u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_simplify_constprop() {
+        runs_ok_with("simplify/constprop.rs", &["--simplify", "--constprop"], &[
+            "let a = 0u8;",
+            "let b = 3u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_dce() {
+        runs_ok_with("dce/unused.rs", &["--constprop", "--dce"], &[
+            "    let b = 10u8 + x;\n    let c = (x) + y;\n}"
+        ]);
+    }
+
+    #[test]
+    fn test_dce_wrap() {
+        runs_ok_with("dce/unused.rs", &["--constprop", "--dce", "--overflow=wrap"], &[
+            "fn main(x: u8, y: u8) {\n}"
+        ]);
+    }
+
+    #[test]
+    fn test_dce_keep() {
+        runs_ok_with("dce/unused.rs", &["--constprop", "--dce", "--overflow=wrap", "--keep", "e"], &[
+            "{\n    let b = 10u8 + x;\n    let e = b.wrapping_mul(2u8);\n}"
+        ]);
+    }
+
+    #[test]
+    fn test_dce_faint() {
+        // b may panic, so it keeps a. c and d are only read by each other.
+        runs_ok_with("dce/faint.rs", &["--dce"], &[
+            "{\n    let a = x.wrapping_add(1u8);\n    let b = (a) + 1u8;\n}"
+        ]);
+        runs_ok_with("dce/faint.rs", &["--dce", "--overflow=wrap"], &[
+            "fn main(x: u8) {\n}"
+        ]);
+    }
+
+    #[test]
+    fn test_dce_keep_undefined() {
+        runs_err_with("dce/unused.rs", &["--dce", "--keep", "z"], &[
+            "Error: Output variable 'z' is not defined."
+        ]);
+    }
+
+    #[test]
+    fn test_cse() {
+        runs_ok_with("cse/repeated.rs", &["--cse"], &[
+            "let tmp0 = (a) + b;",
+            "let x = tmp0 * tmp0;",
+            "let y = tmp0 * c;",
+            "let z = y;",
+            "let tmp1 = ((a) * c) + 1u8;",
+            "let w = tmp1 - tmp1;",
+            "let tmp2 = a.wrapping_mul(b);",
+            "let v = tmp2 + tmp2;"
+        ]);
+    }
+
+    #[test]
+    fn test_cse_bitwise() {
+        runs_ok_with("cse/bitwise.rs", &["--cse"], &[
+            "let tmp0 = (a) & b;",
+            "let x = tmp0 + tmp0;",
+            "let tmp1 = (a) | c;",
+            "let y = tmp1 - tmp1;",
+            "let tmp2 = (b) ^ c;",
+            "let z = tmp2 * tmp2;",
+            "let w = tmp0 | c;",
+            "let v = w;",
+            "let u = tmp3 | tmp3;"
+        ]);
+    }
+
+    #[test]
+    fn test_cse_reuses_bindings() {
+        runs_ok_with("cse/bindings.rs", &["--cse"], &[
+            "let x = ((a) * b) + 1u8;",
+            "let y = x;",
+            "let z = x / 2u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_cse_panic_order() {
+        // b - 2u8 cannot be evaluated before a * 2u8, which may panic first
+        runs_ok_with("cse/panic_order.rs", &["--cse"], &[
+            "let v = (((a) * 2u8)) + ((b) - 2u8);",
+            "let w = (b) - 2u8;",
+            "let tmp0 = (b) >> 1u8;",
+            "let x = (((a) & b)) + tmp0;",
+            "let y = tmp0;"
+        ]);
+    }
+
+    #[test]
+    fn test_copyprop() {
+        runs_ok_with("copyprop/renames.rs", &["--copyprop"], &[
+            "let a = x;",
+            "let b = (x) + 1u8;",
+            "let c = (x);",
+            "let d = x.wrapping_mul(b);"
+        ]);
+    }
+
+    #[test]
+    fn test_copyprop_dce() {
+        runs_ok_with("copyprop/renames.rs", &["--copyprop", "--dce", "--keep", "d"], &[
+            "{\n    let b = (x) + 1u8;\n    let d = x.wrapping_mul(b);\n}"
+        ]);
+    }
+
+    #[test]
+    fn test_copyprop_cse() {
+        runs_ok_with("cse/bindings.rs", &["--cse", "--copyprop"], &[
+            "let y = x;",
+            "let z = x / 2u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_parser_shifts() {
+        runs_ok("parser/shifts.rs", false, &[
+            "let a = (x) << (2u8) + y;",
+            "let b = ((x) >> 1u8) >> y;",
+            "let c = (x) + ((y) << 1u8);",
+            "let d = (x.wrapping_add(y)) << 3u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_shift_overflow() {
+        runs_err("constprop/shifts.rs", true, &[
+            "Error: Constant evaluation panicked with 'attempt to shift left with overflow': 32 << 8 (in the initializer of 'c')"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_shifts() {
+        runs_ok_with("constprop/shifts.rs", &["--constprop", "--overflow=panic"], &[
+            "let a = 32u8;",
+            "let b = 25u8;",
+            "let c = 32u8 << 8u8;",
+            "let d = (x) << 25u8;"
+        ]);
+        warns_with("constprop/shifts.rs", &["--constprop", "--overflow=panic"], &[
+            "Warning: This operation will panic at runtime with 'attempt to shift left with overflow': 32 << 8 (in the initializer of 'c')"
+        ]);
+    }
+
+    #[test]
+    fn test_strength_reduction() {
+        runs_ok_with("strength/powers.rs", &["--strength-reduce"], &[
+            "let a = (x) * 8u8;",
+            "let b = (2u8) * ((x) + y);",
+            "let c = x >> 2u8;",
+            "let d = (y) * 3u8;",
+            "let e = (x) / 0u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_strength_reduction_wrap() {
+        runs_ok_with("strength/powers.rs", &["--strength-reduce", "--overflow=wrap"], &[
+            "let a = x << 3u8;",
+            "let b = ((x) + y) << 1u8;",
+            "let c = x >> 2u8;"
+        ]);
+        // The operand stays inside the shift, instead of being bound to a temporary
+        let output = run_program_with("strength/powers.rs", &["--strength-reduce", "--overflow=wrap"]);
+        assert!(!String::from_utf8(output.stdout).unwrap().contains("tmp"));
+    }
+
+    #[test]
+    fn test_strength_reduction_uniform_costs() {
+        runs_ok_with("strength/powers.rs", &["--strength-reduce", "--cost-model=uniform"], &[
+            "let a = (x) * 8u8;",
+            "let b = (2u8) * ((x) + y);",
+            "let c = (x) / 4u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_strength_reduction_division() {
+        runs_ok_with("strength/division.rs", &["--strength-reduce"], &[
+            "let a = (x) / 3u8;",
+            "let b = (x) / 7u8;"
+        ]);
+        runs_ok_with("strength/division.rs", &["--strength-reduce", "--cost-model=no-div"], &[
+            "let tmp0 = x >> 4u8;",
+            "let tmp1 = x - (tmp0 << 4u8);",
+            "let tmp7 = ((tmp5 + (tmp3 >> 4u8)) + (tmp4 >> 4u8)) + tmp6;",
+            "let a = tmp7 >> 1u8;",
+            "let tmp16 = x - tmp15;",
+            "let tmp17 = (tmp16 >> 1u8) + tmp15;",
+            "let b = tmp17 >> 2u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_strength_reduction_panic_order() {
+        // The dividend is bound before the statement, so a * 2u8 would not panic first anymore
+        runs_ok_with("strength/panic_order.rs", &["--strength-reduce", "--cost-model=no-div"], &[
+            "let v = (((a) * 2u8)) + ((((b) - 2u8)) / 3u8);",
+            "let w = ((tmp7 >> 1u8)) + ((a) * 3u8);",
+            "let x = (((((a) + b)) / 7u8)) - 1u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_strength_reduction_doubling_costs() {
+        let source = std::fs::read_to_string(format!("{}strength/doubling.rs", SNIPPET_PATH)).unwrap();
+        let reduce = |overflow: OverflowMode, costs: CostModel| {
+            let mut program = parse(&source).unwrap();
+            let mut options = PassOptions::new(overflow);
+            options.costs = costs;
+            let mut manager = PassManager::new(options);
+            manager.add_pass("strength").unwrap();
+            manager.run(&mut program);
+            program.to_string()
+        };
+
+        // Shifts cost more than additions, e.g without a barrel shifter
+        let slow_shift = CostModel { add: 1, multiply: 3, divide: 20, shift: 2 };
+        let reduced = reduce(OverflowMode::Wrap, slow_shift);
+        for line in [
+            "let a = x + x;",
+            // The operand costs an addition itself, so doubling it costs as much as the shift
+            "let b = ((x) + y) << 1u8;",
+            // The operand may panic, evaluating it twice is not an option
+            "let c = ((x) / y) << 1u8;",
+            "let d = x << 2u8;"
+        ] {
+            assert!(reduced.contains(line), "Missing {:?} in {}", line, reduced);
+        }
+
+        // With the default costs a shift is as cheap as an addition
+        let reduced = reduce(OverflowMode::Wrap, CostModel::from_name("default").unwrap());
+        assert!(reduced.contains("let a = x << 1u8;"), "{}", reduced);
+
+        // Without wrapping, x + x would panic with another message than x * 2u8
+        let reduced = reduce(OverflowMode::Panic, slow_shift);
+        assert!(reduced.contains("let a = (x) * 2u8;"), "{}", reduced);
+    }
+
+    #[test]
+    fn test_passes_pipeline() {
+        runs_ok_with("passes/pipeline.rs", &["--passes=constprop,simplify"], &[
+            "let a = 6u8;",
+            "let b = x;",
+            "let c = b + 6u8;",
+            "let e = c / 4u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_passes_unknown() {
+        runs_err_with("passes/pipeline.rs", &["--passes=constprop,foo"], &[
+            "Error: Unknown pass 'foo', expected one of: constprop, sccp, simplify, reassociate, strength, cse, copyprop, dce."
+        ]);
+    }
+
+    #[test]
+    fn test_passes_optimization_levels() {
+        runs_ok_with("passes/pipeline.rs", &["-O0"], &[
+            "let a = (2u8) * 3u8;"
+        ]);
+        runs_ok_with("passes/pipeline.rs", &["-O1"], &[
+            "let c = x + 6u8;",
+            "let e = c / 4u8;"
+        ]);
+        runs_ok_with("passes/pipeline.rs", &["-O2", "--keep", "e"], &[
+            "{\n    let c = x + 6u8;\n    let e = c >> 2u8;\n}"
+        ]);
+    }
+
+    #[test]
+    fn test_passes_print_after() {
+        runs_ok_with("passes/pipeline.rs", &["--passes=simplify,constprop", "--fixpoint", "--print-after=simplify"], &[
+            "Program after simplify (iteration 1):\n\nfn main(x: u8) {\n    let a = 2u8 * 3u8;",
+            "Program after simplify (iteration 2):\n\nfn main(x: u8) {\n    let a = 6u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_emit_ir() {
+        runs_ok_with("ir/lowering.rs", &["--emit=ir"], &[
+            "fn main(x: u8, y: u8) {\n    %0 = add x, 1\n    %1 = sub y, 2\n    a = mul %0, %1\n    b = a\n",
+            "    %2 = add y, 3\n    %3 = mul.wrapping x, %2\n    c = shl %3, 1\n    d = 7\n",
+            "    %4 = add a, b\n    %5 = div c, 2\n    %6 = sub %5, d\n    e = sub %4, %6\n",
+            "    f = add.overflowed x, y\n}"
+        ]);
+    }
+
+    // Lowering then lifting gives back the same program, up to parenthesis
+    #[test]
+    fn test_ir_round_trip() {
+        let source = std::fs::read_to_string(format!("{}ir/lowering.rs", SNIPPET_PATH)).unwrap();
+        let program = parse(&source).unwrap();
+        let lifted = lift(&lower(&program));
+
+        assert_eq!(lifted.to_string(), [
+            "fn main(x: u8, y: u8) {",
+            "    let a = (x + 1u8) * (y - 2u8);",
+            "    let b = a;",
+            "    let c = x.wrapping_mul(y + 3u8) << 1u8;",
+            "    let d = 7u8;",
+            "    let e = (a + b) - ((c / 2u8) - d);",
+            "    let f = x.overflowing_add(y).1 as u8;",
+            "}",
+        ].join("\n"));
+        assert_eq!(lower(&lifted), lower(&program));
+    }
+
+    // Overflow flags are parenthesized as operands, since rustc reads `as u8 <<` as generic arguments
+    #[test]
+    fn test_ir_lift_casts() {
+        let operand = |name: &str| Operand::Variable(Variable::Named(name.to_string()));
+        let flag = |operator| Instruction::Binary {
+            destination: Variable::Temporary(0),
+            operator,
+            arithmetic: Arithmetic::Method(rustic::ast::MethodKind::OverflowFlag),
+            left: operand("x"),
+            right: operand("y"),
+        };
+        let function = |instruction| Function {
+            name: "main".to_string(),
+            inputs: vec!["x".to_string(), "y".to_string()],
+            instructions: vec![flag(Operator::Add), instruction],
+        };
+
+        let shifted = function(Instruction::Binary {
+            destination: Variable::Named("a".to_string()),
+            operator: Operator::ShiftLeft,
+            arithmetic: Arithmetic::Panicking,
+            left: Operand::Variable(Variable::Temporary(0)),
+            right: Operand::Constant(2),
+        });
+        let lifted = lift(&shifted);
+        assert!(lifted.to_string().contains("let a = (x.overflowing_add(y).1 as u8) << 2u8;"), "{}", lifted);
+        assert_eq!(lower(&parse(&lifted.to_string()).unwrap()), shifted);
+
+        let added = function(Instruction::Binary {
+            destination: Variable::Named("a".to_string()),
+            operator: Operator::Add,
+            arithmetic: Arithmetic::Panicking,
+            left: Operand::Constant(1),
+            right: Operand::Variable(Variable::Temporary(0)),
+        });
+        let lifted = lift(&added);
+        assert!(lifted.to_string().contains("let a = 1u8 + (x.overflowing_add(y).1 as u8);"), "{}", lifted);
+        assert_eq!(lower(&parse(&lifted.to_string()).unwrap()), added);
+    }
+
+    // Temporaries that cannot be inlined without reordering their evaluation are bound to variables
+    #[test]
+    fn test_ir_lift_temporaries() {
+        let named = |name: &str| Operand::Variable(Variable::Named(name.to_string()));
+        let temporary = |index| Operand::Variable(Variable::Temporary(index));
+        let add = |destination, left, right| Instruction::Binary {
+            destination,
+            operator: Operator::Add,
+            arithmetic: Arithmetic::Panicking,
+            left,
+            right,
+        };
+
+        let function = Function {
+            name: "main".to_string(),
+            inputs: vec!["x".to_string(), "y".to_string()],
+            instructions: vec![
+                add(Variable::Temporary(0), named("x"), Operand::Constant(1)),
+                add(Variable::Temporary(1), named("y"), Operand::Constant(2)),
+                // Reads the temporaries in the opposite order
+                add(Variable::Named("a".to_string()), temporary(1), temporary(0)),
+                add(Variable::Temporary(2), named("a"), named("a")),
+                // Reads a temporary twice
+                add(Variable::Named("b".to_string()), temporary(2), temporary(2)),
+            ],
+        };
+
+        assert_eq!(lift(&function).to_string(), [
+            "fn main(x: u8, y: u8) {",
+            "    let tmp0 = x + 1u8;",
+            "    let tmp1 = y + 2u8;",
+            "    let a = tmp1 + tmp0;",
+            "    let tmp2 = a + a;",
+            "    let b = tmp2 + tmp2;",
+            "}",
+        ].join("\n"));
+    }
+
+    fn named(name: &str) -> Variable {
+        Variable::Named(name.to_string())
+    }
+
+    fn copy(destination: &str, source: Operand) -> Instruction {
+        Instruction::Copy { destination: named(destination), source }
+    }
+
+    fn binary(destination: &str, operator: Operator, left: Operand, right: Operand) -> Instruction {
+        Instruction::Binary {
+            destination: named(destination),
+            operator,
+            arithmetic: Arithmetic::Panicking,
+            left,
+            right,
+        }
+    }
+
+    fn block(instructions: Vec<Instruction>, terminator: Terminator) -> BasicBlock {
+        BasicBlock { phis: Vec::new(), instructions, terminator }
+    }
+
+    // if condition { a = 1 } else { a = 2 }; c = a + 1
+    fn diamond(condition: Operand) -> Cfg {
+        let a = || Operand::Variable(named("a"));
+        Cfg {
+            name: "main".to_string(),
+            inputs: vec!["x".to_string()],
+            blocks: vec![
+                block(vec![copy("k", condition)], Terminator::Branch { condition: Operand::Variable(named("k")), then: 1, otherwise: 2 }),
+                block(vec![copy("a", Operand::Constant(3))], Terminator::Jump(3)),
+                block(vec![binary("a", Operator::Add, Operand::Variable(named("x")), Operand::Constant(200))], Terminator::Jump(3)),
+                block(vec![binary("c", Operator::Multiply, a(), Operand::Constant(2))], Terminator::Return),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_ir_dominators() {
+        let cfg = diamond(Operand::Variable(named("x")));
+        let dominators = Dominators::new(&cfg);
+
+        assert_eq!(dominators.immediate_dominator(0), None);
+        assert_eq!(dominators.immediate_dominator(3), Some(0));
+        assert!(dominators.dominates(0, 3) && !dominators.dominates(1, 3));
+        assert_eq!(dominators.children(0), vec![1, 2, 3]);
+        assert_eq!(dominators.frontier(1).iter().copied().collect::<Vec<_>>(), vec![3]);
+        assert!(dominators.frontier(0).is_empty());
+    }
+
+    #[test]
+    fn test_ir_ssa() {
+        let mut cfg = diamond(Operand::Variable(named("x")));
+        into_ssa(&mut cfg);
+
+        assert_eq!(cfg.to_string(), [
+            "fn main(x: u8) {",
+            "b0:",
+            "    k = x",
+            "    branch k, b1, b2",
+            "b1:",
+            "    a.1 = 3",
+            "    jump b3",
+            "b2:",
+            "    a.2 = add x, 200",
+            "    jump b3",
+            "b3:",
+            "    a.3 = phi [b1: a.1], [b2: a.2]",
+            "    c = mul a.3, 2",
+            "    return",
+            "}",
+        ].join("\n"));
+
+        out_of_ssa(&mut cfg);
+        assert!(cfg.blocks[3].phis.is_empty());
+        assert_eq!(cfg.blocks[1].instructions.last().unwrap().to_string(), "a.3 = a.1");
+    }
+
+    // Only the branch that is taken is propagated through, the other one would overflow
+    #[test]
+    fn test_ir_sccp_branch() {
+        let mut cfg = diamond(Operand::Constant(1));
+        into_ssa(&mut cfg);
+
+        let sccp = Sccp::analyze(&cfg, OverflowMode::Error);
+        assert!(!sccp.is_executable(2));
+        assert!(sccp.panics(&cfg).is_empty());
+        assert_eq!(sccp.value(&named("c")), Lattice::Constant(6));
+
+        sccp.rewrite(&mut cfg);
+        out_of_ssa(&mut cfg);
+        assert_eq!(cfg.to_function().unwrap().to_string(), [
+            "fn main(x: u8) {",
+            "    k = 1",
+            "    a.1 = 3",
+            "    a.3 = 3",
+            "    c = 6",
+            "}",
+        ].join("\n"));
+    }
+
+    // while i != 0 { i = i - 1 }
+    #[test]
+    fn test_ir_sccp_loop() {
+        let i = || Operand::Variable(named("i"));
+        let mut cfg = Cfg {
+            name: "main".to_string(),
+            inputs: vec![],
+            blocks: vec![
+                block(vec![copy("i", Operand::Constant(5))], Terminator::Jump(1)),
+                block(vec![], Terminator::Branch { condition: i(), then: 2, otherwise: 3 }),
+                block(vec![binary("i", Operator::Subtract, i(), Operand::Constant(1))], Terminator::Jump(1)),
+                block(vec![copy("j", i())], Terminator::Return),
+            ],
+        };
+        into_ssa(&mut cfg);
+        assert_eq!(cfg.blocks[1].phis[0].to_string(), "i.2 = phi [b0: i.1], [b2: i.3]");
+
+        let sccp = Sccp::analyze(&cfg, OverflowMode::Error);
+        assert!(sccp.is_executable(3));
+        assert_eq!(sccp.value(&named("j")), Lattice::Overdefined);
+        assert!(cfg.to_function().is_none());
+    }
+
+    #[test]
+    fn test_sccp_pass() {
+        runs_ok_with("passes/pipeline.rs", &["--passes=sccp"], &[
+            "let a = 6u8;",
+            "let b = x * 1u8;",
+            "let c = b + 6u8;",
+            "let d = (x + 6u8) - (x + 6u8);",
+            "let e = c / 4u8;"
+        ]);
+        runs_err_with("constprop/shifts.rs", &["--passes=sccp"], &[
+            "Error: Constant evaluation panicked with 'attempt to shift left with overflow': 32 << 8 (in the initializer of 'c')"
+        ]);
+    }
+
+    // i = 5; while i != 0 { i = i - 1 }; j = i
+    fn countdown() -> Cfg {
+        let i = || Operand::Variable(named("i"));
+        Cfg {
+            name: "main".to_string(),
+            inputs: vec!["x".to_string()],
+            blocks: vec![
+                block(vec![copy("i", Operand::Constant(5))], Terminator::Jump(1)),
+                block(vec![], Terminator::Branch { condition: i(), then: 2, otherwise: 3 }),
+                block(vec![binary("i", Operator::Subtract, i(), Operand::Constant(1))], Terminator::Jump(1)),
+                block(vec![copy("j", i())], Terminator::Return),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_dataflow_constants() {
+        let analysis = Constants::new(OverflowMode::Error);
+
+        // Both sides of the branch are joined, unlike with sccp
+        let cfg = diamond(Operand::Constant(1));
+        let solution = solve(&analysis, &cfg);
+        assert_eq!(solution.start[3].value(&named("a")), Lattice::Overdefined);
+        assert_eq!(solution.end[1].value(&named("a")), Lattice::Constant(3));
+        assert_eq!(solution.end[0].value(&named("k")), Lattice::Constant(1));
+        assert_eq!(solution.end[0].value(&named("x")), Lattice::Overdefined);
+
+        // The loop counter changes on the back edge
+        let cfg = countdown();
+        let solution = solve(&analysis, &cfg);
+        assert_eq!(solution.start[1].value(&named("i")), Lattice::Overdefined);
+    }
+
+    #[test]
+    fn test_dataflow_liveness() {
+        let cfg = countdown();
+        let analysis = Liveness::new([named("j")].into_iter().collect());
+        let solution = solve(&analysis, &cfg);
+
+        assert_eq!(solution.start[0], BTreeSet::new());
+        assert_eq!(solution.start[1], [named("i")].into_iter().collect());
+        assert_eq!(solution.end[3], [named("j")].into_iter().collect());
+
+        // Before and after the copy in the exit block
+        let facts = instruction_facts(&analysis, &cfg, &solution, 3);
+        assert_eq!(facts, vec![[named("i")].into_iter().collect(), [named("j")].into_iter().collect()]);
+    }
+
+    #[test]
+    fn test_dataflow_reaching_definitions() {
+        let cfg = countdown();
+        let solution = solve(&ReachingDefinitions::new(&cfg), &cfg);
+
+        let definition = |block, index| Definition::Instruction(Location { block, index });
+        assert_eq!(solution.start[1], [Definition::Input("x".to_string()), definition(0, 0), definition(2, 0)].into_iter().collect());
+        assert_eq!(solution.end[2], [Definition::Input("x".to_string()), definition(2, 0)].into_iter().collect());
+    }
+
+    #[test]
+    fn test_dataflow_available_expressions() {
+        let x = || Operand::Variable(named("x"));
+        let add = Operation {
+            operator: Operator::Add,
+            arithmetic: Arithmetic::Panicking,
+            left: x(),
+            right: Operand::Constant(1),
+        };
+        let cfg = Cfg {
+            name: "main".to_string(),
+            inputs: vec!["x".to_string()],
+            blocks: vec![
+                block(vec![binary("a", Operator::Add, x(), Operand::Constant(1))], Terminator::Branch { condition: x(), then: 1, otherwise: 2 }),
+                block(vec![binary("b", Operator::Multiply, x(), Operand::Constant(2))], Terminator::Jump(3)),
+                block(vec![], Terminator::Jump(3)),
+                block(vec![copy("x", Operand::Constant(0))], Terminator::Return),
+            ],
+        };
+        let solution = solve(&AvailableExpressions, &cfg);
+
+        // x * 2 is only computed on one side of the branch
+        assert_eq!(solution.start[3], MustSet(Some([add].into_iter().collect())));
+        // Redefining x kills everything that reads it
+        assert_eq!(solution.end[3], MustSet(Some(BTreeSet::new())));
+    }
+
+    #[test]
+    fn test_ranges_inputs() {
+        warns_with(
+            "ranges/inputs.rs",
+            &["--ranges"],
+            &[
+                "Warning: This operation may overflow: a * 2u8 is in [0, 510] (in the initializer of 'double')",
+                "Warning: This operation may divide by zero: b / (a >> 4u8) divides by [0, 15] (in the initializer of 'quotient')",
+            ],
+        );
+
+        // a / 2u8 + 100u8 is at most 227, so it is proven safe
+        let output = run_program_with("ranges/inputs.rs", &["--ranges"]);
+        assert!(!String::from_utf8(output.stderr).unwrap().contains("'safe'"));
+    }
+
+    #[test]
+    fn test_ranges_input_range() {
+        let output = run_program_with("ranges/inputs.rs", &["--ranges", "--input-range", "a=16..=127", "--input-range", "b=0..10"]);
+        assert!(output.status.success(), "Failed: {:?}", output);
+        // Both operations are proven safe once a is known to be between 16 and 127
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+
+        runs_err_with("ranges/inputs.rs", &["--ranges", "--input-range", "c=0..10"], &["Error: Unknown input 'c' in an input range."]);
+        runs_err_with("ranges/inputs.rs", &["--ranges", "--input-range", "a=0..300"], &["Error: The input range 'a=0..300' is out of the range of u8."]);
+    }
+
+    #[test]
+    fn test_ranges_always_overflows() {
+        runs_err_with(
+            "ranges/always.rs",
+            &["--ranges"],
+            &[
+                "Warning: This operation may overflow: (a / 4u8) + 200u8 is in [200, 263] (in the initializer of 'b')",
+                "Error: This operation always overflows: b + 200u8 is in [400, 455] (in the initializer of 'c')",
+            ],
+        );
+        warns_with(
+            "ranges/always.rs",
+            &["--ranges", "--overflow=panic", "--input-range", "a=0..4"],
+            &["Warning: This operation always overflows and will panic at runtime with 'attempt to add with overflow': b + 200u8 is in [400, 400] (in the initializer of 'c')"],
+        );
+
+        // Wrapping operations never overflow
+        let output = run_program_with("ranges/always.rs", &["--ranges", "--overflow=wrap"]);
+        assert!(output.status.success(), "Failed: {:?}", output);
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+    }
+
+    #[test]
+    fn test_dataflow_intervals() {
+        let cfg = countdown();
+        let analysis = Intervals::new(OverflowMode::Error).with_input("x", Interval::new(0, 9));
+        let solution = solve(&analysis, &cfg);
+
+        assert_eq!(solution.start[1].range(&named("x")), Some(Interval::new(0, 9)));
+        // The counter goes down from 5 on the back edge
+        assert_eq!(solution.start[1].range(&named("i")), Some(Interval::new(0, 5)));
+        assert_eq!(solution.end[3].range(&named("j")), Some(Interval::new(0, 5)));
+
+        // Branches do not narrow ranges, so i - 1 may overflow
+        let decrement = &cfg.blocks[2].instructions[0];
+        let outcome = analysis.outcome(decrement, &solution.start[2]).unwrap();
+        assert_eq!(outcome.verdict, Verdict::MayPanic(Panic::Overflow(Operator::Subtract)));
+        assert_eq!(outcome.exact, Some(Interval::new(-1, 4)));
+        assert_eq!(outcome.result, Some(Interval::new(0, 4)));
+    }
+
+    #[test]
+    fn test_parser_bitwise() {
+        runs_ok("parser/bitwise.rs", false, &[
+            "let a = ((x) & 12u8) | (y) ^ 3u8;",
+            "let b = (x) | (y) & (1u8) << 2u8;",
+            "let c = (((x) | y)) & 15u8;",
+            "let d = (x) ^ (y) + 1u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_constprop_bitwise() {
+        runs_ok_with("constprop/bitwise.rs", &["--constprop", "--reassociate"], &[
+            "let a = 11u8;",
+            "let b = 244u8;",
+            "let c = x & 8u8;"
+        ]);
+    }
+
+    #[test]
+    fn test_simplify_known_bits() {
+        runs_ok_with("simplify/known_bits.rs", &["--simplify"], &[
+            "let low = 0u8;",
+            "let masked = high;",
+            "let set = odd;",
+            "let flipped = 0u8;",
+            // x * 2u8 may overflow, so it cannot be dropped
+            "let parity = (x * 2u8) & 1u8;"
+        ]);
+        runs_ok_with("simplify/known_bits.rs", &["--simplify", "--overflow=wrap"], &["let parity = 0u8;"]);
+    }
+
+    #[test]
+    fn test_known_bits() {
+        let source = std::fs::read_to_string(format!("{}simplify/known_bits.rs", SNIPPET_PATH)).unwrap();
+        let program = parse(&source).unwrap();
+        let bits = variable_bits(&program, OverflowMode::Error);
+
+        assert_eq!(bits["x"], Bits::unknown());
+        assert_eq!(bits["even"].to_string(), "???????0");
+        assert_eq!(bits["low"].value(), Some(0));
+        assert_eq!(bits["high"].to_string(), "0000????");
+        assert_eq!(bits["odd"].to_string(), "???????1");
+        // Multiplying by 2 shifts the known bits, like a shift
+        assert_eq!(bits["parity"], Bits::constant(0));
+        assert_eq!(bits["flipped"], Bits::unknown());
+
+        // Carries propagate from the known low bits
+        let program = parse("fn main(x: u8) {\n    let a = x << 2u8;\n    let b = a + 3u8;\n    let c = b - 1u8;\n}\n").unwrap();
+        let bits = variable_bits(&program, OverflowMode::Error);
+        assert_eq!(bits["b"].to_string(), "??????11");
+        assert_eq!(bits["c"].to_string(), "??????10");
+        // A saturated result may be 255
+        let bits = variable_bits(&program, OverflowMode::Saturate);
+        assert_eq!(bits["c"], Bits::unknown());
+    }
+
+    // runs a program with the interpreter, with the given extra command-line arguments.
+    fn run_interpreter(file_name: &str, extra_args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_rustic"))
+            .arg("run")
+            .arg(format!("{}{}", SNIPPET_PATH, file_name))
+            .args(extra_args)
+            .output()
+            .expect("Failed to execute process")
+    }
+
+    #[test]
+    fn test_run() {
+        let output = run_interpreter("run/program.rs", &["--arg", "a=3", "--arg", "b=7u8"]);
+        assert!(output.status.success(), "Failed: {:?}", output);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "sum = 10\nproduct = 20\nquotient = 5\nflag = 1\nmasked = 20\n"
+        );
+
+        let output = run_interpreter("run/program.rs", &["--arg", "a=3", "--arg", "b=7", "--output", "quotient"]);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "quotient = 5\n");
+    }
+
+    #[test]
+    fn test_run_panics() {
+        let output = run_interpreter("run/program.rs", &["--arg", "a=100", "--arg", "b=100"]);
+        assert_eq!(output.status.code(), Some(101));
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "thread 'main' panicked in the initializer of 'product':\nattempt to multiply with overflow\n"
+        );
+
+        // Wrapping arithmetic only panics on the division by zero
+        let output = run_interpreter("run/program.rs", &["--arg", "a=100", "--arg", "b=100", "--overflow=wrap"]);
+        assert_eq!(output.status.code(), Some(101));
+        assert!(String::from_utf8(output.stderr).unwrap().contains("initializer of 'quotient':\nattempt to divide by zero"));
+    }
+
+    #[test]
+    fn test_run_arguments() {
+        let output = run_interpreter("run/program.rs", &["--arg", "a=3"]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8(output.stderr).unwrap().contains("Error: Missing a value for the input 'b', pass it with --arg b=<value>."));
+
+        let output = run_interpreter("run/program.rs", &["--arg", "a=3", "--arg", "b=256"]);
+        assert!(String::from_utf8(output.stderr).unwrap().contains("Error: The argument 'b=256' is out of the range of u8."));
+
+        let output = run_interpreter("run/program.rs", &["--arg", "a=3", "--arg", "b=7", "--arg", "c=1"]);
+        assert!(String::from_utf8(output.stderr).unwrap().contains("Error: There is no input named 'c'."));
+    }
+
+    // Optimized programs compute the same bindings, and panic on the same inputs
+    #[test]
+    fn test_optimizations_preserve_behavior() {
+        let files = ["strength/division.rs", "strength/powers.rs", "strength/panic_order.rs", "reassociate/chains.rs", "simplify/known_bits.rs", "run/program.rs", "cse/panic_order.rs"];
+        for (file_name, costs) in files.into_iter().flat_map(|file_name| [(file_name, "default"), (file_name, "no-div")]) {
+            let source = std::fs::read_to_string(format!("{}{}", SNIPPET_PATH, file_name)).unwrap();
+            let program = parse(&source).unwrap();
+            let mut optimized = program.clone();
+            let mut options = PassOptions::new(OverflowMode::Panic);
+            options.costs = CostModel::from_name(costs).unwrap();
+            PassManager::optimization_level(options, 2).unwrap().run(&mut optimized);
+
+            let interpreter = Interpreter::new(OverflowMode::Panic);
+            let names: Vec<String> = program.inputs.iter().map(|input| input.name.clone()).collect();
+            let values = if names.len() == 1 { 256 } else { 256 * 256 };
+            for index in 0..values {
+                let arguments: BTreeMap<String, u8> = names
+                    .iter()
+                    .enumerate()
+                    .map(|(position, name)| (name.clone(), (index >> (8 * position)) as u8))
+                    .collect();
+
+                match (interpreter.run(&program, &arguments), interpreter.run(&optimized, &arguments)) {
+                    (Ok(expected), Ok(actual)) => {
+                        for binding in &expected {
+                            if let Some(found) = actual.iter().find(|(name, _)| *name == binding.0) {
+                                assert_eq!(found, binding, "{} ({}) on {:?}", file_name, costs, arguments);
+                            }
+                        }
+                    }
+                    (Err(expected), Err(actual)) => assert_eq!(expected, actual, "{} ({}) on {:?}", file_name, costs, arguments),
+                    (expected, actual) => panic!("{} ({}) on {:?}: {:?} became {:?}", file_name, costs, arguments, expected, actual),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_emit_bytecode() {
+        runs_ok_with("run/program.rs", &["--emit=bytecode"], &[
+            "fn main(a: u8, b: u8) {\n    0000  load 0          // a\n    0003  load 1          // b\n    0006  add\n    0008  store 2         // sum\n",
+            "    0037  load 0          // a\n    0040  load 1          // b\n    0043  sub.overflowed\n    0045  store 5         // flag\n",
+            "    0061  or\n    0063  store 6         // masked\n}",
+        ]);
+    }
+
+    #[test]
+    fn test_bytecode_format() {
+        let source = std::fs::read_to_string(format!("{}run/program.rs", SNIPPET_PATH)).unwrap();
+        let bytecode = compile(&parse(&source).unwrap());
+        let bytes = encode(&bytecode);
+        assert!(bytes.starts_with(b"RBC\0\x01\x00"));
+        assert_eq!(decode(&bytes), Ok(bytecode.clone()));
+
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        assert_eq!(decode(&newer), Err("Unsupported bytecode version 2, expected 1.".to_string()));
+
+        assert_eq!(decode(&bytes[..bytes.len() - 1]), Err("Invalid bytecode: the file is truncated.".to_string()));
+        assert_eq!(decode(b"fn main() {}"), Err("Not a bytecode file.".to_string()));
+
+        // A store with an empty stack, then a load of a slot that does not exist
+        let mut invalid = Bytecode { code: vec![bytecode::Instruction::Store(2)], ..bytecode.clone() };
+        assert_eq!(decode(&encode(&invalid)), Err("Invalid bytecode: the stack underflows at offset 0.".to_string()));
+        invalid.code = vec![bytecode::Instruction::Push(1), bytecode::Instruction::Load(7)];
+        assert_eq!(decode(&encode(&invalid)), Err("Invalid bytecode: slot 7 does not exist at offset 2.".to_string()));
+    }
+
+    // The virtual machine computes the same bindings as the interpreter, and panics in the same bindings
+    #[test]
+    fn test_vm_matches_interpreter() {
+        let source = std::fs::read_to_string(format!("{}run/program.rs", SNIPPET_PATH)).unwrap();
+        let program = parse(&source).unwrap();
+        let bytecode = compile(&program);
+
+        for overflow in [OverflowMode::Panic, OverflowMode::Wrap, OverflowMode::Saturate] {
+            let interpreter = Interpreter::new(overflow);
+            let mut vm = Vm::new(overflow);
+            for index in 0..256 * 256 {
+                let arguments = BTreeMap::from([("a".to_string(), index as u8), ("b".to_string(), (index >> 8) as u8)]);
+                assert_eq!(vm.run_named(&bytecode, &arguments), interpreter.run(&program, &arguments), "{:?}", arguments);
+            }
+        }
+
+        let arguments = BTreeMap::from([("a".to_string(), 3)]);
+        assert_eq!(Vm::new(OverflowMode::Panic).run_named(&bytecode, &arguments), Err(RuntimeError::MissingInput("b".to_string())));
+    }
+
+    #[test]
+    fn test_run_bytecode() {
+        let path = std::env::temp_dir().join(format!("rustic-{}.rbc", std::process::id()));
+        let path = path.to_str().unwrap();
+        runs_ok_with("run/program.rs", &["--constprop", "--write-bytecode", path], &[]);
+
+        let output = Command::new(env!("CARGO_BIN_EXE_rustic"))
+            .args(["run", path, "--arg", "a=3", "--arg", "b=7"])
+            .output()
+            .expect("Failed to execute process");
+        std::fs::remove_file(path).unwrap();
+        assert!(output.status.success(), "Failed: {:?}", output);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "sum = 10\nproduct = 20\nquotient = 5\nflag = 1\nmasked = 20\n"
+        );
+
+        let output = run_interpreter("run/program.rs", &["--vm", "--arg", "a=100", "--arg", "b=100"]);
+        assert_eq!(output.status.code(), Some(101));
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "thread 'main' panicked in the initializer of 'product':\nattempt to multiply with overflow\n"
+        );
+    }
+
+    #[test]
+    fn test_emit_asm() {
+        runs_ok_with("run/program.rs", &["--emit=asm"], &[
+            "\t.globl\trustic_main\n",
+            "rustic_main:\n\tpushq\t%rbp\n\tmovq\t%rsp, %rbp\n\tpushq\t%rbx\n\tpushq\t%r12\n\tpushq\t%r13\n\tsubq\t$8, %rsp\n\tmovb\t%dil, %r10b\n\tmovb\t%sil, %bl\n\tmovq\t%rdx, %r11\n",
+            "\t# sum = add a, b\n\tmovzbl\t%r10b, %eax\n\tmovzbl\t%bl, %ecx\n\taddb\t%cl, %al\n\tjc\t.Lrustic_main_panic0\n\tmovb\t%al, 0(%r11)\n\tmovb\t%al, %r12b\n",
+            "\tleaq\t-24(%rbp), %rsp\n\tpopq\t%r13\n\tpopq\t%r12\n\tpopq\t%rbx\n\tpopq\t%rbp\n\tret\n",
+            ".Lrustic_main_panic1:\n\tmovl\t$259, %eax\n",
+        ]);
+    }
+
+    // The status and bindings that a compiled program returns, from the interpreter
+    fn expected_status(program: &Program, overflow: OverflowMode, arguments: &BTreeMap<String, u8>) -> (u32, Vec<u8>) {
+        let names = abi::bindings(&lower(program));
+        match Interpreter::new(overflow).run(program, arguments) {
+            Ok(bindings) => (0, bindings.into_iter().map(|(_, val)| val).collect()),
+            Err(RuntimeError::Panic { panic, variable }) => {
+                (abi::status(&panic, names.iter().position(|name| *name == variable).unwrap()), Vec::new())
+            }
+            Err(runtime_error) => panic!("{:?}", runtime_error),
+        }
+    }
+
+    // Programs with a single operation of every kind, under every overflow mode, and the program of run/program.rs
+    fn differential_programs() -> Vec<(String, OverflowMode)> {
+        let mut programs = Vec::new();
+        for operator in ["+", "-", "*", "/", "<<", ">>", "&", "|", "^"] {
+            for overflow in [OverflowMode::Panic, OverflowMode::Wrap, OverflowMode::Saturate] {
+                programs.push((format!("let r = a {} b;", operator), overflow));
+            }
+        }
+        for method in ["add", "sub", "mul", "div"] {
+            for call in ["wrapping_{}(b)", "saturating_{}(b)", "checked_{}(b).unwrap()", "overflowing_{}(b).0", "overflowing_{}(b).1 as u8"] {
+                programs.push((format!("let r = a.{};", call.replace("{}", method)), OverflowMode::Panic));
+            }
+        }
+        let source = std::fs::read_to_string(format!("{}run/program.rs", SNIPPET_PATH)).unwrap();
+        let body = &source[source.find('{').unwrap() + 1..source.rfind('}').unwrap()];
+        for overflow in [OverflowMode::Panic, OverflowMode::Wrap, OverflowMode::Saturate] {
+            programs.push((body.to_string(), overflow));
+        }
+        programs
+    }
+
+    // The hash that the C harnesses compute over the statuses and bindings of a program on every pair of inputs
+    fn expected_hash(program: &Program, overflow: OverflowMode) -> u32 {
+        let mut hash: u32 = 0;
+        for index in 0..256 * 256 {
+            let arguments = BTreeMap::from([("a".to_string(), index as u8), ("b".to_string(), (index >> 8) as u8)]);
+            let (status, bindings) = expected_status(program, overflow, &arguments);
+            hash = hash.wrapping_mul(31).wrapping_add(status);
+            for val in bindings {
+                hash = hash.wrapping_mul(31).wrapping_add(val as u32);
+            }
+        }
+        hash
+    }
+
+    // The expected hash of every differential program, computed once for all the backends
+    fn expected_hashes() -> &'static [u32] {
+        static HASHES: std::sync::OnceLock<Vec<u32>> = std::sync::OnceLock::new();
+        HASHES.get_or_init(|| {
+            differential_programs()
+                .iter()
+                .enumerate()
+                .map(|(index, (body, overflow))| {
+                    expected_hash(&parse(&format!("fn f{}(a: u8, b: u8) {{\n{}\n}}", index, body)).unwrap(), *overflow)
+                })
+                .collect()
+        })
+    }
+
+    // A C harness that runs the compiled differential programs, which return their status, on every pair of inputs
+    // and prints the hash of the results of each one
+    fn status_harness(functions: &[Function]) -> String {
+        let mut harness = String::from("#include <stdio.h>\n#include <stdint.h>\n\n");
+        let mut calls = String::new();
+        for function in functions {
+            harness += &format!("uint32_t rustic_{}(uint8_t, uint8_t, uint8_t *);\n", function.name);
+            calls += &format!(
+                "    h = 0;\n    for (int i = 0; i < 65536; i++) {{\n        uint32_t s = rustic_{}(i & 255, i >> 8, out);\n        h = h * 31 + s;\n        for (int k = 0; !s && k < {}; k++) h = h * 31 + out[k];\n    }}\n    printf(\"%u\\n\", h);\n",
+                function.name,
+                abi::bindings(function).len()
+            );
+        }
+        harness + &format!("\nint main(void) {{\n    uint8_t out[16];\n    uint32_t h;\n{}    return 0;\n}}\n", calls)
+    }
+
+    // The differential programs, lowered with the names the harnesses call them by
+    fn differential_functions() -> Vec<Function> {
+        differential_programs()
+            .iter()
+            .enumerate()
+            .map(|(index, (body, _))| lower(&parse(&format!("fn f{}(a: u8, b: u8) {{\n{}\n}}", index, body)).unwrap()))
+            .collect()
+    }
+
+    // Checks the hashes that a harness printed against the interpreter
+    fn assert_hashes(output: &str) {
+        let hashes: Vec<u32> = output.lines().map(|line| line.parse().unwrap()).collect();
+        for (index, (body, overflow)) in differential_programs().iter().enumerate() {
+            assert_eq!(hashes[index], expected_hashes()[index], "{} in {:?} mode", body.trim(), overflow);
+        }
+    }
+
+    // Compiled programs compute the same bindings as the interpreter, and panic in the same bindings.
+    // Every program is run on every pair of inputs by a C harness, which prints a hash of the results.
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn test_asm_matches_interpreter() {
+        let functions = differential_functions();
+        let assembly: String = functions
+            .iter()
+            .zip(differential_programs())
+            .map(|(function, (_, overflow))| x86_64::emit(function, overflow))
+            .collect();
+        assert_hashes(&harness_output(&[("program.s", &assembly), ("harness.c", &status_harness(&functions))]));
+    }
+
+    // Inputs after the sixth one are passed on the stack
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn test_asm_stack_arguments() {
+        let program = parse("fn many(a: u8, b: u8, c: u8, d: u8, e: u8, f: u8, g: u8, h: u8) {\n    let s = a + b + c + d + e + f + g + h;\n    let t = h - a;\n}").unwrap();
+        let assembly = x86_64::emit(&lower(&program), OverflowMode::Panic);
+        let harness = "#include <stdio.h>\n#include <stdint.h>\n\nuint32_t rustic_many(uint8_t, uint8_t, uint8_t, uint8_t, uint8_t, uint8_t, uint8_t, uint8_t, uint8_t *);\n\nint main(void) {\n    uint8_t out[2];\n    uint32_t s = rustic_many(1, 2, 3, 4, 5, 6, 7, 8, out);\n    printf(\"%u %u %u\\n\", s, out[0], out[1]);\n    s = rustic_many(9, 2, 3, 4, 5, 6, 7, 8, out);\n    printf(\"%u\\n\", s);\n    return 0;\n}\n";
+        // t = 8 - 9 overflows in the second binding
+        assert_eq!(harness_output(&[("program.s", &assembly), ("harness.c", harness)]), "0 36 7\n258\n");
+    }
+
+    // Compiles and links source files with the system C compiler in a temporary directory,
+    // then runs the program once with every list of arguments. Returns the outputs of the runs.
+    fn compile_and_run(sources: &[(&str, &str)], runs: &[&[&str]]) -> Vec<Output> {
+        static BUILDS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let build = BUILDS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let directory = std::env::temp_dir().join(format!("rustic-cc-{}-{}", std::process::id(), build));
+        std::fs::create_dir_all(&directory).unwrap();
+        for (file_name, source) in sources {
+            std::fs::write(directory.join(file_name), source).unwrap();
+        }
+
+        let compiled = Command::new("cc")
+            .current_dir(&directory)
+            .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-O1", "-o", "program"])
+            .args(sources.iter().map(|(file_name, _)| file_name))
+            .output()
+            .expect("Failed to run the C compiler");
+        assert!(compiled.status.success(), "Failed: {:?}", compiled);
+        let outputs = runs
+            .iter()
+            .map(|args| Command::new(directory.join("program")).args(*args).output().expect("Failed to run the program"))
+            .collect();
+        std::fs::remove_dir_all(&directory).unwrap();
+        outputs
+    }
+
+    // The standard output of a harness that ran successfully
+    fn harness_output(sources: &[(&str, &str)]) -> String {
+        let output = compile_and_run(sources, &[&[]]).remove(0);
+        assert!(output.status.success(), "Failed: {:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn test_emit_riscv() {
+        runs_ok_with("run/program.rs", &["--emit=riscv"], &[
+            "rustic_main:\n\taddi\tsp, sp, -16\n\tsw\ts1, 12(sp)\n\taddi\tt3, a0, 0\n\taddi\tt4, a1, 0\n\taddi\tt6, a2, 0\n",
+            "\t# sum = add a, b\n\taddi\tt0, t3, 0\n\taddi\tt1, t4, 0\n\tadd\tt0, t0, t1\n\tsrli\tt2, t0, 8\n\tbne\tt2, zero, .Lrustic_main_panic0\n\tsb\tt0, 0(t6)\n\taddi\tt5, t0, 0\n",
+            ".Lrustic_main_return:\n\tlw\ts1, 12(sp)\n\taddi\tsp, sp, 16\n\tret\n.Lrustic_main_panic0:\n\tli\ta0, 1\n\tj\t.Lrustic_main_return\n",
+        ]);
+    }
+
+    #[test]
+    fn test_riscv_encoding() {
+        use rustic::codegen::riscv::isa::*;
+        let known = [
+            (Instruction::Immediate { operation: ImmediateOp::Addi, rd: Register::SP, rs1: Register::SP, imm: -16 }, 0xff010113),
+            (Instruction::Jalr { rd: Register::ZERO, rs1: Register::RA, offset: 0 }, 0x00008067),
+            (Instruction::Register { operation: RegisterOp::Add, rd: Register::T0, rs1: Register::T0, rs2: Register::T1 }, 0x006282b3),
+            (Instruction::Register { operation: RegisterOp::Mul, rd: Register(10), rs1: Register(10), rs2: Register(11) }, 0x02b50533),
+            (Instruction::Store { operation: StoreOp::Sw, rs1: Register::SP, rs2: Register::RA, offset: 12 }, 0x00112623),
+            (Instruction::Lui { rd: Register(10), imm: 0x12345 }, 0x12345537),
+            (Instruction::Immediate { operation: ImmediateOp::Srai, rd: Register::T0, rs1: Register::T0, imm: 3 }, 0x4032d293),
+        ];
+        for (instruction, word) in known {
+            assert_eq!(instruction.encode(), word, "{}", instruction);
+            assert_eq!(Instruction::decode(word), Some(instruction));
+        }
+
+        // Branch and jump offsets keep every bit, backwards and forwards
+        for offset in [-4096, -2050, -8, 2, 2046, 4094] {
+            let branch = Instruction::Branch { operation: BranchOp::Bgeu, rs1: Register::T1, rs2: Register::ZERO, offset };
+            assert_eq!(Instruction::decode(branch.encode()), Some(branch));
+        }
+        for offset in [-(1 << 20), -2048, 4, 1 << 19, (1 << 20) - 2] {
+            let jump = Instruction::Jal { rd: Register::RA, offset };
+            assert_eq!(Instruction::decode(jump.encode()), Some(jump));
+        }
+        assert_eq!(Instruction::decode(0), None);
+    }
+
+    // Programs compiled to RV32IM and run on the simulator compute the same bindings as the interpreter,
+    // and panic in the same bindings. Every left operand is paired with the small right operands, where
+    // shifts and divisions change behaviour, and a sample of the others.
+    #[test]
+    fn test_riscv_matches_interpreter() {
+        for (index, (body, overflow)) in differential_programs().iter().enumerate() {
+            let program = parse(&format!("fn f{}(a: u8, b: u8) {{\n{}\n}}", index, body)).unwrap();
+            let mut harness = Harness::new(&riscv::emit(&lower(&program), *overflow));
+            for b in (0..=16).chain((17..=255).step_by(13)).chain([255]) {
+                for a in 0..=255 {
+                    let arguments = BTreeMap::from([("a".to_string(), a), ("b".to_string(), b)]);
+                    let (expected, bindings) = expected_status(&program, *overflow, &arguments);
+                    let (status, values) = harness.call(&[a, b]).unwrap();
+                    assert_eq!(status, expected, "{} in {:?} mode on {:?}", body.trim(), overflow, arguments);
+                    if status == 0 {
+                        assert_eq!(values, bindings, "{} in {:?} mode on {:?}", body.trim(), overflow, arguments);
+                    }
+                }
+            }
+        }
+    }
+
+    // Inputs after the eighth one are passed on the stack
+    #[test]
+    fn test_riscv_stack_arguments() {
+        let program = parse("fn many(a: u8, b: u8, c: u8, d: u8, e: u8, f: u8, g: u8, h: u8, i: u8, j: u8) {\n    let s = a + b + c + d + e + f + g + h + i + j;\n    let t = j - a;\n}").unwrap();
+        let mut harness = Harness::new(&riscv::emit(&lower(&program), OverflowMode::Panic));
+        assert_eq!(harness.call(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]), Ok((0, vec![55, 9])));
+        // t = 10 - 11 overflows in the second binding
+        assert_eq!(harness.call(&[11, 2, 3, 4, 5, 6, 7, 8, 9, 10]), Ok((abi::status(&Panic::Overflow(Operator::Subtract), 1), vec![65, 0])));
+    }
+
+    #[test]
+    fn test_run_riscv() {
+        let output = run_interpreter("run/program.rs", &["--riscv", "--arg", "a=3", "--arg", "b=7"]);
+        assert!(output.status.success(), "Failed: {:?}", output);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "sum = 10\nproduct = 20\nquotient = 5\nflag = 1\nmasked = 20\n"
+        );
+
+        let output = run_interpreter("run/program.rs", &["--riscv", "--arg", "a=3", "--arg", "b=3", "--overflow=wrap"]);
+        assert_eq!(output.status.code(), Some(101));
+        assert!(String::from_utf8(output.stderr).unwrap().contains("initializer of 'quotient':\nattempt to divide by zero"));
+    }
+
+    #[test]
+    fn test_emit_c() {
+        runs_ok_with("run/program.rs", &["--emit=c"], &[
+            "void rustic_main(uint8_t a, uint8_t b, uint8_t *_bindings) {\n",
+            "    // product = mul sum, 2\n    if (sum * 2 > 255)\n        rustic_panic(\"product\", \"attempt to multiply with overflow\");\n    uint8_t product = (uint8_t)(sum * 2);\n    _bindings[1] = product;\n",
+            "    // flag = sub.overflowed a, b\n    uint8_t flag = a < b;\n",
+        ]);
+    }
+
+    // Programs compiled to C compute the same bindings as the interpreter, and panic in the same bindings.
+    // The harness turns panics into statuses, with a rustic_panic that jumps back to it.
+    #[test]
+    fn test_c_matches_interpreter() {
+        let programs = differential_programs();
+        let messages: Vec<String> = (1..=8).map(|code| format!("\"{}\"", abi::decode_status(code).unwrap().0.message())).collect();
+        let mut harness = String::from("#include <setjmp.h>\n#include <stdint.h>\n#include <stdio.h>\n#include <string.h>\n\n");
+        harness += "static jmp_buf panicked;\nstatic const char **names;\nstatic int count;\nstatic volatile uint32_t status;\n\n";
+        harness += &format!("static const char *messages[] = {{\"\", {}}};\n\n", messages.join(", "));
+        harness += "static void test_panic(const char *binding, const char *message) {\n";
+        harness += "    for (uint32_t code = 1; code <= 8; code++)\n        if (!strcmp(messages[code], message))\n            status = code;\n";
+        harness += "    for (int index = 0; index < count; index++)\n        if (!strcmp(names[index], binding))\n            status |= (uint32_t)index << 8;\n";
+        harness += "    longjmp(panicked, 1);\n}\n\n#define rustic_panic test_panic\n\n";
+
+        let mut calls = String::new();
+        for (index, (body, overflow)) in programs.iter().enumerate() {
+            let program = parse(&format!("fn f{}(a: u8, b: u8) {{\n{}\n}}", index, body)).unwrap();
+            let function = lower(&program);
+            harness += &c::emit(&function, *overflow);
+            let bindings: Vec<String> = abi::bindings(&function).iter().map(|name| format!("\"{}\"", name)).collect();
+            calls += &format!(
+                "    static const char *names_f{}[] = {{{}}};\n    names = names_f{}, count = {}, h = 0;\n    for (volatile int i = 0; i < 65536; i++) {{\n        status = 0;\n        if (!setjmp(panicked))\n            rustic_f{}(i & 255, i >> 8, out);\n        h = h * 31 + status;\n        for (int k = 0; !status && k < count; k++) h = h * 31 + out[k];\n    }}\n    printf(\"%u\\n\", h);\n",
+                index,
+                bindings.join(", "),
+                index,
+                bindings.len(),
+                index
+            );
+        }
+        harness += &format!("\nint main(void) {{\n    uint8_t out[16];\n    volatile uint32_t h;\n{}    return 0;\n}}\n", calls);
+
+        assert_hashes(&harness_output(&[("harness.c", &harness)]));
+    }
+
+    // The main harness reads the inputs from the command line, prints the bindings and aborts on panics
+    #[test]
+    fn test_c_main() {
+        let output = run_program_with("run/program.rs", &["--emit=c", "--c-main"]);
+        assert!(output.status.success(), "Failed: {:?}", output);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let code = stdout.split_once("C code:\n\n").unwrap().1;
+
+        let outputs = compile_and_run(&[("program.c", code)], &[&["3", "7"], &["100", "100"], &["3", "256"], &["3"]]);
+        assert_eq!(String::from_utf8(outputs[0].stdout.clone()).unwrap(), "sum = 10\nproduct = 20\nquotient = 5\nflag = 1\nmasked = 20\n");
+        assert!(!outputs[1].status.success());
+        assert_eq!(
+            String::from_utf8(outputs[1].stderr.clone()).unwrap(),
+            "thread 'main' panicked in the initializer of 'product':\nattempt to multiply with overflow\n"
+        );
+        assert_eq!(outputs[2].status.code(), Some(2));
+        assert_eq!(String::from_utf8(outputs[2].stderr.clone()).unwrap(), "The argument '256' is not a u8.\n");
+        assert_eq!(outputs[3].status.code(), Some(2));
+        assert!(String::from_utf8(outputs[3].stderr.clone()).unwrap().ends_with(" <a> <b>\n"));
+    }
+
+    #[test]
+    fn test_emit_wat() {
+        runs_ok_with("run/program.rs", &["--emit=wat"], &[
+            "(module\n  (func $main (export \"main\") (param $a i32) (param $b i32) (result i32 i32 i32 i32 i32)\n",
+            "    (local $%0 i32)\n",
+            "    local.get $a\n    i32.const 255\n    i32.and\n    local.set $a\n",
+            "    ;; product = mul sum, 2\n    local.get $sum\n    i32.const 2\n    i32.mul\n    local.set $%wide\n    local.get $%wide\n    i32.const 255\n    i32.gt_u\n    if\n      unreachable\n    end\n",
+            "    ;; flag = sub.overflowed a, b\n    local.get $a\n    local.get $b\n    i32.lt_u\n    local.set $flag\n",
+            "    local.get $flag\n    local.get $masked\n  )\n)",
+        ]);
+        runs_ok_with("run/program.rs", &["--emit=wasm"], &["00000000  00 61 73 6d 01 00 00 00 01 0b 01 60 02 7f 7f 05\n"]);
+    }
+
+    // Modules survive a round trip through the binary format, apart from their comments
+    #[test]
+    fn test_wasm_format() {
+        let source = std::fs::read_to_string(format!("{}run/program.rs", SNIPPET_PATH)).unwrap();
+        let module = wat::emit(&lower(&parse(&source).unwrap()), OverflowMode::Panic);
+        let bytes = wasm::encode(&module);
+        assert_eq!(wasm::decode(&bytes), Ok(Module { comments: Vec::new(), ..module.clone() }));
+
+        // Constants are signed LEB128
+        for val in [0, 63, 64, -1, -64, -65, 255, i32::MIN, i32::MAX] {
+            let module = Module { results: 1, body: vec![WasmInstruction::I32Const(val)], ..module.clone() };
+            assert_eq!(wasm::decode(&wasm::encode(&module)).unwrap().body, module.body);
+        }
+
+        // Without a name section, locals are named after their index
+        let unnamed = &bytes[..bytes.windows(5).position(|window| window == b"\x04name").unwrap() - 2];
+        assert_eq!(wasm::decode(unnamed).unwrap().names[10], "10");
+
+        assert_eq!(wasm::decode(b"RBC\0"), Err("Not a WebAssembly module.".to_string()));
+        assert_eq!(wasm::decode(b"\0asm\x02\0\0\0"), Err("Unsupported WebAssembly version 2, expected 1.".to_string()));
+        assert_eq!(wasm::decode(&bytes[..40]), Err("Invalid WebAssembly: the module is truncated.".to_string()));
+        let mut unsupported = bytes.clone();
+        let offset = bytes.iter().position(|byte| *byte == BinaryOp::Add.opcode()).unwrap();
+        unsupported[offset] = 0x6d;
+        assert!(wasm::decode(&unsupported).unwrap_err().starts_with("Invalid WebAssembly: unsupported opcode 0x6d"));
+
+        // The module written with --write-wasm runs like the program
+        let path = std::env::temp_dir().join(format!("rustic-{}.wasm", std::process::id()));
+        let output = run_program_with("run/program.rs", &["--write-wasm", path.to_str().unwrap()]);
+        assert!(output.status.success(), "Failed: {:?}", output);
+        let module = wasm::decode(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(validate(&module), Ok(()));
+        assert_eq!(invoke(&module, &[3, 7]), Ok(vec![10, 20, 5, 1, 20]));
+        // The division by zero is checked before dividing
+        assert_eq!(invoke(&module, &[3, 3]), Err(Trap::Unreachable));
+        let division = Module { results: 1, body: vec![WasmInstruction::I32Const(1), WasmInstruction::I32Const(0), WasmInstruction::Binary(BinaryOp::DivU)], ..module };
+        assert_eq!(invoke(&division, &[3, 3]), Err(Trap::IntegerDivideByZero));
+    }
+
+    #[test]
+    fn test_wasm_validator() {
+        let module = |params, results, body: Vec<WasmInstruction>| Module {
+            export: "f".to_string(),
+            params,
+            results,
+            locals: 1,
+            names: (0..params + 1).map(|index| index.to_string()).collect(),
+            body,
+            comments: Vec::new(),
+        };
+        use WasmInstruction::*;
+
+        assert_eq!(validate(&module(1, 1, vec![LocalGet(0), I32Const(1), Binary(BinaryOp::Add)])), Ok(()));
+        // Code after unreachable may pop values that were never pushed
+        assert_eq!(validate(&module(0, 1, vec![I32Const(1), If, Unreachable, Binary(BinaryOp::Add), LocalSet(0), End, LocalGet(0)])), Ok(()));
+        assert_eq!(validate(&module(0, 2, vec![Unreachable])), Ok(()));
+
+        assert_eq!(
+            validate(&module(0, 1, vec![I32Const(1), Binary(BinaryOp::Add)])),
+            Err("Invalid instruction 1 (Binary(Add)): the stack is empty.".to_string())
+        );
+        assert_eq!(validate(&module(1, 0, vec![LocalGet(2)])), Err("Invalid instruction 0 (LocalGet(2)): local 2 does not exist.".to_string()));
+        assert_eq!(validate(&module(0, 1, vec![])), Err("Invalid end of the function: the stack is empty.".to_string()));
+        assert_eq!(
+            validate(&module(0, 0, vec![I32Const(1)])),
+            Err("Invalid end of the function: 1 values are left on the stack.".to_string())
+        );
+        assert_eq!(
+            validate(&module(0, 0, vec![I32Const(1), If, I32Const(2), End])),
+            Err("Invalid instruction 3 (End): 1 values are left on the stack.".to_string())
+        );
+        assert_eq!(validate(&module(0, 0, vec![I32Const(1), If])), Err("An if block is not closed.".to_string()));
+        assert_eq!(validate(&module(0, 0, vec![End])), Err("Invalid instruction 0 (End): there is no block to end.".to_string()));
+    }
+
+    // Modules compute the same bindings as the interpreter, and trap where it panics.
+    // The upper bits of the arguments are set, and must be ignored.
+    #[test]
+    fn test_wasm_matches_interpreter() {
+        for (index, (body, overflow)) in differential_programs().iter().enumerate() {
+            let program = parse(&format!("fn f{}(a: u8, b: u8) {{\n{}\n}}", index, body)).unwrap();
+            let module = wat::emit(&lower(&program), *overflow);
+            assert_eq!(validate(&module), Ok(()), "{}", module);
+            for b in (0..=16).chain((17..=255).step_by(13)).chain([255]) {
+                for a in 0..=255 {
+                    let arguments = BTreeMap::from([("a".to_string(), a), ("b".to_string(), b)]);
+                    let (status, bindings) = expected_status(&program, *overflow, &arguments);
+                    let result = invoke(&module, &[a as i32 | -0x100, b as i32 | 0x7fff_ff00]);
+                    let expected = if status == 0 {
+                        Ok(bindings.into_iter().map(|val| val as i32).collect())
+                    } else {
+                        Err(Trap::Unreachable)
+                    };
+                    assert_eq!(result, expected, "{} in {:?} mode on {:?}", body.trim(), overflow, arguments);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_emit_llvm_ir() {
+        runs_ok_with("run/program.rs", &["--emit=llvm-ir"], &[
+            "define i32 @rustic_main(i8 zeroext %a, i8 zeroext %b, i8* %_bindings) {\n_start:\n",
+            "  ; sum = add a, b\n  %sum.pair = call { i8, i1 } @llvm.uadd.with.overflow.i8(i8 %a, i8 %b)\n  %sum.overflow = extractvalue { i8, i1 } %sum.pair, 1\n  br i1 %sum.overflow, label %_panic1, label %_bb1\n\n_bb1:\n  %sum = extractvalue { i8, i1 } %sum.pair, 0\n  %_slot0 = getelementptr inbounds i8, i8* %_bindings, i64 0\n  store i8 %sum, i8* %_slot0\n",
+            "  ; flag = sub.overflowed a, b\n  %flag.pair = call { i8, i1 } @llvm.usub.with.overflow.i8(i8 %a, i8 %b)\n  %flag.overflow = extractvalue { i8, i1 } %flag.pair, 1\n  %flag = zext i1 %flag.overflow to i8\n",
+            "_panic4:\n  ; attempt to divide by zero in 'quotient'\n  ret i32 519\n",
+            "declare { i8, i1 } @llvm.umul.with.overflow.i8(i8, i8)\n",
+        ]);
+    }
+
+    // Runs an LLVM tool on a module, if it is installed. Returns its standard output, None if it is not installed.
+    fn run_llvm_tool(tool: &str, args: &[&str], module: &str) -> Option<String> {
+        use std::io::Write;
+        let mut child = Command::new(tool)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .ok()?;
+        child.stdin.take().unwrap().write_all(module.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{} failed: {}\n{}", tool, String::from_utf8_lossy(&output.stderr), module);
+        Some(String::from_utf8(output.stdout).unwrap())
+    }
+
+    // The differential programs in a single module, whose intrinsics are declared once
+    fn llvm_module() -> String {
+        let mut definitions = String::new();
+        let mut declarations = BTreeSet::new();
+        for (function, (_, overflow)) in differential_functions().iter().zip(differential_programs()) {
+            for line in llvm::emit(function, overflow).lines() {
+                if line.starts_with("declare ") {
+                    declarations.insert(line.to_string());
+                } else {
+                    definitions += line;
+                    definitions += "\n";
+                }
+            }
+        }
+        definitions + &declarations.into_iter().collect::<Vec<String>>().join("\n")
+    }
+
+    // The modules are valid for llvm-as and opt, when they are installed
+    #[test]
+    fn test_llvm_ir_is_valid() {
+        let module = llvm_module();
+        if run_llvm_tool("llvm-as", &["-o", "/dev/null"], &module).is_none() {
+            eprintln!("llvm-as is not installed, skipping");
+        }
+        if run_llvm_tool("opt", &["-verify", "-S", "-o", "/dev/null"], &module).is_none() {
+            eprintln!("opt is not installed, skipping");
+        }
+    }
+
+    // Programs compiled by llc compute the same bindings as the interpreter, and panic in the same bindings
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn test_llvm_ir_matches_interpreter() {
+        let Some(assembly) = run_llvm_tool("llc", &["-O1", "-o", "-"], &llvm_module()) else {
+            eprintln!("llc is not installed, skipping");
+            return;
+        };
+        assert_hashes(&harness_output(&[("program.s", &assembly), ("harness.c", &status_harness(&differential_functions()))]));
+    }
+
+    #[test]
+    fn test_live_intervals() {
+        let source = std::fs::read_to_string(format!("{}run/program.rs", SNIPPET_PATH)).unwrap();
+        let function = lower(&parse(&source).unwrap());
+        let interval = |variable: Variable, start, end| regalloc::Interval { variable, start, end };
+        assert_eq!(regalloc::live_intervals(&function), vec![
+            interval(Variable::Named("a".to_string()), 0, 5),
+            interval(Variable::Named("b".to_string()), 0, 5),
+            interval(Variable::Named("sum".to_string()), 1, 2),
+            interval(Variable::Named("product".to_string()), 2, 6),
+            interval(Variable::Temporary(0), 3, 4),
+            interval(Variable::Temporary(1), 6, 8),
+            interval(Variable::Temporary(2), 7, 8),
+        ]);
+
+        // A register is free again once the last read of its variable is done, and when none is
+        // free the interval that ends last is spilled: product outlives %0
+        let allocation = regalloc::allocate(&function, &["r0", "r1", "r2"]);
+        let named = |name: &str| Variable::Named(name.to_string());
+        assert_eq!(allocation.register(&named("sum")), Some("r2"));
+        assert_eq!(allocation.locations[&named("product")], regalloc::Location::Spilled);
+        assert_eq!(allocation.register(&Variable::Temporary(0)), Some("r2"));
+        assert_eq!(allocation.register(&Variable::Temporary(1)), Some("r0"));
+        assert_eq!(allocation.used_registers(&["r2", "r3", "r0"]), vec!["r2", "r0"]);
+        // Bindings that are never read have no interval
+        assert_eq!(allocation.locations.get(&named("quotient")), None);
+
+        runs_ok_with("regalloc/live.rs", &["--emit=asm", "--print-intervals"], &[
+            "Live intervals:\n\na   [0, 6]    r10\nb   [0, 7]    rbx\nc   [1, 9]    r12\n",
+            "g   [5, 12]   spilled\nh   [6, 13]   r10\n",
+        ]);
+        runs_ok_with("regalloc/live.rs", &["--emit=riscv", "--print-intervals"], &["g   [5, 12]   s4\n", "j   [8, 15]   s5\n"]);
+        runs_err_with("regalloc/live.rs", &["--emit=c", "--print-intervals"], &["--print-intervals needs --emit=asm or --emit=riscv."]);
+    }
+
+    // Programs with more live variables than registers spill some of them, and restore the saved registers they use
+    #[test]
+    fn test_register_allocation() {
+        let source = std::fs::read_to_string(format!("{}regalloc/live.rs", SNIPPET_PATH)).unwrap();
+        let program = parse(&source).unwrap();
+        let function = lower(&program);
+        for overflow in [OverflowMode::Panic, OverflowMode::Wrap] {
+            let mut harness = Harness::new(&riscv::emit(&function, overflow));
+            for b in (0..=255).step_by(7) {
+                for a in 0..=255 {
+                    let arguments = BTreeMap::from([("a".to_string(), a), ("b".to_string(), b)]);
+                    let (status, values) = harness.call(&[a, b]).unwrap();
+                    let (expected, bindings) = expected_status(&program, overflow, &arguments);
+                    assert_eq!(status, expected, "{:?} mode on {:?}", overflow, arguments);
+                    if status == 0 {
+                        assert_eq!(values, bindings, "{:?} mode on {:?}", overflow, arguments);
+                    }
+                }
+            }
+
+            #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+            {
+                let assembly = x86_64::emit(&function, overflow);
+                assert!(assembly.contains("\tpushq\t%r15\n"));
+                let output = harness_output(&[("program.s", &assembly), ("harness.c", &status_harness(std::slice::from_ref(&function)))]);
+                assert_eq!(output, format!("{}\n", expected_hash(&program, overflow)), "{:?} mode", overflow);
+            }
+        }
+    }
+
+    #[test]
+    fn test_emit_r1cs() {
+        runs_ok_with("run/program.rs", &["--emit=r1cs", "--overflow=panic"], &[
+            "R1CS:\n\n{\n  \"name\": \"main\",\n  \"field\": \"18446744069414584321\",\n  \"wires\": [\"one\", \"a\", \"b\", \"a.bit0\", ",
+            "  \"inputs\": [1, 2],\n",
+            "{\"binding\": \"sum\", \"wire\": 28}, ",
+            // sum = a + b, with the carry in the ninth bit
+            "    {\"a\": {\"19\": \"1\", \"20\": \"2\", \"21\": \"4\", \"22\": \"8\", \"23\": \"16\", \"24\": \"32\", \"25\": \"64\", \"26\": \"128\", \"27\": \"256\"}, \"b\": {\"0\": \"1\"}, \"c\": {\"1\": \"1\", \"2\": \"1\"}},\n",
+            "    {\"a\": {\"27\": \"1\"}, \"b\": {\"0\": \"1\"}, \"c\": {}, \"panic\": {\"binding\": \"sum\", \"message\": \"attempt to add with overflow\"}},\n",
+            "\"panic\": {\"binding\": \"quotient\", \"message\": \"attempt to divide by zero\"}},\n",
+            "}\n  ]\n}\n",
+        ]);
+    }
+
+    // Every program of the differential tests, and operations on constants, which are folded into the circuit
+    #[test]
+    fn test_r1cs_matches_interpreter() {
+        let mut programs = differential_programs();
+        let constants = "let r = a * 3u8;\nlet s = 200u8 >> b;\nlet t = 7u8 / 2u8 + r;\nlet u = 5u8 ^ a;\nlet v = a.overflowing_sub(255u8).1 as u8;";
+        programs.push((constants.to_string(), OverflowMode::Panic));
+        programs.push((constants.to_string(), OverflowMode::Wrap));
+        for (index, (body, overflow)) in programs.iter().enumerate() {
+            let program = parse(&format!("fn f{}(a: u8, b: u8) {{\n{}\n}}", index, body)).unwrap();
+            let system = circuit::compile(&program, *overflow);
+            for b in (0..=16).chain((17..=255).step_by(13)).chain([255]) {
+                for a in (0..=255).step_by(3).chain([254, 255]) {
+                    let arguments = BTreeMap::from([("a".to_string(), a), ("b".to_string(), b)]);
+                    let expected = Interpreter::new(*overflow).run(&program, &arguments);
+                    assert_eq!(witness::run_named(&system, &arguments), expected, "{} in {:?} mode on {:?}", body.trim(), overflow, arguments);
+                }
+            }
+        }
+
+        // A panic on constants is a constraint that nothing satisfies
+        let program = parse("fn main(a: u8) {\n    let x = a + 1u8;\n    let y = x / 0u8;\n}").unwrap();
+        let system = circuit::compile(&program, OverflowMode::Panic);
+        assert_eq!(
+            witness::generate(&system, &[3]),
+            Err(RuntimeError::Panic { panic: Panic::DivisionByZero, variable: "y".to_string() })
+        );
+    }
+
+    // The hints are not trusted: a witness with other values for them does not satisfy the constraints
+    #[test]
+    fn test_r1cs_constraints() {
+        let program = parse("fn main(a: u8, b: u8) {\n    let q = a / b;\n    let s = a.wrapping_add(b);\n}").unwrap();
+        let system = circuit::compile(&program, OverflowMode::Panic);
+        let wire = |name: &str| system.wires.iter().position(|wire| wire == name).unwrap();
+        let mut values = witness::generate(&system, &[200, 100]).unwrap();
+        assert_eq!(system.check(&values), Ok(()));
+        assert_eq!(values[wire("q")], Field::new(2));
+        assert_eq!(values[wire("s")], Field::new(44));
+
+        // 200 = 1 * 100 + 100, but the remainder is not below the divisor
+        let mut tampered = values.clone();
+        tampered[wire("q.quotient")] = Field::new(1);
+        tampered[wire("q.remainder")] = Field::new(100);
+        assert!(system.check(&tampered).is_err());
+        // 300 = 256 + 44 without the carry, if the lowest bit could be 256
+        values[wire("s.exact.bit8")] = Field::ZERO;
+        values[wire("s.exact.bit0")] = Field::new(256);
+        values[wire("s")] = Field::new(300);
+        assert!(system.check(&values).is_err());
+
+        // Inputs are range checked
+        let mut values = witness::generate(&system, &[0, 1]).unwrap();
+        values[wire("a")] = Field::new(256);
+        assert!(system.check(&values).is_err());
+    }
+
+    #[test]
+    fn test_run_r1cs() {
+        let output = run_interpreter("run/program.rs", &["--r1cs", "--arg", "a=3", "--arg", "b=7"]);
+        assert!(output.status.success(), "Failed: {:?}", output);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "sum = 10\nproduct = 20\nquotient = 5\nflag = 1\nmasked = 20\n"
+        );
+
+        let output = run_interpreter("run/program.rs", &["--r1cs", "--arg", "a=3", "--arg", "b=3", "--overflow=wrap"]);
+        assert_eq!(output.status.code(), Some(101));
+        assert!(String::from_utf8(output.stderr).unwrap().contains("initializer of 'quotient':\nattempt to divide by zero"));
+    }
+
+    #[test]
+    fn test_emit_verilog() {
+        runs_ok_with("verilog/ports.rs", &["--emit=verilog", "--output", "overflow", "--output", "shifted"], &[
+            "Verilog:\n\n// fn main(input: u8, b: u8)\nmodule rustic_main (\n    input wire [7:0] _input,\n    input wire [7:0] b,\n    output wire [7:0] _overflow,\n    output wire [7:0] shifted,\n    output wire overflow\n);\n",
+            "\n    // overflow = add.overflowed input, b\n    and (_n[0], _input[0], b[0]);\n    xor (_n[1], _input[1], b[1]);\n",
+            "\n    // wire = xor input, b\n    xor (",
+            // Shifting by a constant only moves wires
+            "    assign shifted = {_n[",
+            "], 1'b0, 1'b0, 1'b0};\n    assign overflow = 1'b0;\nendmodule\n",
+        ]);
+        runs_err_with("verilog/ports.rs", &["--emit=verilog", "--output", "sum"], &["There is no binding named 'sum'."]);
+    }
+
+    // The expected outputs of a netlist that outputs every binding: the bindings, or the overflow flag if the program panics
+    fn expected_netlist_outputs(program: &Program, overflow: OverflowMode, inputs: &[u8]) -> (Vec<u8>, bool) {
+        let arguments = BTreeMap::from_iter(program.inputs.iter().map(|input| input.name.clone()).zip(inputs.iter().copied()));
+        match Interpreter::new(overflow).run(program, &arguments) {
+            Ok(bindings) => (bindings.into_iter().map(|(_, val)| val).collect(), false),
+            Err(RuntimeError::Panic { .. }) => (Vec::new(), true),
+            Err(runtime_error) => panic!("{:?}", runtime_error),
+        }
+    }
+
+    #[test]
+    fn test_netlist_matches_interpreter() {
+        let mut programs = differential_programs();
+        let constants = "let r = a * 3u8;\nlet s = 200u8 >> b;\nlet t = 7u8 / 2u8 + r;\nlet u = 5u8 ^ a;\nlet v = a.overflowing_sub(255u8).1 as u8;";
+        programs.push((constants.to_string(), OverflowMode::Panic));
+        programs.push((constants.to_string(), OverflowMode::Wrap));
+        for (index, (body, overflow)) in programs.iter().enumerate() {
+            let program = parse(&format!("fn f{}(a: u8, b: u8) {{\n{}\n}}", index, body)).unwrap();
+            let netlist = netlist::synthesize(&program, *overflow);
+            for b in (0..=16).chain((17..=255).step_by(7)).chain([255]) {
+                for a in 0..=255 {
+                    let (bindings, panics) = expected_netlist_outputs(&program, *overflow, &[a, b]);
+                    let (outputs, flag) = netlist.evaluate(&[a, b]);
+                    assert_eq!(flag, panics, "{} in {:?} mode on a = {}, b = {}", body.trim(), overflow, a, b);
+                    if !panics {
+                        assert_eq!(outputs, bindings, "{} in {:?} mode on a = {}, b = {}", body.trim(), overflow, a, b);
+                    }
+                }
+            }
+        }
+    }
+
+    // Runs the gate primitives of a Verilog module, which only reads its own output. Returns the value of every output port.
+    fn simulate_verilog(module: &str, inputs: &[u8]) -> BTreeMap<String, u32> {
+        let ports: Vec<&str> = module.lines().filter_map(|line| line.strip_prefix("    input wire [7:0] ")).map(|port| port.trim_end_matches(',')).collect();
+        let mut nets: Vec<bool> = Vec::new();
+        let read = |nets: &Vec<bool>, signal: &str| -> bool {
+            match signal {
+                "1'b0" => false,
+                "1'b1" => true,
+                _ => {
+                    let (name, index) = signal.trim_end_matches(']').split_once('[').unwrap();
+                    let index: usize = index.parse().unwrap();
+                    match ports.iter().position(|port| *port == name) {
+                        Some(input) => inputs[input] >> index & 1 == 1,
+                        None => nets[index],
+                    }
+                }
+            }
+        };
+
+        let mut outputs = BTreeMap::new();
+        for line in module.lines().map(str::trim) {
+            if let Some((gate, operands)) = line.strip_suffix(");").and_then(|line| line.split_once(" (")) {
+                let operands: Vec<&str> = operands.split(", ").collect();
+                assert_eq!(operands[0], format!("_n[{}]", nets.len()), "{}", line);
+                let val = match gate {
+                    "and" => read(&nets, operands[1]) && read(&nets, operands[2]),
+                    "or" => read(&nets, operands[1]) || read(&nets, operands[2]),
+                    "xor" => read(&nets, operands[1]) ^ read(&nets, operands[2]),
+                    "not" => !read(&nets, operands[1]),
+                    _ => panic!("unknown gate {}", line),
+                };
+                nets.push(val);
+            } else if let Some((port, value)) = line.strip_prefix("assign ").and_then(|line| line.strip_suffix(';')).and_then(|line| line.split_once(" = ")) {
+                let bits: Vec<&str> = value.trim_matches(|c| c == '{' || c == '}').split(", ").collect();
+                let val = bits.iter().fold(0, |val, bit| val << 1 | read(&nets, bit) as u32);
+                outputs.insert(port.to_string(), val);
+            }
+        }
+        outputs
+    }
+
+    // The printed module computes what the netlist does
+    #[test]
+    fn test_verilog_matches_netlist() {
+        for file in ["run/program.rs", "verilog/ports.rs", "regalloc/live.rs"] {
+            let source = std::fs::read_to_string(format!("{}{}", SNIPPET_PATH, file)).unwrap();
+            let program = parse(&source).unwrap();
+            for overflow in [OverflowMode::Panic, OverflowMode::Wrap, OverflowMode::Saturate] {
+                let mut netlist: Netlist = netlist::synthesize(&program, overflow);
+                let bindings: Vec<String> = netlist.outputs.iter().map(|(binding, _)| binding.clone()).collect();
+                // Keeping every other binding drops some gates
+                let kept: Vec<String> = bindings.iter().step_by(2).cloned().collect();
+                netlist.select(&kept);
+                assert_eq!(netlist.outputs.len(), kept.len());
+                let module = verilog::emit(&netlist);
+                let full = netlist::synthesize(&program, overflow);
+
+                for b in (0..=255).step_by(17) {
+                    for a in (0..=255).step_by(5) {
+                        let (expected, panics) = full.evaluate(&[a, b]);
+                        let (outputs, flag) = netlist.evaluate(&[a, b]);
+                        let simulated = simulate_verilog(&module, &[a, b]);
+                        assert_eq!((flag, simulated["overflow"]), (panics, panics as u32), "{} on a = {}, b = {}", file, a, b);
+                        for (binding, val) in bindings.iter().zip(expected) {
+                            if let Some(index) = kept.iter().position(|kept| kept == binding) {
+                                assert_eq!(outputs[index], val, "{} on a = {}, b = {}", binding, a, b);
+                                let port = if binding == "overflow" { "_overflow" } else { binding.as_str() };
+                                assert_eq!(simulated[port], val as u32, "{} on a = {}, b = {}\n{}", binding, a, b, module);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}